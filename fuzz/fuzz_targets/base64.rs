@@ -0,0 +1,8 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use crystals_kyber::fuzzing;
+
+fuzz_target!(|data: &[u8]| {
+    fuzzing::base64(data);
+});
@@ -0,0 +1,13 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use crystals_kyber::fuzzing;
+use crystals_kyber::hybrid::HybridSecretKey;
+
+thread_local! {
+    static SECRET_KEY: HybridSecretKey = fuzzing::decryption_key();
+}
+
+fuzz_target!(|data: &[u8]| {
+    SECRET_KEY.with(|sk| fuzzing::decrypt(sk, data));
+});
@@ -0,0 +1,447 @@
+//! The key exchange protocols of the Kyber paper, built on Kyber.KEM:
+//!
+//! - KE, unauthenticated: the initiator sends an ephemeral public key, the responder
+//!   encapsulates a key to it.
+//! - UAKE, unilaterally authenticated: additionally, the initiator encapsulates a key to
+//!   the static public key of the responder, so only the owner of that key can compute
+//!   the session key.
+//! - AKE, mutually authenticated: additionally, the responder encapsulates a key to the
+//!   static public key of the initiator.
+//!
+//! Each party is a state machine whose methods consume the current state, so messages
+//! can only be processed in the right order. The session key is derived with SHAKE-256
+//! from all encapsulated keys and the transcript, i.e. the static public keys and the
+//! encoded messages. A tampered message therefore leads to different session keys, which
+//! the parties detect as soon as they use them.
+
+use super::kyber::{ PublicKey, Ciphertext, Seed, PUBLIC_KEY_BYTES, CIPHERTEXT_BYTES, expand_randomness_shake_256 };
+use super::kem;
+use super::kem::{ KemSecretKey, SharedSecret };
+use super::io;
+use super::io::ValidationError;
+
+use sha3::digest::{ ExtendableOutput, Input, XofReader };
+
+pub type SessionKey = [u8; 32];
+
+const KE_LABEL: &[u8] = b"Kyber KE";
+const UAKE_LABEL: &[u8] = b"Kyber UAKE";
+const AKE_LABEL: &[u8] = b"Kyber AKE";
+
+/// The data that enters the session key; the parts are fixed-size encodings, so their
+/// concatenation is unambiguous
+struct Transcript
+{
+    hasher: sha3::Shake256
+}
+
+impl Transcript
+{
+    fn new(label: &[u8]) -> Transcript
+    {
+        let mut hasher = sha3::Shake256::default();
+        hasher.input(label);
+        return Transcript { hasher: hasher };
+    }
+
+    fn append(mut self, data: &[u8]) -> Transcript
+    {
+        self.hasher.input(data);
+        return self;
+    }
+
+    fn session_key(self, shared_secrets: &[&SharedSecret]) -> SessionKey
+    {
+        let mut hasher = self.hasher;
+        for secret in shared_secrets {
+            hasher.input(secret);
+        }
+        let mut result = [0; 32];
+        hasher.xof_result().read(&mut result);
+        return result;
+    }
+}
+
+/// Derives the seeds for the ephemeral key pair and for one encapsulation
+fn expand_seed(seed: Seed) -> (Seed, Seed, Seed)
+{
+    let mut random = expand_randomness_shake_256(seed);
+    let mut matrix_seed: Seed = [0; 32];
+    let mut secret_seed: Seed = [0; 32];
+    let mut encapsulation_seed: Seed = [0; 32];
+    random.read(&mut matrix_seed);
+    random.read(&mut secret_seed);
+    random.read(&mut encapsulation_seed);
+    return (matrix_seed, secret_seed, encapsulation_seed);
+}
+
+fn check_length(data: &[u8], expected: usize) -> Result<(), ValidationError>
+{
+    if data.len() != expected {
+        return Err(ValidationError::InvalidLength { expected: expected, actual: data.len() });
+    }
+    return Ok(());
+}
+
+/// First message of KE: the ephemeral public key of the initiator
+#[derive(Debug, Clone)]
+pub struct KeInitMessage
+{
+    pub ephemeral_pk: PublicKey
+}
+
+/// Second message of KE and UAKE: the key encapsulated to the ephemeral public key
+#[derive(Debug, Clone)]
+pub struct ResponseMessage
+{
+    pub ciphertext: Ciphertext
+}
+
+/// First message of UAKE and AKE: the ephemeral public key of the initiator and the
+/// key encapsulated to the static public key of the responder
+#[derive(Debug, Clone)]
+pub struct AuthInitMessage
+{
+    pub ephemeral_pk: PublicKey,
+    pub ciphertext: Ciphertext
+}
+
+/// Second message of AKE: the keys encapsulated to the ephemeral and to the static
+/// public key of the initiator
+#[derive(Debug, Clone)]
+pub struct AkeResponseMessage
+{
+    pub ciphertext: Ciphertext,
+    pub static_ciphertext: Ciphertext
+}
+
+impl KeInitMessage
+{
+    pub const BYTES: usize = PUBLIC_KEY_BYTES;
+
+    pub fn to_bytes(&self) -> Vec<u8>
+    {
+        io::pk_to_bytes(&self.ephemeral_pk)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<KeInitMessage, ValidationError>
+    {
+        Ok(KeInitMessage { ephemeral_pk: io::validate_public_key(data)? })
+    }
+}
+
+impl ResponseMessage
+{
+    pub const BYTES: usize = CIPHERTEXT_BYTES;
+
+    pub fn to_bytes(&self) -> Vec<u8>
+    {
+        io::ciphertext_to_bytes(&self.ciphertext)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<ResponseMessage, ValidationError>
+    {
+        Ok(ResponseMessage { ciphertext: io::validate_ciphertext(data)? })
+    }
+}
+
+impl AuthInitMessage
+{
+    pub const BYTES: usize = PUBLIC_KEY_BYTES + CIPHERTEXT_BYTES;
+
+    pub fn to_bytes(&self) -> Vec<u8>
+    {
+        let mut result = io::pk_to_bytes(&self.ephemeral_pk);
+        result.extend_from_slice(&io::ciphertext_to_bytes(&self.ciphertext));
+        return result;
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<AuthInitMessage, ValidationError>
+    {
+        check_length(data, Self::BYTES)?;
+        let (pk_part, ciphertext_part) = data.split_at(PUBLIC_KEY_BYTES);
+        return Ok(AuthInitMessage {
+            ephemeral_pk: io::validate_public_key(pk_part)?,
+            ciphertext: io::validate_ciphertext(ciphertext_part)?
+        });
+    }
+}
+
+impl AkeResponseMessage
+{
+    pub const BYTES: usize = 2 * CIPHERTEXT_BYTES;
+
+    pub fn to_bytes(&self) -> Vec<u8>
+    {
+        let mut result = io::ciphertext_to_bytes(&self.ciphertext);
+        result.extend_from_slice(&io::ciphertext_to_bytes(&self.static_ciphertext));
+        return result;
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<AkeResponseMessage, ValidationError>
+    {
+        check_length(data, Self::BYTES)?;
+        let (first, second) = data.split_at(CIPHERTEXT_BYTES);
+        return Ok(AkeResponseMessage {
+            ciphertext: io::validate_ciphertext(first)?,
+            static_ciphertext: io::validate_ciphertext(second)?
+        });
+    }
+}
+
+/// The initiator of KE, waiting for the response
+pub struct KeInitiator
+{
+    ephemeral_sk: KemSecretKey,
+    transcript: Transcript
+}
+
+impl KeInitiator
+{
+    pub fn start(seed: Seed) -> (KeInitiator, KeInitMessage)
+    {
+        let (matrix_seed, secret_seed, _) = expand_seed(seed);
+        let (ephemeral_sk, ephemeral_pk) = kem::key_gen(matrix_seed, secret_seed);
+        let message = KeInitMessage { ephemeral_pk: ephemeral_pk };
+        let transcript = Transcript::new(KE_LABEL).append(&message.to_bytes());
+        return (KeInitiator { ephemeral_sk: ephemeral_sk, transcript: transcript }, message);
+    }
+
+    pub fn finish(self, response: &ResponseMessage) -> SessionKey
+    {
+        let key = kem::decapsulate(&self.ephemeral_sk, &response.ciphertext);
+        self.transcript.append(&response.to_bytes()).session_key(&[&key])
+    }
+}
+
+/// The responder of KE, which needs no state
+pub struct KeResponder;
+
+impl KeResponder
+{
+    pub fn respond(message: &KeInitMessage, seed: Seed) -> (ResponseMessage, SessionKey)
+    {
+        let (_, _, encapsulation_seed) = expand_seed(seed);
+        let (key, ciphertext) = kem::encapsulate(&message.ephemeral_pk, encapsulation_seed);
+        let response = ResponseMessage { ciphertext: ciphertext };
+        let session_key = Transcript::new(KE_LABEL).append(&message.to_bytes()).append(&response.to_bytes()).session_key(&[&key]);
+        return (response, session_key);
+    }
+}
+
+/// The initiator of UAKE, which knows the static public key of the responder
+pub struct UakeInitiator
+{
+    ephemeral_sk: KemSecretKey,
+    static_key: SharedSecret,
+    transcript: Transcript
+}
+
+impl UakeInitiator
+{
+    pub fn start(responder_pk: &PublicKey, seed: Seed) -> (UakeInitiator, AuthInitMessage)
+    {
+        let (matrix_seed, secret_seed, encapsulation_seed) = expand_seed(seed);
+        let (ephemeral_sk, ephemeral_pk) = kem::key_gen(matrix_seed, secret_seed);
+        let (static_key, ciphertext) = kem::encapsulate(responder_pk, encapsulation_seed);
+        let message = AuthInitMessage { ephemeral_pk: ephemeral_pk, ciphertext: ciphertext };
+        let transcript = Transcript::new(UAKE_LABEL).append(&io::pk_to_bytes(responder_pk)).append(&message.to_bytes());
+        return (UakeInitiator { ephemeral_sk: ephemeral_sk, static_key: static_key, transcript: transcript }, message);
+    }
+
+    pub fn finish(self, response: &ResponseMessage) -> SessionKey
+    {
+        let key = kem::decapsulate(&self.ephemeral_sk, &response.ciphertext);
+        self.transcript.append(&response.to_bytes()).session_key(&[&key, &self.static_key])
+    }
+}
+
+/// The responder of UAKE, holding its static secret key
+pub struct UakeResponder<'a>
+{
+    sk: &'a KemSecretKey
+}
+
+impl<'a> UakeResponder<'a>
+{
+    pub fn new(sk: &'a KemSecretKey) -> UakeResponder<'a>
+    {
+        UakeResponder { sk: sk }
+    }
+
+    pub fn respond(&self, message: &AuthInitMessage, seed: Seed) -> (ResponseMessage, SessionKey)
+    {
+        let (_, _, encapsulation_seed) = expand_seed(seed);
+        let static_key = kem::decapsulate(self.sk, &message.ciphertext);
+        let (key, ciphertext) = kem::encapsulate(&message.ephemeral_pk, encapsulation_seed);
+        let response = ResponseMessage { ciphertext: ciphertext };
+        let session_key = Transcript::new(UAKE_LABEL).append(&io::pk_to_bytes(&self.sk.1))
+            .append(&message.to_bytes()).append(&response.to_bytes()).session_key(&[&key, &static_key]);
+        return (response, session_key);
+    }
+}
+
+/// The initiator of AKE, holding its static secret key and knowing the static public
+/// key of the responder
+pub struct AkeInitiator<'a>
+{
+    sk: &'a KemSecretKey,
+    ephemeral_sk: KemSecretKey,
+    static_key: SharedSecret,
+    transcript: Transcript
+}
+
+impl<'a> AkeInitiator<'a>
+{
+    pub fn start(sk: &'a KemSecretKey, responder_pk: &PublicKey, seed: Seed) -> (AkeInitiator<'a>, AuthInitMessage)
+    {
+        let (matrix_seed, secret_seed, encapsulation_seed) = expand_seed(seed);
+        let (ephemeral_sk, ephemeral_pk) = kem::key_gen(matrix_seed, secret_seed);
+        let (static_key, ciphertext) = kem::encapsulate(responder_pk, encapsulation_seed);
+        let message = AuthInitMessage { ephemeral_pk: ephemeral_pk, ciphertext: ciphertext };
+        let transcript = Transcript::new(AKE_LABEL).append(&io::pk_to_bytes(&sk.1)).append(&io::pk_to_bytes(responder_pk))
+            .append(&message.to_bytes());
+        return (AkeInitiator { sk: sk, ephemeral_sk: ephemeral_sk, static_key: static_key, transcript: transcript }, message);
+    }
+
+    pub fn finish(self, response: &AkeResponseMessage) -> SessionKey
+    {
+        let key = kem::decapsulate(&self.ephemeral_sk, &response.ciphertext);
+        let initiator_static_key = kem::decapsulate(self.sk, &response.static_ciphertext);
+        self.transcript.append(&response.to_bytes()).session_key(&[&key, &initiator_static_key, &self.static_key])
+    }
+}
+
+/// The responder of AKE, holding its static secret key and knowing the static public
+/// key of the initiator
+pub struct AkeResponder<'a>
+{
+    sk: &'a KemSecretKey,
+    initiator_pk: &'a PublicKey
+}
+
+impl<'a> AkeResponder<'a>
+{
+    pub fn new(sk: &'a KemSecretKey, initiator_pk: &'a PublicKey) -> AkeResponder<'a>
+    {
+        AkeResponder { sk: sk, initiator_pk: initiator_pk }
+    }
+
+    pub fn respond(&self, message: &AuthInitMessage, seed: Seed) -> (AkeResponseMessage, SessionKey)
+    {
+        let (_, static_seed, encapsulation_seed) = expand_seed(seed);
+        let responder_static_key = kem::decapsulate(self.sk, &message.ciphertext);
+        let (key, ciphertext) = kem::encapsulate(&message.ephemeral_pk, encapsulation_seed);
+        let (initiator_static_key, static_ciphertext) = kem::encapsulate(self.initiator_pk, static_seed);
+        let response = AkeResponseMessage { ciphertext: ciphertext, static_ciphertext: static_ciphertext };
+        let session_key = Transcript::new(AKE_LABEL).append(&io::pk_to_bytes(self.initiator_pk)).append(&io::pk_to_bytes(&self.sk.1))
+            .append(&message.to_bytes()).append(&response.to_bytes())
+            .session_key(&[&key, &initiator_static_key, &responder_static_key]);
+        return (response, session_key);
+    }
+}
+
+#[cfg(test)]
+use super::kyber::test_seed;
+
+/// Flips one bit of the encoded message, which yields another valid message
+#[cfg(test)]
+fn tamper(mut data: Vec<u8>, index: usize) -> Vec<u8>
+{
+    data[index] ^= 0x04;
+    return data;
+}
+
+#[test]
+fn test_ke() {
+    let (initiator, message) = KeInitiator::start(test_seed(1));
+    let message = KeInitMessage::from_bytes(&message.to_bytes()).unwrap();
+    let (response, responder_key) = KeResponder::respond(&message, test_seed(2));
+    let response = ResponseMessage::from_bytes(&response.to_bytes()).unwrap();
+    assert_eq!(responder_key, initiator.finish(&response));
+}
+
+#[test]
+fn test_ke_tampered() {
+    let (initiator, message) = KeInitiator::start(test_seed(1));
+    let tampered = KeInitMessage::from_bytes(&tamper(message.to_bytes(), 100)).unwrap();
+    let (response, responder_key) = KeResponder::respond(&tampered, test_seed(2));
+    assert_ne!(responder_key, initiator.finish(&response));
+
+    let (initiator, message) = KeInitiator::start(test_seed(1));
+    let (response, responder_key) = KeResponder::respond(&message, test_seed(2));
+    let tampered = ResponseMessage::from_bytes(&tamper(response.to_bytes(), 7)).unwrap();
+    assert_ne!(responder_key, initiator.finish(&tampered));
+}
+
+#[test]
+fn test_uake() {
+    let (responder_sk, responder_pk) = kem::key_gen(test_seed(10), test_seed(11));
+    let responder = UakeResponder::new(&responder_sk);
+    let (initiator, message) = UakeInitiator::start(&responder_pk, test_seed(1));
+    let (response, responder_key) = responder.respond(&AuthInitMessage::from_bytes(&message.to_bytes()).unwrap(), test_seed(2));
+    assert_eq!(responder_key, initiator.finish(&ResponseMessage::from_bytes(&response.to_bytes()).unwrap()));
+
+    // both the ephemeral public key and the ciphertext are protected
+    for index in &[20, PUBLIC_KEY_BYTES + 20] {
+        let (initiator, message) = UakeInitiator::start(&responder_pk, test_seed(1));
+        let tampered = AuthInitMessage::from_bytes(&tamper(message.to_bytes(), *index)).unwrap();
+        let (response, responder_key) = responder.respond(&tampered, test_seed(2));
+        assert_ne!(responder_key, initiator.finish(&response));
+    }
+    let (initiator, message) = UakeInitiator::start(&responder_pk, test_seed(1));
+    let (response, responder_key) = responder.respond(&message, test_seed(2));
+    assert_ne!(responder_key, initiator.finish(&ResponseMessage::from_bytes(&tamper(response.to_bytes(), 300)).unwrap()));
+}
+
+#[test]
+fn test_uake_wrong_responder() {
+    let (_, responder_pk) = kem::key_gen(test_seed(10), test_seed(11));
+    let (impostor_sk, _) = kem::key_gen(test_seed(10), test_seed(12));
+    let (initiator, message) = UakeInitiator::start(&responder_pk, test_seed(1));
+    let (response, impostor_key) = UakeResponder::new(&impostor_sk).respond(&message, test_seed(2));
+    assert_ne!(impostor_key, initiator.finish(&response));
+}
+
+#[test]
+fn test_ake() {
+    let (initiator_sk, initiator_pk) = kem::key_gen(test_seed(20), test_seed(21));
+    let (responder_sk, responder_pk) = kem::key_gen(test_seed(10), test_seed(11));
+    let responder = AkeResponder::new(&responder_sk, &initiator_pk);
+    let (initiator, message) = AkeInitiator::start(&initiator_sk, &responder_pk, test_seed(1));
+    let (response, responder_key) = responder.respond(&AuthInitMessage::from_bytes(&message.to_bytes()).unwrap(), test_seed(2));
+    let initiator_key = initiator.finish(&AkeResponseMessage::from_bytes(&response.to_bytes()).unwrap());
+    assert_eq!(responder_key, initiator_key);
+
+    for index in &[20, PUBLIC_KEY_BYTES + 20] {
+        let (initiator, message) = AkeInitiator::start(&initiator_sk, &responder_pk, test_seed(1));
+        let tampered = AuthInitMessage::from_bytes(&tamper(message.to_bytes(), *index)).unwrap();
+        let (response, responder_key) = responder.respond(&tampered, test_seed(2));
+        assert_ne!(responder_key, initiator.finish(&response));
+    }
+    for index in &[20, CIPHERTEXT_BYTES + 20] {
+        let (initiator, message) = AkeInitiator::start(&initiator_sk, &responder_pk, test_seed(1));
+        let (response, responder_key) = responder.respond(&message, test_seed(2));
+        let tampered = AkeResponseMessage::from_bytes(&tamper(response.to_bytes(), *index)).unwrap();
+        assert_ne!(responder_key, initiator.finish(&tampered));
+    }
+}
+
+#[test]
+fn test_ake_wrong_initiator() {
+    let (_, initiator_pk) = kem::key_gen(test_seed(20), test_seed(21));
+    let (impostor_sk, _) = kem::key_gen(test_seed(20), test_seed(22));
+    let (responder_sk, responder_pk) = kem::key_gen(test_seed(10), test_seed(11));
+    // the responder expects initiator_pk, but the impostor starts the exchange with its own key
+    let (initiator, message) = AkeInitiator::start(&impostor_sk, &responder_pk, test_seed(1));
+    let (response, responder_key) = AkeResponder::new(&responder_sk, &initiator_pk).respond(&message, test_seed(2));
+    assert_ne!(responder_key, initiator.finish(&response));
+}
+
+#[test]
+fn test_message_lengths() {
+    assert!(KeInitMessage::from_bytes(&[0; KeInitMessage::BYTES - 1]).is_err());
+    assert!(ResponseMessage::from_bytes(&[0; ResponseMessage::BYTES + 1]).is_err());
+    assert!(AuthInitMessage::from_bytes(&[0; AuthInitMessage::BYTES - 1]).is_err());
+    assert!(AkeResponseMessage::from_bytes(&[]).is_err());
+}
@@ -0,0 +1,380 @@
+//! Computes the decryption failure probability of a parameter set, following the analysis
+//! of the Kyber submission: The decryption v - s^T u of a ciphertext of m yields
+//! q/2 * m + (e + c_t)^T r - s^T (e1 + c_u) + e2 + c_v, where c_t, c_u and c_v are the
+//! errors introduced by compressing t, u and v. Assuming that these are distributed like
+//! the compression error of a uniform element of Zq and that all coefficients are
+//! independent, the distribution of the noise in one coefficient is computed exactly by
+//! convolution, and decryption fails if it is larger than q/4 in absolute value.
+//!
+//! As this relies on the assumptions above, there is also a Monte-Carlo mode, that measures
+//! the noise of actual encryptions of the implemented parameter set.
+
+use super::params::ParameterSet;
+use super::kyber::*;
+use super::zq::*;
+use super::ring::{ RqElementCompression, CompressedRq, N };
+
+use sha3::digest::{ ExtendableOutput, Input, XofReader };
+
+/// Probabilities below this are dropped at the ends of a distribution, which is far below
+/// the failure probabilities we are interested in (at least 2^-200)
+const PRUNE_THRESHOLD: f64 = 4.9e-91; // ~ 2^-300
+
+/// A probability distribution on a finite range of integers
+#[derive(Clone, Debug)]
+pub struct Distribution
+{
+    min: i64,
+    probabilities: Vec<f64>
+}
+
+impl Distribution
+{
+    pub fn constant(x: i64) -> Distribution
+    {
+        Distribution {
+            min: x,
+            probabilities: vec![1.]
+        }
+    }
+
+    /// The distribution of a - b for a, b each the sum of eta uniform bits
+    pub fn centered_binomial(eta: u32) -> Distribution
+    {
+        let mut binomial = vec![1.];
+        for _ in 0..(2 * eta) {
+            let mut next = vec![0.; binomial.len() + 1];
+            for i in 0..binomial.len() {
+                next[i] += binomial[i] / 2.;
+                next[i + 1] += binomial[i] / 2.;
+            }
+            binomial = next;
+        }
+        return Distribution {
+            min: -(eta as i64),
+            probabilities: binomial
+        };
+    }
+
+    /// The distribution of x - decompress(compress(x)), taking the representative in
+    /// -q/2..q/2, for x uniform in Zq and compression to d bits
+    pub fn compression_error(q: u32, d: u16) -> Distribution
+    {
+        let q = q as i64;
+        let d = d as u32;
+        let mut errors = Vec::with_capacity(q as usize);
+        for x in 0..q {
+            // the same rounding as in ZqElement::compress() and ZqElement::decompress()
+            let compressed = (((x << (d + 1)) + q) / (2 * q)) % (1 << d);
+            let decompressed = (((compressed * q) << 1) + (1 << d)) >> (d + 1);
+            let mut error = (x - decompressed) % q;
+            if error > q / 2 {
+                error -= q;
+            } else if error < -q / 2 {
+                error += q;
+            }
+            errors.push(error);
+        }
+        let min = *errors.iter().min().unwrap();
+        let max = *errors.iter().max().unwrap();
+        let mut probabilities = vec![0.; (max - min + 1) as usize];
+        for error in errors {
+            probabilities[(error - min) as usize] += 1. / q as f64;
+        }
+        return Distribution {
+            min: min,
+            probabilities: probabilities
+        };
+    }
+
+    #[allow(unused)]
+    pub fn probability(&self, x: i64) -> f64
+    {
+        if x < self.min || x - self.min >= self.probabilities.len() as i64 {
+            0.
+        } else {
+            self.probabilities[(x - self.min) as usize]
+        }
+    }
+
+    fn values<'a>(&'a self) -> impl 'a + Iterator<Item = (i64, f64)>
+    {
+        self.probabilities.iter().enumerate().map(move |(i, p)| (self.min + i as i64, *p))
+    }
+
+    pub fn variance(&self) -> f64
+    {
+        let mean: f64 = self.values().map(|(x, p)| x as f64 * p).sum();
+        return self.values().map(|(x, p)| (x as f64 - mean) * (x as f64 - mean) * p).sum();
+    }
+
+    /// Returns the probability that the absolute value is greater than the threshold
+    pub fn tail_probability(&self, threshold: i64) -> f64
+    {
+        self.values().filter(|(x, _)| x.abs() > threshold).map(|(_, p)| p).sum()
+    }
+
+    /// The distribution of X + Y for independent X, Y distributed like self and other
+    pub fn add(&self, other: &Distribution) -> Distribution
+    {
+        let mut probabilities = vec![0.; self.probabilities.len() + other.probabilities.len() - 1];
+        for (i, p) in self.probabilities.iter().enumerate() {
+            for (j, q) in other.probabilities.iter().enumerate() {
+                probabilities[i + j] += p * q;
+            }
+        }
+        return Distribution {
+            min: self.min + other.min,
+            probabilities: probabilities
+        }.pruned();
+    }
+
+    /// The distribution of X * Y for independent X, Y distributed like self and other
+    pub fn product(&self, other: &Distribution) -> Distribution
+    {
+        let products = || self.values().flat_map(move |(x, p)| other.values().map(move |(y, q)| (x * y, p * q)));
+        let min = products().map(|(z, _)| z).min().unwrap();
+        let max = products().map(|(z, _)| z).max().unwrap();
+        let mut probabilities = vec![0.; (max - min + 1) as usize];
+        for (z, p) in products() {
+            probabilities[(z - min) as usize] += p;
+        }
+        return Distribution {
+            min: min,
+            probabilities: probabilities
+        }.pruned();
+    }
+
+    /// The distribution of the sum of count independent variables distributed like self
+    pub fn sum(&self, mut count: usize) -> Distribution
+    {
+        let mut result = Distribution::constant(0);
+        let mut power = self.clone();
+        while count != 0 {
+            if count & 1 == 1 {
+                result = result.add(&power);
+            }
+            count = count >> 1;
+            if count != 0 {
+                power = power.add(&power);
+            }
+        }
+        return result;
+    }
+
+    fn pruned(mut self) -> Distribution
+    {
+        let start = self.probabilities.iter().position(|p| *p >= PRUNE_THRESHOLD).unwrap_or(0);
+        let end = self.probabilities.iter().rposition(|p| *p >= PRUNE_THRESHOLD).map(|i| i + 1).unwrap_or(1);
+        self.probabilities.truncate(end);
+        self.probabilities.drain(0..start);
+        self.min += start as i64;
+        return self;
+    }
+}
+
+/// The distribution of one coefficient of the noise of a decrypted ciphertext
+pub fn noise_distribution(params: &ParameterSet) -> Distribution
+{
+    rerandomized_noise_distribution(params, 0)
+}
+
+/// The distribution of one coefficient of the noise of a ciphertext that was re-randomized
+/// the given number of times using kyber::rerandomize(). Each re-randomization adds the
+/// noise of an encryption of zero and compresses u and v again, so the ciphertext has the
+/// noise of rerandomizations + 1 independent encryptions (ignoring that they share e and
+/// c_t).
+pub fn rerandomized_noise_distribution(params: &ParameterSet, rerandomizations: usize) -> Distribution
+{
+    let encryptions = rerandomizations + 1;
+    let error = Distribution::centered_binomial(params.eta);
+    let count = params.k * params.n * encryptions;
+    let t_part = error.add(&Distribution::compression_error(params.q, params.dt)).product(&error).sum(count);
+    let u_part = error.add(&Distribution::compression_error(params.q, params.du)).product(&error).sum(count);
+    let v_part = error.add(&Distribution::compression_error(params.q, params.dv)).sum(encryptions);
+    return t_part.add(&u_part).add(&v_part);
+}
+
+/// The probability that a single coefficient decrypts wrongly
+pub fn coefficient_failure_probability(params: &ParameterSet) -> f64
+{
+    noise_distribution(params).tail_probability(params.q as i64 / 4)
+}
+
+/// The probability that a ciphertext decrypts wrongly, using a union bound over its
+/// n coefficients
+pub fn failure_probability(params: &ParameterSet) -> f64
+{
+    rerandomized_failure_probability(params, 0)
+}
+
+/// The probability that a ciphertext decrypts wrongly after it was re-randomized the given
+/// number of times, using a union bound over its n coefficients
+pub fn rerandomized_failure_probability(params: &ParameterSet, rerandomizations: usize) -> f64
+{
+    let coefficient_probability = rerandomized_noise_distribution(params, rerandomizations).tail_probability(params.q as i64 / 4);
+    return (params.n as f64 * coefficient_probability).min(1.);
+}
+
+/// The results of encrypting and decrypting random messages with random keys
+#[derive(Clone, Debug)]
+pub struct MonteCarloResult
+{
+    pub ciphertexts: u64,
+    /// The count of ciphertexts that did not decrypt to their message
+    pub failures: u64,
+    /// histogram[x] is the count of coefficients whose noise has absolute value x
+    histogram: Vec<u64>
+}
+
+impl MonteCarloResult
+{
+    pub fn coefficients(&self) -> u64
+    {
+        self.ciphertexts * N as u64
+    }
+
+    /// The observed rate of coefficients whose noise is greater than the threshold in
+    /// absolute value, comparable to Distribution::tail_probability()
+    pub fn tail_rate(&self, threshold: i64) -> f64
+    {
+        let count: u64 = self.histogram.iter().skip(threshold as usize + 1).sum();
+        return count as f64 / self.coefficients() as f64;
+    }
+
+    pub fn variance(&self) -> f64
+    {
+        let sum: f64 = self.histogram.iter().enumerate().map(|(x, count)| (x * x) as f64 * *count as f64).sum();
+        return sum / self.coefficients() as f64;
+    }
+}
+
+/// The coefficients of x, with representatives in -q/2..q/2; compressing to 13 bits
+/// is lossless, as 2^13 > q
+pub fn centered_coefficients(x: &RqElement) -> [i64; N]
+{
+    let compressed: CompressedRq<13> = x.compress();
+    let mut result = [0; N];
+    for i in 0..N {
+        let value = ZqElement::decompress(compressed.data[i]).representative_pos() as i64;
+        result[i] = if value > Q as i64 / 2 { value - Q as i64 } else { value };
+    }
+    return result;
+}
+
+/// Encrypts and decrypts the given count of random messages, each with a new key, and
+/// records the noise of each coefficient. All randomness is derived from the seed.
+pub fn monte_carlo(ciphertexts: u64, seed: Seed) -> MonteCarloResult
+{
+    let mut result = MonteCarloResult {
+        ciphertexts: ciphertexts,
+        failures: 0,
+        histogram: vec![0; Q as usize / 2 + 1]
+    };
+    for i in 0..ciphertexts {
+        let mut hasher = sha3::Shake256::default();
+        hasher.input(&seed);
+        hasher.input(&i.to_le_bytes());
+        let mut reader = hasher.xof_result();
+        let mut seeds: [Seed; 4] = [[0; 32]; 4];
+        for seed in seeds.iter_mut() {
+            reader.read(seed);
+        }
+        let [matrix_seed, secret_seed, plaintext, enc_seed] = seeds;
+
+        let (sk, pk) = key_gen(matrix_seed, secret_seed);
+        let ciphertext = encrypt(&pk, plaintext, enc_seed);
+        let u = RqVector::decompress(&ciphertext.0);
+        let v = RqElement::decompress(&ciphertext.1);
+        let noise = decrypt_uncompressed(&sk, &u, v) - &RqElement::decompress(&CompressedRq::from_data(plaintext));
+        for x in centered_coefficients(&noise).iter() {
+            result.histogram[x.abs() as usize] += 1;
+        }
+        if decrypt(sk, ciphertext) != plaintext {
+            result.failures += 1;
+        }
+    }
+    return result;
+}
+
+#[cfg(test)]
+use super::params;
+
+#[test]
+fn test_elementary_distributions()
+{
+    let binomial = Distribution::centered_binomial(4);
+    assert_eq!(70. / 256., binomial.probability(0));
+    assert_eq!(1. / 256., binomial.probability(-4));
+    assert_eq!(0., binomial.probability(5));
+    assert!((binomial.variance() - 2.).abs() < 1e-12);
+
+    let sum = binomial.sum(3);
+    assert!((sum.variance() - 6.).abs() < 1e-12);
+    assert!((Distribution::centered_binomial(12).probability(0) - sum.probability(0)).abs() < 1e-12);
+
+    let product = binomial.product(&binomial);
+    assert!((product.variance() - 4.).abs() < 1e-12);
+    assert!((product.probability(16) - 2. / 65536.).abs() < 1e-15);
+
+    // compressing to 3 bits has an error of up to q/16 ~ 480
+    let compression = Distribution::compression_error(Q, 3);
+    assert_eq!(0., compression.tail_probability(481));
+    assert!(compression.tail_probability(479) > 0.);
+    assert!((compression.variance() - 76820.).abs() < 10.);
+}
+
+#[test]
+fn test_compression_error_matches_implementation()
+{
+    let distribution = Distribution::compression_error(Q, 11);
+    let mut counts = vec![0; distribution.probabilities.len()];
+    for x in 0..Q {
+        let value = ZqElement::from_perfect(x as i16);
+        let error = (value - ZqElement::decompress(value.compress::<11>())).representative_pos() as i64;
+        let error = if error > Q as i64 / 2 { error - Q as i64 } else { error };
+        counts[(error - distribution.min) as usize] += 1;
+    }
+    for i in 0..counts.len() {
+        assert!((counts[i] as f64 / Q as f64 - distribution.probabilities[i]).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn test_failure_probability()
+{
+    // the values stated in the submission are 2^-145, 2^-142 and 2^-169
+    let expected = [-145., -142., -169.];
+    for (params, expected) in params::PARAMETER_SETS.iter().zip(expected.iter()) {
+        let log_probability = failure_probability(params).log2();
+        assert!((log_probability - expected).abs() < 1., "{}: 2^{}", params.name, log_probability);
+    }
+    // compressing to 13 bits is lossless, so not compressing t only removes noise
+    assert_eq!(0., Distribution::compression_error(Q, 13).variance());
+    assert!(failure_probability(&params::KYBER768_UNCOMPRESSED_T) < failure_probability(&params::KYBER768) / 2.);
+}
+
+#[test]
+fn test_rerandomized_failure_probability()
+{
+    // the values stated in the documentation of kyber::rerandomize(); the computed values
+    // are at least 0.05 away from the bounds of the tolerance
+    let expected = [-142.8, -38.5, -12.2, -4.0];
+    for (rerandomizations, expected) in expected.iter().enumerate() {
+        let log_probability = rerandomized_failure_probability(&params::IMPLEMENTED, rerandomizations).log2();
+        assert!((log_probability - expected).abs() < 0.1, "{} re-randomizations: 2^{}", rerandomizations, log_probability);
+    }
+    assert_eq!(failure_probability(&params::IMPLEMENTED), rerandomized_failure_probability(&params::IMPLEMENTED, 0));
+}
+
+#[test]
+fn test_monte_carlo()
+{
+    let result = monte_carlo(24, [1; 32]);
+    assert_eq!(0, result.failures);
+    let distribution = noise_distribution(&params::IMPLEMENTED);
+    assert!((result.variance() / distribution.variance() - 1.).abs() < 0.1);
+    for threshold in &[100, 240, 480, 640] {
+        assert!((result.tail_rate(*threshold) - distribution.tail_probability(*threshold)).abs() < 0.03);
+    }
+}
@@ -153,6 +153,17 @@ impl<'a> From<&'a [i16]> for RqElementCoefficientReprImpl
     }
 }
 
+impl<'a> From<&'a [ZqElement]> for RqElementCoefficientReprImpl
+{
+    fn from(value: &'a [ZqElement]) -> RqElementCoefficientReprImpl
+    {
+        assert_eq!(N, value.len());
+        return RqElementCoefficientReprImpl::from(util::create_array(|i| 
+            ZqVector8::from(&value[i * VEC_SIZE..(i+1) * VEC_SIZE])
+        ));
+    }
+}
+
 impl From<[ZqElement; N]> for RqElementCoefficientReprImpl
 {
     fn from(value: [ZqElement; N]) -> RqElementCoefficientReprImpl 
@@ -201,7 +212,10 @@ impl RqElementCoefficientRepr for RqElementCoefficientReprImpl
     {
         RqElementChineseRemainderReprImpl::chinese_remainder_repr(self)
     }
+}
 
+impl RqElementCompression for RqElementCoefficientReprImpl
+{
     fn compress<const D: u16>(&self) -> CompressedRq<D>
     {
         unsafe {
@@ -503,6 +517,17 @@ impl<'a> From<&'a [i16]> for RqElementChineseRemainderReprImpl
     }
 }
 
+impl<'a> From<&'a [ZqElement]> for RqElementChineseRemainderReprImpl
+{
+    fn from(value: &'a [ZqElement]) -> RqElementChineseRemainderReprImpl
+    {
+        assert_eq!(N, value.len());
+        return RqElementChineseRemainderReprImpl::from(util::create_array(|i| 
+            ZqVector8::from(&value[i * VEC_SIZE..(i+1) * VEC_SIZE])
+        ));
+    }
+}
+
 impl From<[ZqElement; N]> for RqElementChineseRemainderReprImpl
 {
     fn from(value: [ZqElement; N]) -> RqElementChineseRemainderReprImpl 
@@ -547,11 +572,12 @@ impl encoding::Encodable for RqElementChineseRemainderReprImpl
         }
     }
 
-    fn decode<T: encoding::Decoder>(data: &mut T) -> Self
+    fn try_decode<T: encoding::Decoder>(data: &mut T) -> Option<Self>
     {
-        RqElementChineseRemainderReprImpl {
-            values: util::create_array(|_i| ZqVector8::from(util::create_array(|_j| data.read_bits(ENCODE_BITS).expect("Input too short") as i16)))
-        }
+        let values: [ZqElement; N] = util::try_create_array(|_i| ZqElement::try_decode(data).ok_or(())).ok()?;
+        Some(RqElementChineseRemainderReprImpl {
+            values: util::create_array(|i| ZqVector8::from(&values[i * VEC_SIZE..(i + 1) * VEC_SIZE]))
+        })
     }
 }
 
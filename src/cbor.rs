@@ -0,0 +1,240 @@
+//! A minimal CBOR (RFC 8949) writer and reader, as required for COSE keys.
+//! Only the data items that appear in COSE_Key structures are supported. The reader
+//! only accepts definite lengths and arguments in their shortest form, and rejects
+//! duplicate map keys; unlike the deterministic encoding of RFC 8949, it does not
+//! require the map keys to be sorted, and the writer keeps them in the given order.
+
+use super::encoding::*;
+use super::io;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CborValue
+{
+    Integer(i64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<CborValue>),
+    /// The entries in the order in which they appear in the encoding
+    Map(Vec<(CborValue, CborValue)>),
+    Bool(bool),
+    Null
+}
+
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_SIMPLE: u8 = 7;
+
+const SIMPLE_FALSE: u8 = 20;
+const SIMPLE_TRUE: u8 = 21;
+const SIMPLE_NULL: u8 = 22;
+
+const MAX_DEPTH: usize = 64;
+
+impl CborValue
+{
+    /// Returns the value for the given integer key, if this is a map that contains it
+    pub fn get(&self, key: i64) -> Option<&CborValue>
+    {
+        match self {
+            CborValue::Map(entries) => entries.iter().find(|entry| entry.0 == CborValue::Integer(key)).map(|entry| &entry.1),
+            _ => None
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]>
+    {
+        match self {
+            CborValue::Bytes(value) => Some(value),
+            _ => None
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64>
+    {
+        match self {
+            CborValue::Integer(value) => Some(*value),
+            _ => None
+        }
+    }
+}
+
+fn encode_head<T: Encoder>(encoder: &mut T, major_type: u8, argument: u64)
+{
+    let initial = major_type << 5;
+    if argument < 24 {
+        encoder.encode(initial | argument as u8);
+    } else if argument <= 0xFF {
+        encoder.encode(initial | 24);
+        encoder.encode(argument as u8);
+    } else if argument <= 0xFFFF {
+        encoder.encode(initial | 25);
+        encoder.encode_bytes(&(argument as u16).to_be_bytes());
+    } else if argument <= 0xFFFF_FFFF {
+        encoder.encode(initial | 26);
+        encoder.encode_bytes(&(argument as u32).to_be_bytes());
+    } else {
+        encoder.encode(initial | 27);
+        encoder.encode_bytes(&argument.to_be_bytes());
+    }
+}
+
+pub fn encode_value<T: Encoder>(encoder: &mut T, value: &CborValue)
+{
+    match value {
+        CborValue::Integer(value) if *value >= 0 => encode_head(encoder, MAJOR_UNSIGNED, *value as u64),
+        CborValue::Integer(value) => encode_head(encoder, MAJOR_NEGATIVE, !*value as u64),
+        CborValue::Bytes(value) => {
+            encode_head(encoder, MAJOR_BYTES, value.len() as u64);
+            encoder.encode_bytes(value);
+        },
+        CborValue::Text(value) => {
+            encode_head(encoder, MAJOR_TEXT, value.len() as u64);
+            encoder.encode_bytes(value.as_bytes());
+        },
+        CborValue::Array(elements) => {
+            encode_head(encoder, MAJOR_ARRAY, elements.len() as u64);
+            for element in elements {
+                encode_value(encoder, element);
+            }
+        },
+        CborValue::Map(entries) => {
+            encode_head(encoder, MAJOR_MAP, entries.len() as u64);
+            for (key, value) in entries {
+                encode_value(encoder, key);
+                encode_value(encoder, value);
+            }
+        },
+        CborValue::Bool(false) => encoder.encode(MAJOR_SIMPLE << 5 | SIMPLE_FALSE),
+        CborValue::Bool(true) => encoder.encode(MAJOR_SIMPLE << 5 | SIMPLE_TRUE),
+        CborValue::Null => encoder.encode(MAJOR_SIMPLE << 5 | SIMPLE_NULL)
+    }
+}
+
+pub fn write(value: &CborValue) -> Vec<u8>
+{
+    let mut result = Vec::new();
+    encode_value(&mut io::bytes_encode(&mut result), value);
+    return result;
+}
+
+/// Reads the initial byte and the argument, returns None if the argument is not
+/// given in its shortest form or has an indefinite length
+fn decode_head<T: Decoder>(decoder: &mut T) -> Option<(u8, u64)>
+{
+    let initial = decoder.read()?;
+    let additional_info = initial & 0x1F;
+    let (argument, minimum) = match additional_info {
+        0..=23 => (additional_info as u64, 0),
+        24 => (decoder.read()? as u64, 24),
+        25 => (u16::from_be_bytes(decoder.read_bytes()?) as u64, 0x100),
+        26 => (u32::from_be_bytes(decoder.read_bytes()?) as u64, 0x10000),
+        27 => (u64::from_be_bytes(decoder.read_bytes()?), 0x1_0000_0000),
+        _ => return None
+    };
+    if argument < minimum {
+        return None;
+    }
+    return Some((initial >> 5, argument));
+}
+
+fn decode_bytes<T: Decoder>(decoder: &mut T, length: u64, remaining: usize) -> Option<Vec<u8>>
+{
+    // check the length before allocating memory for it
+    if length > remaining as u64 {
+        return None;
+    }
+    return (0..length).map(|_| decoder.read()).collect();
+}
+
+/// Decodes one data item; remaining is an upper bound for the count of bytes left in the input
+pub fn decode_value<T: Decoder>(decoder: &mut T, remaining: usize, depth: usize) -> Option<CborValue>
+{
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    let (major_type, argument) = decode_head(decoder)?;
+    match major_type {
+        MAJOR_UNSIGNED if argument <= i64::max_value() as u64 => Some(CborValue::Integer(argument as i64)),
+        MAJOR_NEGATIVE if argument <= i64::max_value() as u64 => Some(CborValue::Integer(!(argument as i64))),
+        MAJOR_BYTES => decode_bytes(decoder, argument, remaining).map(CborValue::Bytes),
+        MAJOR_TEXT => String::from_utf8(decode_bytes(decoder, argument, remaining)?).ok().map(CborValue::Text),
+        MAJOR_ARRAY if argument <= remaining as u64 => {
+            (0..argument).map(|_| decode_value(decoder, remaining, depth + 1)).collect::<Option<Vec<_>>>().map(CborValue::Array)
+        },
+        MAJOR_MAP if argument <= remaining as u64 => {
+            let mut entries: Vec<(CborValue, CborValue)> = Vec::new();
+            for _ in 0..argument {
+                let key = decode_value(decoder, remaining, depth + 1)?;
+                if entries.iter().any(|entry| entry.0 == key) {
+                    return None;
+                }
+                let value = decode_value(decoder, remaining, depth + 1)?;
+                entries.push((key, value));
+            }
+            Some(CborValue::Map(entries))
+        },
+        MAJOR_SIMPLE if argument == SIMPLE_FALSE as u64 => Some(CborValue::Bool(false)),
+        MAJOR_SIMPLE if argument == SIMPLE_TRUE as u64 => Some(CborValue::Bool(true)),
+        MAJOR_SIMPLE if argument == SIMPLE_NULL as u64 => Some(CborValue::Null),
+        // integers out of range, tags, floats and other simple values
+        _ => None
+    }
+}
+
+/// Parses data that consists of exactly one data item
+pub fn parse(data: &[u8]) -> Option<CborValue>
+{
+    let mut decoder = io::bytes_decode(data);
+    let result = decode_value(&mut decoder, data.len(), 0)?;
+    if decoder.read().is_some() {
+        return None;
+    }
+    return Some(result);
+}
+
+#[test]
+fn test_write() {
+    // examples from RFC 8949, appendix A
+    assert_eq!(vec![0x17], write(&CborValue::Integer(23)));
+    assert_eq!(vec![0x18, 0x18], write(&CborValue::Integer(24)));
+    assert_eq!(vec![0x19, 0x03, 0xe8], write(&CborValue::Integer(1000)));
+    assert_eq!(vec![0x1b, 0x00, 0x00, 0x00, 0xe8, 0xd4, 0xa5, 0x10, 0x00], write(&CborValue::Integer(1000000000000)));
+    assert_eq!(vec![0x20], write(&CborValue::Integer(-1)));
+    assert_eq!(vec![0x39, 0x03, 0xe7], write(&CborValue::Integer(-1000)));
+    assert_eq!(vec![0x44, 0x01, 0x02, 0x03, 0x04], write(&CborValue::Bytes(vec![1, 2, 3, 4])));
+    assert_eq!(vec![0x62, 0xc3, 0xbc], write(&CborValue::Text("\u{fc}".to_owned())));
+    assert_eq!(vec![0xf4, 0xf5, 0xf6].iter().map(|x| vec![*x]).collect::<Vec<_>>(),
+        vec![write(&CborValue::Bool(false)), write(&CborValue::Bool(true)), write(&CborValue::Null)]);
+    let map = CborValue::Map(vec![
+        (CborValue::Integer(1), CborValue::Integer(2)),
+        (CborValue::Integer(3), CborValue::Array(vec![CborValue::Integer(4), CborValue::Integer(5)]))
+    ]);
+    assert_eq!(vec![0xa2, 0x01, 0x02, 0x03, 0x82, 0x04, 0x05], write(&map));
+    assert_eq!(Some(map), parse(&[0xa2, 0x01, 0x02, 0x03, 0x82, 0x04, 0x05]));
+}
+
+#[test]
+fn test_parse() {
+    for value in &[0, 1, 23, 24, 255, 256, 65535, 65536, 1 << 40, i64::max_value(), -1, -24, -25, -257, i64::min_value()] {
+        assert_eq!(Some(CborValue::Integer(*value)), parse(&write(&CborValue::Integer(*value))));
+    }
+    let bytes = CborValue::Bytes(vec![0xAB; 300]);
+    assert_eq!(Some(bytes.clone()), parse(&write(&bytes)));
+}
+
+#[test]
+fn test_parse_rejects_invalid() {
+    // non-shortest argument, indefinite length, float, tag, truncated, trailing data,
+    // duplicate key, integer overflow, huge length, invalid utf-8
+    for data in &[&[0x18, 0x17][..], &[0x5f, 0x41, 0x00, 0xff], &[0xf9, 0x3c, 0x00], &[0xc1, 0x00], &[0x43, 0x01, 0x02],
+        &[0x01, 0x02], &[0xa2, 0x01, 0x02, 0x01, 0x03], &[0x1b, 0x80, 0, 0, 0, 0, 0, 0, 0], &[0x5b, 0x10, 0, 0, 0, 0, 0, 0, 0],
+        &[0x61, 0xff], &[]] {
+        assert_eq!(None, parse(data), "{:?}", data);
+    }
+    let nested = vec![0x81; 100];
+    assert_eq!(None, parse(&nested));
+}
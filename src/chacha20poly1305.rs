@@ -0,0 +1,246 @@
+//! The authenticated encryption scheme ChaCha20-Poly1305 as specified in RFC 8439.
+
+pub type AeadKey = [u8; 32];
+pub type AeadNonce = [u8; 12];
+
+pub const TAG_BYTES: usize = 16;
+
+fn read_u32_le(data: &[u8]) -> u32
+{
+    u32::from_le_bytes([data[0], data[1], data[2], data[3]])
+}
+
+#[inline(always)]
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize)
+{
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+fn chacha20_block(key: &AeadKey, counter: u32, nonce: &AeadNonce) -> [u8; 64]
+{
+    let mut initial_state: [u32; 16] = [0; 16];
+    // the constant "expand 32-byte k"
+    initial_state[0] = 0x61707865;
+    initial_state[1] = 0x3320646e;
+    initial_state[2] = 0x79622d32;
+    initial_state[3] = 0x6b206574;
+    for i in 0..8 {
+        initial_state[4 + i] = read_u32_le(&key[4 * i..]);
+    }
+    initial_state[12] = counter;
+    for i in 0..3 {
+        initial_state[13 + i] = read_u32_le(&nonce[4 * i..]);
+    }
+
+    let mut state = initial_state;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+
+    let mut result = [0; 64];
+    for i in 0..16 {
+        let word = state[i].wrapping_add(initial_state[i]);
+        result[4 * i..4 * i + 4].copy_from_slice(&word.to_le_bytes());
+    }
+    return result;
+}
+
+/// Xors the data with the ChaCha20 key stream, starting at the given block counter
+pub fn chacha20_xor(key: &AeadKey, initial_counter: u32, nonce: &AeadNonce, data: &mut [u8])
+{
+    for (block_index, chunk) in data.chunks_mut(64).enumerate() {
+        let key_stream = chacha20_block(key, initial_counter.wrapping_add(block_index as u32), nonce);
+        for (byte, key_byte) in chunk.iter_mut().zip(key_stream.iter()) {
+            *byte ^= key_byte;
+        }
+    }
+}
+
+/// The one-time authenticator Poly1305, computed modulo 2^130 - 5 with
+/// five limbs of 26 bits each
+pub fn poly1305(key: &[u8; 32], message: &[u8]) -> [u8; TAG_BYTES]
+{
+    const MASK: u32 = 0x3FFFFFF;
+    let r0 = read_u32_le(&key[0..]) & 0x3FFFFFF;
+    let r1 = (read_u32_le(&key[3..]) >> 2) & 0x3FFFF03;
+    let r2 = (read_u32_le(&key[6..]) >> 4) & 0x3FFC0FF;
+    let r3 = (read_u32_le(&key[9..]) >> 6) & 0x3F03FFF;
+    let r4 = (read_u32_le(&key[12..]) >> 8) & 0x00FFFFF;
+    let (s1, s2, s3, s4) = (r1 * 5, r2 * 5, r3 * 5, r4 * 5);
+    let (mut h0, mut h1, mut h2, mut h3, mut h4): (u32, u32, u32, u32, u32) = (0, 0, 0, 0, 0);
+
+    for chunk in message.chunks(16) {
+        let mut block = [0_u8; 17];
+        block[..chunk.len()].copy_from_slice(chunk);
+        block[chunk.len()] = 1;
+        h0 += read_u32_le(&block[0..]) & MASK;
+        h1 += (read_u32_le(&block[3..]) >> 2) & MASK;
+        h2 += (read_u32_le(&block[6..]) >> 4) & MASK;
+        h3 += (read_u32_le(&block[9..]) >> 6) & MASK;
+        h4 += (read_u32_le(&block[12..]) >> 8) | ((block[16] as u32) << 24);
+
+        let d0 = h0 as u64 * r0 as u64 + h1 as u64 * s4 as u64 + h2 as u64 * s3 as u64 + h3 as u64 * s2 as u64 + h4 as u64 * s1 as u64;
+        let mut d1 = h0 as u64 * r1 as u64 + h1 as u64 * r0 as u64 + h2 as u64 * s4 as u64 + h3 as u64 * s3 as u64 + h4 as u64 * s2 as u64;
+        let mut d2 = h0 as u64 * r2 as u64 + h1 as u64 * r1 as u64 + h2 as u64 * r0 as u64 + h3 as u64 * s4 as u64 + h4 as u64 * s3 as u64;
+        let mut d3 = h0 as u64 * r3 as u64 + h1 as u64 * r2 as u64 + h2 as u64 * r1 as u64 + h3 as u64 * r0 as u64 + h4 as u64 * s4 as u64;
+        let mut d4 = h0 as u64 * r4 as u64 + h1 as u64 * r3 as u64 + h2 as u64 * r2 as u64 + h3 as u64 * r1 as u64 + h4 as u64 * r0 as u64;
+
+        d1 += d0 >> 26;
+        h0 = d0 as u32 & MASK;
+        d2 += d1 >> 26;
+        h1 = d1 as u32 & MASK;
+        d3 += d2 >> 26;
+        h2 = d2 as u32 & MASK;
+        d4 += d3 >> 26;
+        h3 = d3 as u32 & MASK;
+        h0 += (d4 >> 26) as u32 * 5;
+        h4 = d4 as u32 & MASK;
+        h1 += h0 >> 26;
+        h0 &= MASK;
+    }
+
+    // fully carry h
+    let mut c = h1 >> 26;
+    h1 &= MASK;
+    h2 += c;
+    c = h2 >> 26;
+    h2 &= MASK;
+    h3 += c;
+    c = h3 >> 26;
+    h3 &= MASK;
+    h4 += c;
+    c = h4 >> 26;
+    h4 &= MASK;
+    h0 += c * 5;
+    c = h0 >> 26;
+    h0 &= MASK;
+    h1 += c;
+
+    // compute g = h - p = h + 5 - 2^130 and select g if it is nonnegative
+    let mut g0 = h0 + 5;
+    c = g0 >> 26;
+    g0 &= MASK;
+    let mut g1 = h1 + c;
+    c = g1 >> 26;
+    g1 &= MASK;
+    let mut g2 = h2 + c;
+    c = g2 >> 26;
+    g2 &= MASK;
+    let mut g3 = h3 + c;
+    c = g3 >> 26;
+    g3 &= MASK;
+    let g4 = (h4 + c).wrapping_sub(1 << 26);
+    let select_g = (g4 >> 31).wrapping_sub(1);
+    h0 = (h0 & !select_g) | (g0 & select_g);
+    h1 = (h1 & !select_g) | (g1 & select_g);
+    h2 = (h2 & !select_g) | (g2 & select_g);
+    h3 = (h3 & !select_g) | (g3 & select_g);
+    h4 = (h4 & !select_g) | (g4 & select_g);
+
+    // h mod 2^128, then add s
+    let words: [u32; 4] = [
+        h0 | (h1 << 26),
+        (h1 >> 6) | (h2 << 20),
+        (h2 >> 12) | (h3 << 14),
+        (h3 >> 18) | (h4 << 8)
+    ];
+    let mut result = [0; TAG_BYTES];
+    let mut carry: u64 = 0;
+    for i in 0..4 {
+        let sum = words[i] as u64 + read_u32_le(&key[16 + 4 * i..]) as u64 + carry;
+        result[4 * i..4 * i + 4].copy_from_slice(&(sum as u32).to_le_bytes());
+        carry = sum >> 32;
+    }
+    return result;
+}
+
+fn compute_tag(key: &AeadKey, nonce: &AeadNonce, aad: &[u8], ciphertext: &[u8]) -> [u8; TAG_BYTES]
+{
+    let poly_key_block = chacha20_block(key, 0, nonce);
+    let mut poly_key = [0; 32];
+    poly_key.copy_from_slice(&poly_key_block[0..32]);
+
+    let padding = |len: usize| (16 - len % 16) % 16;
+    let mut mac_data: Vec<u8> = Vec::with_capacity(aad.len() + ciphertext.len() + 48);
+    mac_data.extend_from_slice(aad);
+    mac_data.extend(std::iter::repeat(0).take(padding(aad.len())));
+    mac_data.extend_from_slice(ciphertext);
+    mac_data.extend(std::iter::repeat(0).take(padding(ciphertext.len())));
+    mac_data.extend_from_slice(&(aad.len() as u64).to_le_bytes());
+    mac_data.extend_from_slice(&(ciphertext.len() as u64).to_le_bytes());
+    return poly1305(&poly_key, &mac_data);
+}
+
+/// Returns the ciphertext, followed by the authentication tag
+pub fn seal(key: &AeadKey, nonce: &AeadNonce, aad: &[u8], plaintext: &[u8]) -> Vec<u8>
+{
+    let mut result = plaintext.to_vec();
+    chacha20_xor(key, 1, nonce, &mut result);
+    let tag = compute_tag(key, nonce, aad, &result);
+    result.extend_from_slice(&tag);
+    return result;
+}
+
+/// Returns the plaintext, or None if the ciphertext or the additional data have been modified
+pub fn open(key: &AeadKey, nonce: &AeadNonce, aad: &[u8], ciphertext: &[u8]) -> Option<Vec<u8>>
+{
+    if ciphertext.len() < TAG_BYTES {
+        return None;
+    }
+    let (encrypted, tag) = ciphertext.split_at(ciphertext.len() - TAG_BYTES);
+    let expected_tag = compute_tag(key, nonce, aad, encrypted);
+    let difference = tag.iter().zip(expected_tag.iter()).fold(0, |acc, (a, b)| acc | (a ^ b));
+    if difference != 0 {
+        return None;
+    }
+    let mut result = encrypted.to_vec();
+    chacha20_xor(key, 1, nonce, &mut result);
+    return Some(result);
+}
+
+#[test]
+fn test_poly1305() {
+    let mut key = [0; 32];
+    key.copy_from_slice(&super::util::from_hex("85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b"));
+    let tag = poly1305(&key, b"Cryptographic Forum Research Group");
+    assert_eq!("a8061dc1305136c6c22b8baf0c0127a9", super::util::to_hex(&tag));
+}
+
+#[test]
+fn test_rfc8439_aead() {
+    let key: AeadKey = super::util::create_array(|i| 0x80 + i as u8);
+    let mut nonce: AeadNonce = [0; 12];
+    nonce.copy_from_slice(&super::util::from_hex("070000004041424344454647"));
+    let aad = super::util::from_hex("50515253c0c1c2c3c4c5c6c7");
+    let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+    let ciphertext = seal(&key, &nonce, &aad, plaintext);
+    assert_eq!("d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d63dbea45e8ca9671282fafb69da92728b\
+        1a71de0a9e060b2905d6a5b67ecd3b3692ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc3ff4def08e4b7a9d\
+        e576d26586cec64b61161ae10b594f09e26a7e902ecbd0600691", super::util::to_hex(&ciphertext));
+    assert_eq!(&plaintext[..], &open(&key, &nonce, &aad, &ciphertext).unwrap()[..]);
+}
+
+#[test]
+fn test_open_detects_modification() {
+    let key: AeadKey = [7; 32];
+    let nonce: AeadNonce = [1; 12];
+    let mut ciphertext = seal(&key, &nonce, b"header", b"some message");
+    assert!(open(&key, &nonce, b"Header", &ciphertext).is_none());
+    ciphertext[3] ^= 0x80;
+    assert!(open(&key, &nonce, b"header", &ciphertext).is_none());
+    assert!(open(&key, &nonce, b"header", &ciphertext[0..10]).is_none());
+}
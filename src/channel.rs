@@ -0,0 +1,455 @@
+//! A secure channel over a byte stream (usually TCP), established with the mutually
+//! authenticated key exchange of ake.rs. The client sends
+//!
+//! ```text
+//! magic "KYBC" | version (1 byte) | parameter set id (1 byte) | AKE init message
+//! ```
+//!
+//! and the server answers with the AKE response message. From the session key, one
+//! ChaCha20-Poly1305 key per direction and two confirmation values are derived. The
+//! server sends its confirmation value right after the response and the client answers
+//! with its own, so a key mismatch is detected before any data is exchanged. All further
+//! data is sent in records
+//!
+//! ```text
+//! ciphertext length (u32) | ChaCha20-Poly1305 ciphertext of (record type (1 byte) | payload)
+//! ```
+//!
+//! where the length is authenticated as additional data and the nonce is 0^4 | i (as u64)
+//! for the i-th record under the current key. Since the sequence number is implicit,
+//! dropped, replayed or reordered records fail to authenticate. A sender replaces its key
+//! by sending a key update record, after which both sides derive the next key from the
+//! current one and restart the sequence numbers; this happens automatically every
+//! rekey_interval records. The end of the data is marked by a close record, so that
+//! truncation is detected.
+
+use super::kyber::{ PublicKey, Seed, PARAMETER_SET_ID };
+use super::kem::KemSecretKey;
+use super::ake::{ AkeInitiator, AkeResponder, AuthInitMessage, AkeResponseMessage, SessionKey };
+use super::hkdf;
+use super::chacha20poly1305 as aead;
+use super::chacha20poly1305::{ AeadKey, AeadNonce, TAG_BYTES };
+use super::io::ValidationError;
+
+use std::io::{ Read, Write };
+
+const MAGIC: &[u8; 4] = b"KYBC";
+pub const VERSION: u8 = 1;
+/// The maximal payload of a single record
+pub const MAX_RECORD_SIZE: usize = 1 << 14;
+pub const DEFAULT_REKEY_INTERVAL: u64 = 1 << 20;
+
+const RECORD_DATA: u8 = 0;
+const RECORD_KEY_UPDATE: u8 = 1;
+const RECORD_CLOSE: u8 = 2;
+
+#[derive(Debug)]
+pub enum ChannelError
+{
+    Io(std::io::Error),
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    UnsupportedParameterSet(u8),
+    InvalidHandshake(ValidationError),
+    AuthenticationFailed,
+    InvalidRecord,
+    Truncated,
+    Closed
+}
+
+impl From<std::io::Error> for ChannelError
+{
+    fn from(error: std::io::Error) -> ChannelError
+    {
+        if error.kind() == std::io::ErrorKind::UnexpectedEof {
+            return ChannelError::Truncated;
+        }
+        ChannelError::Io(error)
+    }
+}
+
+impl std::fmt::Display for ChannelError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        match self {
+            ChannelError::Io(error) => write!(f, "IO error: {}", error),
+            ChannelError::InvalidMagic => write!(f, "The peer does not speak the Kyber channel protocol"),
+            ChannelError::UnsupportedVersion(version) => write!(f, "Unsupported protocol version {}", version),
+            ChannelError::UnsupportedParameterSet(id) => write!(f, "Unsupported parameter set {}", id),
+            ChannelError::InvalidHandshake(error) => write!(f, "Invalid handshake message: {}", error),
+            ChannelError::AuthenticationFailed => write!(f, "Authentication failed, the data has been modified or the peer has a different key"),
+            ChannelError::InvalidRecord => write!(f, "Invalid record"),
+            ChannelError::Truncated => write!(f, "The connection was closed without a close record"),
+            ChannelError::Closed => write!(f, "The channel has already been closed")
+        }
+    }
+}
+
+/// The keys for both directions, as seen from one side of the channel
+pub struct ChannelKeys
+{
+    send_key: AeadKey,
+    receive_key: AeadKey
+}
+
+/// The keys and confirmation values derived from the session key
+struct HandshakeKeys
+{
+    client_to_server: AeadKey,
+    server_to_client: AeadKey,
+    client_confirmation: [u8; 32],
+    server_confirmation: [u8; 32]
+}
+
+fn to_key(data: &[u8]) -> AeadKey
+{
+    let mut result: AeadKey = [0; 32];
+    result.copy_from_slice(data);
+    return result;
+}
+
+fn header() -> Vec<u8>
+{
+    let mut result = MAGIC.to_vec();
+    result.push(VERSION);
+    result.push(PARAMETER_SET_ID);
+    return result;
+}
+
+fn derive_keys(session_key: &SessionKey) -> HandshakeKeys
+{
+    let prk = hkdf::extract(&header(), &[session_key]);
+    return HandshakeKeys {
+        client_to_server: to_key(&hkdf::expand(&prk, &[b"Kyber channel client to server"], 32)),
+        server_to_client: to_key(&hkdf::expand(&prk, &[b"Kyber channel server to client"], 32)),
+        client_confirmation: to_key(&hkdf::expand(&prk, &[b"Kyber channel client confirmation"], 32)),
+        server_confirmation: to_key(&hkdf::expand(&prk, &[b"Kyber channel server confirmation"], 32))
+    };
+}
+
+fn check_confirmation<R: Read>(stream: &mut R, expected: &[u8; 32]) -> Result<(), ChannelError>
+{
+    let mut received = [0; 32];
+    stream.read_exact(&mut received)?;
+    let difference = received.iter().zip(expected.iter()).fold(0, |acc, (a, b)| acc | (a ^ b));
+    if difference != 0 {
+        return Err(ChannelError::AuthenticationFailed);
+    }
+    return Ok(());
+}
+
+fn next_key(key: &AeadKey) -> AeadKey
+{
+    to_key(&hkdf::expand(key, &[b"Kyber channel key update"], 32))
+}
+
+fn record_nonce(sequence_number: u64) -> AeadNonce
+{
+    let mut result: AeadNonce = [0; 12];
+    result[4..12].copy_from_slice(&sequence_number.to_be_bytes());
+    return result;
+}
+
+/// Runs the handshake as client, which has to know the public key of the server
+pub fn client_handshake<S: Read + Write>(stream: &mut S, sk: &KemSecretKey, server_pk: &PublicKey, seed: Seed) -> Result<ChannelKeys, ChannelError>
+{
+    let (initiator, message) = AkeInitiator::start(sk, server_pk, seed);
+    let mut data = header();
+    data.extend_from_slice(&message.to_bytes());
+    stream.write_all(&data)?;
+    stream.flush()?;
+
+    let mut response = vec![0; AkeResponseMessage::BYTES];
+    stream.read_exact(&mut response)?;
+    let response = AkeResponseMessage::from_bytes(&response).map_err(ChannelError::InvalidHandshake)?;
+    let keys = derive_keys(&initiator.finish(&response));
+    check_confirmation(stream, &keys.server_confirmation)?;
+    stream.write_all(&keys.client_confirmation)?;
+    stream.flush()?;
+    return Ok(ChannelKeys { send_key: keys.client_to_server, receive_key: keys.server_to_client });
+}
+
+/// Runs the handshake as server, which has to know the public key of the client
+pub fn server_handshake<S: Read + Write>(stream: &mut S, sk: &KemSecretKey, client_pk: &PublicKey, seed: Seed) -> Result<ChannelKeys, ChannelError>
+{
+    let mut received_header = [0; 6];
+    stream.read_exact(&mut received_header)?;
+    if &received_header[0..4] != MAGIC {
+        return Err(ChannelError::InvalidMagic);
+    }
+    if received_header[4] != VERSION {
+        return Err(ChannelError::UnsupportedVersion(received_header[4]));
+    }
+    if received_header[5] != PARAMETER_SET_ID {
+        return Err(ChannelError::UnsupportedParameterSet(received_header[5]));
+    }
+    let mut message = vec![0; AuthInitMessage::BYTES];
+    stream.read_exact(&mut message)?;
+    let message = AuthInitMessage::from_bytes(&message).map_err(ChannelError::InvalidHandshake)?;
+
+    let (response, session_key) = AkeResponder::new(sk, client_pk).respond(&message, seed);
+    let keys = derive_keys(&session_key);
+    stream.write_all(&response.to_bytes())?;
+    stream.write_all(&keys.server_confirmation)?;
+    stream.flush()?;
+    check_confirmation(stream, &keys.client_confirmation)?;
+    return Ok(ChannelKeys { send_key: keys.server_to_client, receive_key: keys.client_to_server });
+}
+
+impl ChannelKeys
+{
+    /// Creates the two halves of the channel, e.g. from a TcpStream and a clone of it
+    pub fn split<R: Read, W: Write>(self, reader: R, writer: W) -> (RecordReceiver<R>, RecordSender<W>)
+    {
+        (
+            RecordReceiver { reader: reader, key: self.receive_key, sequence_number: 0, closed: false },
+            RecordSender { writer: writer, key: self.send_key, sequence_number: 0, rekey_interval: DEFAULT_REKEY_INTERVAL, closed: false }
+        )
+    }
+}
+
+pub struct RecordSender<W: Write>
+{
+    writer: W,
+    key: AeadKey,
+    sequence_number: u64,
+    rekey_interval: u64,
+    closed: bool
+}
+
+impl<W: Write> RecordSender<W>
+{
+    /// Sets the count of records after which the key is updated automatically
+    #[allow(unused)]
+    pub fn set_rekey_interval(&mut self, interval: u64)
+    {
+        assert!(interval > 0, "The rekey interval must be positive");
+        self.rekey_interval = interval;
+    }
+
+    fn write_record(&mut self, record_type: u8, payload: &[u8]) -> Result<(), ChannelError>
+    {
+        if self.closed {
+            return Err(ChannelError::Closed);
+        }
+        let mut plaintext = Vec::with_capacity(payload.len() + 1);
+        plaintext.push(record_type);
+        plaintext.extend_from_slice(payload);
+        let length = ((plaintext.len() + TAG_BYTES) as u32).to_be_bytes();
+        let ciphertext = aead::seal(&self.key, &record_nonce(self.sequence_number), &length, &plaintext);
+        self.writer.write_all(&length)?;
+        self.writer.write_all(&ciphertext)?;
+        self.sequence_number += 1;
+        return Ok(());
+    }
+
+    /// Sends a key update record and switches to the next key
+    pub fn rekey(&mut self) -> Result<(), ChannelError>
+    {
+        self.write_record(RECORD_KEY_UPDATE, &[])?;
+        self.key = next_key(&self.key);
+        self.sequence_number = 0;
+        return Ok(());
+    }
+
+    /// Sends the data, split into records of at most MAX_RECORD_SIZE bytes
+    pub fn send(&mut self, data: &[u8]) -> Result<(), ChannelError>
+    {
+        for chunk in data.chunks(MAX_RECORD_SIZE) {
+            // leave room for the key update record
+            if self.sequence_number + 1 >= self.rekey_interval {
+                self.rekey()?;
+            }
+            self.write_record(RECORD_DATA, chunk)?;
+        }
+        self.writer.flush()?;
+        return Ok(());
+    }
+
+    /// Marks the end of the data; the underlying stream is not shut down
+    pub fn close(&mut self) -> Result<(), ChannelError>
+    {
+        self.write_record(RECORD_CLOSE, &[])?;
+        self.closed = true;
+        self.writer.flush()?;
+        return Ok(());
+    }
+}
+
+pub struct RecordReceiver<R: Read>
+{
+    reader: R,
+    key: AeadKey,
+    sequence_number: u64,
+    closed: bool
+}
+
+impl<R: Read> RecordReceiver<R>
+{
+    /// Returns the payload of the next data record, or None after the close record
+    pub fn receive(&mut self) -> Result<Option<Vec<u8>>, ChannelError>
+    {
+        loop {
+            if self.closed {
+                return Ok(None);
+            }
+            let mut length = [0; 4];
+            self.reader.read_exact(&mut length)?;
+            let ciphertext_length = u32::from_be_bytes(length) as usize;
+            if ciphertext_length < 1 + TAG_BYTES || ciphertext_length > MAX_RECORD_SIZE + 1 + TAG_BYTES {
+                return Err(ChannelError::InvalidRecord);
+            }
+            let mut ciphertext = vec![0; ciphertext_length];
+            self.reader.read_exact(&mut ciphertext)?;
+            let mut plaintext = aead::open(&self.key, &record_nonce(self.sequence_number), &length, &ciphertext)
+                .ok_or(ChannelError::AuthenticationFailed)?;
+            self.sequence_number += 1;
+            match (plaintext[0], plaintext.len()) {
+                (RECORD_DATA, _) => return Ok(Some(plaintext.split_off(1))),
+                (RECORD_KEY_UPDATE, 1) => {
+                    self.key = next_key(&self.key);
+                    self.sequence_number = 0;
+                },
+                (RECORD_CLOSE, 1) => self.closed = true,
+                _ => return Err(ChannelError::InvalidRecord)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_keys() -> (ChannelKeys, ChannelKeys)
+{
+    let keys = derive_keys(&[7; 32]);
+    return (
+        ChannelKeys { send_key: keys.client_to_server, receive_key: keys.server_to_client },
+        ChannelKeys { send_key: keys.server_to_client, receive_key: keys.client_to_server }
+    );
+}
+
+#[test]
+fn test_records() {
+    let (client_keys, server_keys) = test_keys();
+    let (_, mut sender) = client_keys.split(std::io::empty(), Vec::new());
+    sender.set_rekey_interval(3);
+    let messages: Vec<Vec<u8>> = (1..11).map(|i| vec![i as u8; i * 1000]).collect();
+    for message in &messages {
+        sender.send(message).unwrap();
+    }
+    sender.rekey().unwrap();
+    sender.send(&vec![0xAB; 3 * MAX_RECORD_SIZE + 1]).unwrap();
+    sender.close().unwrap();
+    assert!(sender.send(b"late").is_err());
+
+    let (mut receiver, _) = server_keys.split(&sender.writer[..], std::io::sink());
+    for message in &messages {
+        assert_eq!(Some(message.clone()), receiver.receive().unwrap());
+    }
+    let mut large = Vec::new();
+    for _ in 0..4 {
+        large.extend_from_slice(&receiver.receive().unwrap().unwrap());
+    }
+    assert_eq!(vec![0xAB; 3 * MAX_RECORD_SIZE + 1], large);
+    assert_eq!(None, receiver.receive().unwrap());
+}
+
+#[test]
+fn test_records_tampered() {
+    let (client_keys, _) = test_keys();
+    let (_, mut sender) = client_keys.split(std::io::empty(), Vec::new());
+    sender.send(b"first").unwrap();
+    let first_record_end = sender.writer.len();
+    sender.send(b"second").unwrap();
+    sender.close().unwrap();
+    let data = sender.writer;
+
+    let receive_all = |data: &[u8]| {
+        let (_, server_keys) = test_keys();
+        let (mut receiver, _) = server_keys.split(data, std::io::sink());
+        let mut result = Vec::new();
+        while let Some(payload) = receiver.receive()? {
+            result.push(payload);
+        }
+        Ok::<Vec<Vec<u8>>, ChannelError>(result)
+    };
+    assert_eq!(vec![b"first".to_vec(), b"second".to_vec()], receive_all(&data).unwrap());
+
+    let mut tampered = data.clone();
+    tampered[7] ^= 1;
+    assert!(match receive_all(&tampered) { Err(ChannelError::AuthenticationFailed) => true, _ => false });
+    // the second record alone does not authenticate with sequence number 0
+    assert!(match receive_all(&data[first_record_end..]) { Err(ChannelError::AuthenticationFailed) => true, _ => false });
+    // replaying the first record
+    let mut replayed = data[..first_record_end].to_vec();
+    replayed.extend_from_slice(&data);
+    assert!(match receive_all(&replayed) { Err(ChannelError::AuthenticationFailed) => true, _ => false });
+    // dropping the close record
+    assert!(match receive_all(&data[..data.len() - 1 - 4 - TAG_BYTES]) { Err(ChannelError::Truncated) => true, _ => false });
+}
+
+#[cfg(test)]
+fn run_loopback(server_sk: KemSecretKey, expected_client_pk: PublicKey, client_sk: &KemSecretKey, server_pk: &PublicKey)
+    -> (Result<Vec<Vec<u8>>, ChannelError>, Result<Vec<Vec<u8>>, ChannelError>)
+{
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    // the server echoes every message in upper case
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let keys = server_handshake(&mut stream, &server_sk, &expected_client_pk, [2; 32])?;
+        let (mut receiver, mut sender) = keys.split(stream.try_clone().unwrap(), stream);
+        let mut received = Vec::new();
+        while let Some(message) = receiver.receive()? {
+            sender.send(&message.to_ascii_uppercase())?;
+            received.push(message);
+        }
+        sender.close()?;
+        Ok(received)
+    });
+
+    let mut stream = std::net::TcpStream::connect(address).unwrap();
+    let client = client_handshake(&mut stream, client_sk, server_pk, [1; 32]).and_then(|keys| {
+        let (mut receiver, mut sender) = keys.split(stream.try_clone().unwrap(), stream);
+        sender.set_rekey_interval(2);
+        let mut echoed = Vec::new();
+        for message in &[&b"hello"[..], b"kyber", &[b'x'; 40000]] {
+            sender.send(message)?;
+            let mut echo = Vec::new();
+            while echo.len() < message.len() {
+                echo.extend_from_slice(&receiver.receive()?.ok_or(ChannelError::Truncated)?);
+            }
+            echoed.push(echo);
+        }
+        sender.close()?;
+        if receiver.receive()?.is_some() {
+            return Err(ChannelError::InvalidRecord);
+        }
+        Ok(echoed)
+    });
+    return (client, server.join().unwrap());
+}
+
+#[test]
+fn test_tcp_loopback() {
+    let (client_sk, client_pk) = super::kem::key_gen([3; 32], [4; 32]);
+    let (server_sk, server_pk) = super::kem::key_gen([5; 32], [6; 32]);
+    let (client, server) = run_loopback(server_sk, client_pk, &client_sk, &server_pk);
+    assert_eq!(vec![b"HELLO".to_vec(), b"KYBER".to_vec(), vec![b'X'; 40000]], client.unwrap());
+    // the 40000 bytes arrive in three records
+    let server = server.unwrap();
+    assert_eq!(5, server.len());
+    assert_eq!(b"hello".to_vec(), server[0]);
+}
+
+#[test]
+fn test_tcp_wrong_key() {
+    let (client_sk, client_pk) = super::kem::key_gen([3; 32], [4; 32]);
+    let (server_sk, _) = super::kem::key_gen([5; 32], [6; 32]);
+    let (_, other_pk) = super::kem::key_gen([5; 32], [7; 32]);
+    // the client expects another server key, so the session keys differ and the client
+    // rejects the confirmation value of the server
+    let (client, server) = run_loopback(server_sk, client_pk, &client_sk, &other_pk);
+    assert!(match client { Err(ChannelError::AuthenticationFailed) => true, _ => false });
+    assert!(server.is_err());
+}
@@ -0,0 +1,736 @@
+//! An implementation of Rq that multiplies in the coefficient domain, using either schoolbook,
+//! Karatsuba or Toom-Cook-4 multiplication followed by the reduction modulo X^N + 1. The
+//! multiplication does not use any roots of unity, so this is used to cross-check the
+//! NTT-based implementations. It can replace them in kyber.rs by enabling the feature
+//! coefficient_domain.
+//!
+//! The multiplication algorithms and negacyclic_mul() work for all RingParams. Only the
+//! Rq elements are restricted to KyberParams: the RqElementChineseRemainderReprImpl also
+//! stores coefficients, and the values at the roots of unity are computed where the Kyber
+//! format requires them, i.e. when sampling the matrix A (which is defined in the NTT
+//! domain), when encoding or decoding and in value_at_zeta(). These use the NTT of
+//! generic_ring.rs.
+
+use super::zq::*;
+use super::ring::*;
+use super::ring_params::{ RingParams, KyberParams };
+use super::generic_ring::{ Element, NttElement };
+
+use super::util;
+use super::encoding;
+use super::ref_impl_compat;
+
+use std::ops::{ Add, Mul, Sub, AddAssign, MulAssign, SubAssign };
+use std::cmp::{ PartialEq, Eq };
+use std::convert::From;
+use std::fmt::{ Formatter, Debug };
+use std::marker::PhantomData;
+
+/// An algorithm to multiply polynomials over Zq
+pub trait PolyMul: Clone + Copy + PartialEq + Eq + Debug
+{
+    /// Returns the product of a and b in Zq[X], where a and b have the same length n, which
+    /// is a power of two; the result has length 2n - 1
+    fn mul_full<P: RingParams>(a: &[Zq<P>], b: &[Zq<P>]) -> Vec<Zq<P>>;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Schoolbook;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Karatsuba;
+
+/// Toom-Cook-4 on the top level, and Karatsuba for the seven products of a quarter of the
+/// length, as in Saber
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ToomCook4;
+
+/// Below this length, Karatsuba uses schoolbook multiplication
+const KARATSUBA_THRESHOLD: usize = 16;
+
+/// The evaluation points of Toom-Cook-4, except infinity
+const TOOM_COOK_POINTS: [i16; 6] = [0, 1, -1, 2, -2, 3];
+
+/// The sixth powers of TOOM_COOK_POINTS
+const TOOM_COOK_SIXTH_POWERS: [i16; 6] = [0, 1, 1, 64, 64, 729];
+
+/// The inverse of the Vandermonde matrix (points[i]^j) of TOOM_COOK_POINTS, multiplied by
+/// TOOM_COOK_DENOMINATOR so that all entries are integers
+const TOOM_COOK_INTERPOLATION: [[i16; 6]; 6] = [
+    [120, 0, 0, 0, 0, 0],
+    [-40, 120, -60, -30, 6, 4],
+    [-150, 80, 80, -5, -5, 0],
+    [50, -70, -5, 35, -5, -5],
+    [30, -20, -20, 5, 5, 0],
+    [-10, 10, 5, -5, -1, 1]
+];
+
+/// 120 = 2^3 * 3 * 5 is invertible for every prime q > 5
+const TOOM_COOK_DENOMINATOR: i16 = 120;
+
+fn schoolbook_full<P: RingParams>(a: &[Zq<P>], b: &[Zq<P>]) -> Vec<Zq<P>>
+{
+    let mut result = vec![Zq::zero(); a.len() + b.len() - 1];
+    for i in 0..a.len() {
+        for j in 0..b.len() {
+            result[i + j] += a[i] * b[j];
+        }
+    }
+    return result;
+}
+
+fn karatsuba_full<P: RingParams>(a: &[Zq<P>], b: &[Zq<P>]) -> Vec<Zq<P>>
+{
+    let n = a.len();
+    if n <= KARATSUBA_THRESHOLD {
+        return schoolbook_full(a, b);
+    }
+    // (a0 + a1 Y)(b0 + b1 Y) = a0 b0 + ((a0 + a1)(b0 + b1) - a0 b0 - a1 b1) Y + a1 b1 Y^2
+    let h = n / 2;
+    let (a0, a1) = a.split_at(h);
+    let (b0, b1) = b.split_at(h);
+    let low = karatsuba_full(a0, b0);
+    let high = karatsuba_full(a1, b1);
+    let a_sum: Vec<Zq<P>> = (0..h).map(|i| a0[i] + a1[i]).collect();
+    let b_sum: Vec<Zq<P>> = (0..h).map(|i| b0[i] + b1[i]).collect();
+    let middle = karatsuba_full(&a_sum, &b_sum);
+
+    let mut result = vec![Zq::zero(); 2 * n - 1];
+    for i in 0..2 * h - 1 {
+        result[i] += low[i];
+        result[i + h] += middle[i] - low[i] - high[i];
+        result[i + 2 * h] += high[i];
+    }
+    return result;
+}
+
+fn toom_cook_4_full<P: RingParams>(a: &[Zq<P>], b: &[Zq<P>]) -> Vec<Zq<P>>
+{
+    // write a = a0 + a1 Y + a2 Y^2 + a3 Y^3 with Y = X^m, and the same for b; then the
+    // product c = c0 + c1 Y + ... + c6 Y^6 is determined by its values at the 6 points and
+    // c6 = a3 b3 (the "value at infinity")
+    let m = a.len() / 4;
+    let evaluate = |x: &[Zq<P>], point: Zq<P>| -> Vec<Zq<P>> {
+        (0..m).map(|t| x[t] + point * (x[m + t] + point * (x[2 * m + t] + point * x[3 * m + t]))).collect()
+    };
+    let points: [Zq<P>; 6] = util::create_array(|i| Zq::<P>::from(TOOM_COOK_POINTS[i]));
+    let values: Vec<Vec<Zq<P>>> = points.iter().map(|point| {
+        karatsuba_full(&evaluate(a, *point), &evaluate(b, *point))
+    }).collect();
+    let infinity = karatsuba_full(&a[3 * m..], &b[3 * m..]);
+
+    // c0, ..., c5 is the solution of the Vandermonde system V c = values - c6 * point^6
+    let scale = Zq::one() / Zq::<P>::from(TOOM_COOK_DENOMINATOR);
+    let interpolation: [[Zq<P>; 6]; 6] = util::create_array(|j| {
+        util::create_array(|i| Zq::<P>::from(TOOM_COOK_INTERPOLATION[j][i]) * scale)
+    });
+    let sixth_powers: [Zq<P>; 6] = util::create_array(|i| Zq::<P>::from(TOOM_COOK_SIXTH_POWERS[i]));
+    let mut result = vec![Zq::zero(); 8 * m - 1];
+    for t in 0..2 * m - 1 {
+        let rhs: [Zq<P>; 6] = util::create_array(|i| values[i][t] - infinity[t] * sixth_powers[i]);
+        for j in 0..6 {
+            let mut coefficient = Zq::zero();
+            for i in 0..6 {
+                coefficient += interpolation[j][i] * rhs[i];
+            }
+            result[j * m + t] += coefficient;
+        }
+        result[6 * m + t] += infinity[t];
+    }
+    return result;
+}
+
+impl PolyMul for Schoolbook
+{
+    fn mul_full<P: RingParams>(a: &[Zq<P>], b: &[Zq<P>]) -> Vec<Zq<P>>
+    {
+        schoolbook_full(a, b)
+    }
+}
+
+impl PolyMul for Karatsuba
+{
+    fn mul_full<P: RingParams>(a: &[Zq<P>], b: &[Zq<P>]) -> Vec<Zq<P>>
+    {
+        karatsuba_full(a, b)
+    }
+}
+
+impl PolyMul for ToomCook4
+{
+    fn mul_full<P: RingParams>(a: &[Zq<P>], b: &[Zq<P>]) -> Vec<Zq<P>>
+    {
+        toom_cook_4_full(a, b)
+    }
+}
+
+/// Multiplies in Zq[X] / (X^N + 1) with N = P::N, i.e. reduces the full product using
+/// X^N = -1
+pub fn negacyclic_mul<P: RingParams, M: PolyMul>(a: &[Zq<P>], b: &[Zq<P>]) -> Vec<Zq<P>>
+{
+    assert!(a.len() == P::N && b.len() == P::N);
+    let product = M::mul_full(a, b);
+    (0..P::N).map(|i| if i + P::N < product.len() { product[i] - product[i + P::N] } else { product[i] }).collect()
+}
+
+/// The values at the primitive 512-th roots of unity, in the order of ref_r.rs
+fn to_ntt_values(coefficients: &[ZqElement; N]) -> [ZqElement; N]
+{
+    let ntt = KyberParams::ring().to_ntt(Element::from(coefficients.to_vec()));
+    let values = util::create_array(|i| ntt.values()[i]);
+    if cfg!(feature = "compatibility") {
+        return ref_impl_compat::convert_our_crt_their_crt(values);
+    }
+    return values;
+}
+
+fn from_ntt_values(mut values: [ZqElement; N]) -> [ZqElement; N]
+{
+    if cfg!(feature = "compatibility") {
+        values = ref_impl_compat::convert_their_crt_our_crt(values);
+    }
+    let element = KyberParams::ring().from_ntt(NttElement::from(values.to_vec()));
+    return util::create_array(|i| element.coefficients()[i]);
+}
+
+#[derive(Clone)]
+pub struct RqElementCoefficientReprImpl<M: PolyMul>
+{
+    data: [ZqElement; N],
+    multiplication: PhantomData<M>
+}
+
+impl<M: PolyMul> RqElementCoefficientReprImpl<M>
+{
+    fn new(data: [ZqElement; N]) -> RqElementCoefficientReprImpl<M>
+    {
+        RqElementCoefficientReprImpl {
+            data: data,
+            multiplication: PhantomData
+        }
+    }
+}
+
+impl<M: PolyMul> PartialEq for RqElementCoefficientReprImpl<M>
+{
+    fn eq(&self, rhs: &RqElementCoefficientReprImpl<M>) -> bool
+    {
+        (0..N).all(|i| self.data[i] == rhs.data[i])
+    }
+}
+
+impl<M: PolyMul> Eq for RqElementCoefficientReprImpl<M> {}
+
+impl<'a, M: PolyMul> Add<&'a RqElementCoefficientReprImpl<M>> for RqElementCoefficientReprImpl<M>
+{
+    type Output = RqElementCoefficientReprImpl<M>;
+
+    #[inline(always)]
+    fn add(mut self, rhs: &'a RqElementCoefficientReprImpl<M>) -> Self::Output
+    {
+        self += rhs;
+        return self;
+    }
+}
+
+impl<'a, M: PolyMul> Add<RqElementCoefficientReprImpl<M>> for &'a RqElementCoefficientReprImpl<M>
+{
+    type Output = RqElementCoefficientReprImpl<M>;
+
+    #[inline(always)]
+    fn add(self, mut rhs: RqElementCoefficientReprImpl<M>) -> Self::Output
+    {
+        rhs += self;
+        return rhs;
+    }
+}
+
+impl<'a, M: PolyMul> Sub<&'a RqElementCoefficientReprImpl<M>> for RqElementCoefficientReprImpl<M>
+{
+    type Output = RqElementCoefficientReprImpl<M>;
+
+    #[inline(always)]
+    fn sub(mut self, rhs: &'a RqElementCoefficientReprImpl<M>) -> Self::Output
+    {
+        self -= rhs;
+        return self;
+    }
+}
+
+impl<'a, M: PolyMul> Sub<RqElementCoefficientReprImpl<M>> for &'a RqElementCoefficientReprImpl<M>
+{
+    type Output = RqElementCoefficientReprImpl<M>;
+
+    #[inline(always)]
+    fn sub(self, mut rhs: RqElementCoefficientReprImpl<M>) -> Self::Output
+    {
+        rhs -= self;
+        rhs *= ZERO - ONE;
+        return rhs;
+    }
+}
+
+impl<M: PolyMul> Mul<ZqElement> for RqElementCoefficientReprImpl<M>
+{
+    type Output = RqElementCoefficientReprImpl<M>;
+
+    #[inline(always)]
+    fn mul(mut self, rhs: ZqElement) -> Self::Output
+    {
+        self *= rhs;
+        return self;
+    }
+}
+
+impl<'a, M: PolyMul> AddAssign<&'a RqElementCoefficientReprImpl<M>> for RqElementCoefficientReprImpl<M>
+{
+    #[inline(always)]
+    fn add_assign(&mut self, rhs: &'a RqElementCoefficientReprImpl<M>)
+    {
+        for i in 0..N {
+            self.data[i] += rhs.data[i];
+        }
+    }
+}
+
+impl<'a, M: PolyMul> SubAssign<&'a RqElementCoefficientReprImpl<M>> for RqElementCoefficientReprImpl<M>
+{
+    #[inline(always)]
+    fn sub_assign(&mut self, rhs: &'a RqElementCoefficientReprImpl<M>)
+    {
+        for i in 0..N {
+            self.data[i] -= rhs.data[i];
+        }
+    }
+}
+
+impl<M: PolyMul> MulAssign<ZqElement> for RqElementCoefficientReprImpl<M>
+{
+    #[inline(always)]
+    fn mul_assign(&mut self, rhs: ZqElement)
+    {
+        for i in 0..N {
+            self.data[i] *= rhs;
+        }
+    }
+}
+
+impl<'a, M: PolyMul> From<&'a [i16]> for RqElementCoefficientReprImpl<M>
+{
+    fn from(value: &'a [i16]) -> RqElementCoefficientReprImpl<M>
+    {
+        assert_eq!(N, value.len());
+        return RqElementCoefficientReprImpl::new(util::create_array(|i| ZqElement::from(value[i])));
+    }
+}
+
+impl<'a, M: PolyMul> From<&'a [ZqElement]> for RqElementCoefficientReprImpl<M>
+{
+    fn from(value: &'a [ZqElement]) -> RqElementCoefficientReprImpl<M>
+    {
+        assert_eq!(N, value.len());
+        return RqElementCoefficientReprImpl::new(util::create_array(|i| value[i]));
+    }
+}
+
+impl<M: PolyMul> From<[ZqElement; N]> for RqElementCoefficientReprImpl<M>
+{
+    fn from(data: [ZqElement; N]) -> RqElementCoefficientReprImpl<M>
+    {
+        RqElementCoefficientReprImpl::new(data)
+    }
+}
+
+impl<M: PolyMul> Debug for RqElementCoefficientReprImpl<M>
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        write!(f, "[")?;
+        (0..N - 1).try_for_each(|i| write!(f, "{}, ", self.data[i]))?;
+        write!(f, "{}]", self.data[N - 1])?;
+        return Ok(());
+    }
+}
+
+impl<M: PolyMul> RqElementCoefficientRepr for RqElementCoefficientReprImpl<M>
+{
+    type ChineseRemainderRepr = RqElementChineseRemainderReprImpl<M>;
+
+    fn get_zero() -> RqElementCoefficientReprImpl<M>
+    {
+        RqElementCoefficientReprImpl::new([ZERO; N])
+    }
+
+    fn to_chinese_remainder_repr(self) -> RqElementChineseRemainderReprImpl<M>
+    {
+        RqElementChineseRemainderReprImpl {
+            coefficients: self.data,
+            multiplication: PhantomData
+        }
+    }
+}
+
+impl<M: PolyMul> RqElementCompression for RqElementCoefficientReprImpl<M>
+{
+    fn compress<const D: u16>(&self) -> CompressedRq<D>
+    {
+        CompressedRq {
+            data: util::create_array(|i| self.data[i].compress())
+        }
+    }
+
+    fn decompress<const D: u16>(x: &CompressedRq<D>) -> RqElementCoefficientReprImpl<M>
+    {
+        RqElementCoefficientReprImpl::new(util::create_array(|i| ZqElement::decompress(x.data[i])))
+    }
+}
+
+/// Has the same interface as the chinese remainder representations of the NTT-based
+/// implementations, but stores the coefficients and multiplies them with M
+#[derive(Clone)]
+pub struct RqElementChineseRemainderReprImpl<M: PolyMul>
+{
+    coefficients: [ZqElement; N],
+    multiplication: PhantomData<M>
+}
+
+impl<M: PolyMul> RqElementChineseRemainderReprImpl<M>
+{
+    /// Creates the element with the given values at the roots of unity
+    fn from_values(values: [ZqElement; N]) -> RqElementChineseRemainderReprImpl<M>
+    {
+        RqElementChineseRemainderReprImpl {
+            coefficients: from_ntt_values(values),
+            multiplication: PhantomData
+        }
+    }
+}
+
+impl<M: PolyMul> PartialEq for RqElementChineseRemainderReprImpl<M>
+{
+    fn eq(&self, rhs: &RqElementChineseRemainderReprImpl<M>) -> bool
+    {
+        (0..N).all(|i| self.coefficients[i] == rhs.coefficients[i])
+    }
+}
+
+impl<M: PolyMul> Eq for RqElementChineseRemainderReprImpl<M> {}
+
+impl<'a, M: PolyMul> Add<&'a RqElementChineseRemainderReprImpl<M>> for RqElementChineseRemainderReprImpl<M>
+{
+    type Output = RqElementChineseRemainderReprImpl<M>;
+
+    #[inline(always)]
+    fn add(mut self, rhs: &'a RqElementChineseRemainderReprImpl<M>) -> Self::Output
+    {
+        self += rhs;
+        return self;
+    }
+}
+
+impl<'a, M: PolyMul> Add<RqElementChineseRemainderReprImpl<M>> for &'a RqElementChineseRemainderReprImpl<M>
+{
+    type Output = RqElementChineseRemainderReprImpl<M>;
+
+    #[inline(always)]
+    fn add(self, mut rhs: RqElementChineseRemainderReprImpl<M>) -> Self::Output
+    {
+        rhs += self;
+        return rhs;
+    }
+}
+
+impl<'a, M: PolyMul> Sub<&'a RqElementChineseRemainderReprImpl<M>> for RqElementChineseRemainderReprImpl<M>
+{
+    type Output = RqElementChineseRemainderReprImpl<M>;
+
+    #[inline(always)]
+    fn sub(mut self, rhs: &'a RqElementChineseRemainderReprImpl<M>) -> Self::Output
+    {
+        self -= rhs;
+        return self;
+    }
+}
+
+impl<'a, M: PolyMul> Sub<RqElementChineseRemainderReprImpl<M>> for &'a RqElementChineseRemainderReprImpl<M>
+{
+    type Output = RqElementChineseRemainderReprImpl<M>;
+
+    #[inline(always)]
+    fn sub(self, mut rhs: RqElementChineseRemainderReprImpl<M>) -> Self::Output
+    {
+        rhs -= self;
+        rhs *= ZERO - ONE;
+        return rhs;
+    }
+}
+
+impl<'a, M: PolyMul> Mul<&'a RqElementChineseRemainderReprImpl<M>> for RqElementChineseRemainderReprImpl<M>
+{
+    type Output = RqElementChineseRemainderReprImpl<M>;
+
+    #[inline(always)]
+    fn mul(mut self, rhs: &'a RqElementChineseRemainderReprImpl<M>) -> Self::Output
+    {
+        self *= rhs;
+        return self;
+    }
+}
+
+impl<'a, M: PolyMul> Mul<RqElementChineseRemainderReprImpl<M>> for &'a RqElementChineseRemainderReprImpl<M>
+{
+    type Output = RqElementChineseRemainderReprImpl<M>;
+
+    #[inline(always)]
+    fn mul(self, mut rhs: RqElementChineseRemainderReprImpl<M>) -> Self::Output
+    {
+        rhs *= self;
+        return rhs;
+    }
+}
+
+impl<M: PolyMul> Mul<ZqElement> for RqElementChineseRemainderReprImpl<M>
+{
+    type Output = RqElementChineseRemainderReprImpl<M>;
+
+    #[inline(always)]
+    fn mul(mut self, rhs: ZqElement) -> Self::Output
+    {
+        self *= rhs;
+        return self;
+    }
+}
+
+impl<'a, M: PolyMul> AddAssign<&'a RqElementChineseRemainderReprImpl<M>> for RqElementChineseRemainderReprImpl<M>
+{
+    #[inline(always)]
+    fn add_assign(&mut self, rhs: &'a RqElementChineseRemainderReprImpl<M>)
+    {
+        for i in 0..N {
+            self.coefficients[i] += rhs.coefficients[i];
+        }
+    }
+}
+
+impl<'a, M: PolyMul> SubAssign<&'a RqElementChineseRemainderReprImpl<M>> for RqElementChineseRemainderReprImpl<M>
+{
+    #[inline(always)]
+    fn sub_assign(&mut self, rhs: &'a RqElementChineseRemainderReprImpl<M>)
+    {
+        for i in 0..N {
+            self.coefficients[i] -= rhs.coefficients[i];
+        }
+    }
+}
+
+impl<'a, M: PolyMul> MulAssign<&'a RqElementChineseRemainderReprImpl<M>> for RqElementChineseRemainderReprImpl<M>
+{
+    #[inline(always)]
+    fn mul_assign(&mut self, rhs: &'a RqElementChineseRemainderReprImpl<M>)
+    {
+        let product = negacyclic_mul::<KyberParams, M>(&self.coefficients, &rhs.coefficients);
+        self.coefficients = util::create_array(|i| product[i]);
+    }
+}
+
+impl<M: PolyMul> MulAssign<ZqElement> for RqElementChineseRemainderReprImpl<M>
+{
+    #[inline(always)]
+    fn mul_assign(&mut self, rhs: ZqElement)
+    {
+        for i in 0..N {
+            self.coefficients[i] *= rhs;
+        }
+    }
+}
+
+impl<M: PolyMul> Debug for RqElementChineseRemainderReprImpl<M>
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
+    {
+        write!(f, "[")?;
+        (0..N - 1).try_for_each(|i| write!(f, "{}, ", self.coefficients[i]))?;
+        write!(f, "{}]", self.coefficients[N - 1])?;
+        return Ok(());
+    }
+}
+
+/// Uses the same format as ref_r.rs, i.e. the values at the roots of unity
+impl<M: PolyMul> encoding::Encodable for RqElementChineseRemainderReprImpl<M>
+{
+    fn encode<T: encoding::Encoder>(&self, encoder: &mut T)
+    {
+        for value in to_ntt_values(&self.coefficients).iter() {
+            encoder.encode_bits(value.representative_pos() as u16, ENCODE_BITS);
+        }
+    }
+
+    fn try_decode<T: encoding::Decoder>(data: &mut T) -> Option<Self>
+    {
+        let values = util::try_create_array(|_i| ZqElement::try_decode(data).ok_or(())).ok()?;
+        Some(RqElementChineseRemainderReprImpl::from_values(values))
+    }
+}
+
+/// Creates the element with the given values at the roots of unity, as in ref_r.rs
+impl<'a, M: PolyMul> From<&'a [i16]> for RqElementChineseRemainderReprImpl<M>
+{
+    fn from(value: &'a [i16]) -> Self
+    {
+        assert_eq!(N, value.len());
+        RqElementChineseRemainderReprImpl::from_values(util::create_array(|i| ZqElement::from(value[i])))
+    }
+}
+
+impl<'a, M: PolyMul> From<&'a [ZqElement]> for RqElementChineseRemainderReprImpl<M>
+{
+    fn from(value: &'a [ZqElement]) -> Self
+    {
+        assert_eq!(N, value.len());
+        RqElementChineseRemainderReprImpl::from_values(util::create_array(|i| value[i]))
+    }
+}
+
+impl<M: PolyMul> From<[ZqElement; N]> for RqElementChineseRemainderReprImpl<M>
+{
+    fn from(value: [ZqElement; N]) -> Self
+    {
+        RqElementChineseRemainderReprImpl::from_values(value)
+    }
+}
+
+impl<M: PolyMul> RqElementChineseRemainderRepr for RqElementChineseRemainderReprImpl<M>
+{
+    type CoefficientRepr = RqElementCoefficientReprImpl<M>;
+
+    fn get_zero() -> RqElementChineseRemainderReprImpl<M>
+    {
+        RqElementChineseRemainderReprImpl {
+            coefficients: [ZERO; N],
+            multiplication: PhantomData
+        }
+    }
+
+    fn to_coefficient_repr(self) -> RqElementCoefficientReprImpl<M>
+    {
+        RqElementCoefficientReprImpl::new(self.coefficients)
+    }
+
+    fn mul_scalar(&mut self, x: ZqElement)
+    {
+        *self *= x;
+    }
+
+    fn add_product(&mut self, a: &RqElementChineseRemainderReprImpl<M>, b: &RqElementChineseRemainderReprImpl<M>)
+    {
+        let product = negacyclic_mul::<KyberParams, M>(&a.coefficients, &b.coefficients);
+        for i in 0..N {
+            self.coefficients[i] += product[i];
+        }
+    }
+
+    fn value_at_zeta(&self, zeta_index: usize) -> ZqElement
+    {
+        to_ntt_values(&self.coefficients)[zeta_index]
+    }
+}
+
+#[cfg(test)]
+use super::generic_ring::Ring;
+
+#[cfg(test)]
+fn test_coefficients(seed: u32) -> [ZqElement; N]
+{
+    util::create_array(|i| ZqElement::from(((i as u32 * i as u32 * 7919 + seed * 104729 + i as u32 * 31) % Q) as i16))
+}
+
+#[cfg(test)]
+fn check_against_ntt<M: PolyMul>()
+{
+    use super::kyber::RqElement;
+
+    let (a, b) = (test_coefficients(1), test_coefficients(2));
+    let ring = Ring::<KyberParams>::new();
+    let expected = ring.mul(&Element::from(a.to_vec()), &Element::from(b.to_vec()));
+    assert_eq!(expected.coefficients(), &negacyclic_mul::<KyberParams, M>(&a, &b)[..]);
+
+    // kyber::RqElement is one of the NTT-based implementations, unless the feature
+    // coefficient_domain is enabled
+    let ntt_product = RqElement::from(a).to_chinese_remainder_repr() * &RqElement::from(b).to_chinese_remainder_repr();
+    let product = RqElementCoefficientReprImpl::<M>::from(a).to_chinese_remainder_repr() *
+        &RqElementCoefficientReprImpl::<M>::from(b).to_chinese_remainder_repr();
+    assert_eq!(RqElement::from(util::create_array(|i| product.coefficients[i])), ntt_product.clone().to_coefficient_repr());
+    for i in [0, 1, 100, 255].iter() {
+        assert_eq!(ntt_product.value_at_zeta(*i), product.value_at_zeta(*i));
+    }
+}
+
+#[test]
+fn test_multiplication_algorithms()
+{
+    check_against_ntt::<Schoolbook>();
+    check_against_ntt::<Karatsuba>();
+    check_against_ntt::<ToomCook4>();
+}
+
+#[cfg(test)]
+fn check_against_generic_ring<P: RingParams>()
+{
+    let a: Vec<Zq<P>> = (0..P::N).map(|i| Zq::from((i * i * 7919 + i * 31 + 5) as i16)).collect();
+    let b: Vec<Zq<P>> = (0..P::N).map(|i| Zq::from((i * 104729 % 65521) as i16)).collect();
+    let expected = Ring::<P>::new().mul(&Element::from(a.clone()), &Element::from(b.clone()));
+    assert_eq!(expected.coefficients(), &negacyclic_mul::<P, Schoolbook>(&a, &b)[..]);
+    assert_eq!(expected.coefficients(), &negacyclic_mul::<P, Karatsuba>(&a, &b)[..]);
+    assert_eq!(expected.coefficients(), &negacyclic_mul::<P, ToomCook4>(&a, &b)[..]);
+}
+
+#[test]
+fn test_multiplication_other_params()
+{
+    use super::ring_params::{ NewHopeParams, Toy16Params, Toy64Params };
+
+    check_against_generic_ring::<Toy16Params>();
+    check_against_generic_ring::<Toy64Params>();
+    check_against_generic_ring::<NewHopeParams>();
+}
+
+#[test]
+fn test_toom_cook_tables()
+{
+    for i in 0..6 {
+        let point = TOOM_COOK_POINTS[i] as i64;
+        assert_eq!(point.pow(6), TOOM_COOK_SIXTH_POWERS[i] as i64);
+        for k in 0..6 {
+            let entry: i64 = (0..6).map(|j| TOOM_COOK_INTERPOLATION[j][k] as i64 * point.pow(j as u32)).sum();
+            assert_eq!(if i == k { TOOM_COOK_DENOMINATOR as i64 } else { 0 }, entry);
+        }
+    }
+}
+
+#[test]
+fn test_x_to_n_is_minus_one()
+{
+    let mut x = [ZERO; N];
+    x[N / 2] = ONE;
+    let mut expected = [ZERO; N];
+    expected[0] = -ONE;
+    assert_eq!(&expected[..], &negacyclic_mul::<KyberParams, ToomCook4>(&x, &x)[..]);
+    assert_eq!(&expected[..], &negacyclic_mul::<KyberParams, Karatsuba>(&x, &x)[..]);
+}
+
+#[test]
+fn test_encoding_matches_ntt_implementation()
+{
+    use super::kyber::RqElement;
+    use super::encoding::Encodable;
+    use super::io::{ bytes_encode, bytes_decode };
+
+    let a = test_coefficients(3);
+    let mut expected = Vec::new();
+    RqElement::from(a).to_chinese_remainder_repr().encode(&mut bytes_encode(&mut expected));
+    let element = RqElementCoefficientReprImpl::<ToomCook4>::from(a).to_chinese_remainder_repr();
+    let mut actual = Vec::new();
+    element.encode(&mut bytes_encode(&mut actual));
+    assert_eq!(expected, actual);
+    assert!(element == RqElementChineseRemainderReprImpl::decode(&mut bytes_decode(&actual)));
+}
@@ -0,0 +1,276 @@
+//! A versioned container format for encrypting data of arbitrary length to a
+//! Kyber public key (KEM-DEM). The layout is
+//!
+//! ```text
+//! header:  magic "KYBF" | version (1 byte) | parameter set id (1 byte)
+//!          | KEM ciphertext length (u32) | KEM ciphertext
+//! records: final flag (1 byte) | plaintext length (u32) | ChaCha20-Poly1305 ciphertext
+//! ```
+//!
+//! All integers are big-endian. The data is split into records of at most CHUNK_SIZE
+//! bytes, and the last record (which may be empty) has the final flag set. The i-th
+//! record is encrypted with the nonce 0^3 | final flag | i (as u64), and the whole
+//! header together with the flag and the length of the record is authenticated as
+//! additional data. Therefore, records cannot be reordered, dropped or truncated
+//! without detection.
+
+use super::kyber::{ PublicKey, Seed, PARAMETER_SET_ID, CIPHERTEXT_BYTES };
+use super::kem;
+use super::kem::KemSecretKey;
+use super::hkdf;
+use super::chacha20poly1305 as aead;
+use super::chacha20poly1305::{ AeadKey, AeadNonce, TAG_BYTES };
+use super::io::{ ciphertext_to_bytes, validate_ciphertext };
+
+use std::io::{ Read, Write };
+
+const MAGIC: &[u8; 4] = b"KYBF";
+pub const VERSION: u8 = 1;
+pub const CHUNK_SIZE: usize = 1 << 16;
+
+#[derive(Debug)]
+pub enum ContainerError
+{
+    Io(std::io::Error),
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    UnsupportedParameterSet(u8),
+    InvalidHeader,
+    AuthenticationFailed,
+    Truncated,
+    TrailingData
+}
+
+impl From<std::io::Error> for ContainerError
+{
+    fn from(error: std::io::Error) -> ContainerError
+    {
+        ContainerError::Io(error)
+    }
+}
+
+impl std::fmt::Display for ContainerError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        match self {
+            ContainerError::Io(error) => write!(f, "IO error: {}", error),
+            ContainerError::InvalidMagic => write!(f, "Input is not an encrypted Kyber container"),
+            ContainerError::UnsupportedVersion(version) => write!(f, "Unsupported container version {}", version),
+            ContainerError::UnsupportedParameterSet(id) => write!(f, "Unsupported parameter set {}", id),
+            ContainerError::InvalidHeader => write!(f, "Invalid container header"),
+            ContainerError::AuthenticationFailed => write!(f, "Authentication failed, the data has been modified or the key is wrong"),
+            ContainerError::Truncated => write!(f, "Container is truncated"),
+            ContainerError::TrailingData => write!(f, "Unexpected data after the final record")
+        }
+    }
+}
+
+fn derive_file_key(shared_secret: &kem::SharedSecret, header: &[u8]) -> AeadKey
+{
+    let prk = hkdf::extract(b"", &[shared_secret]);
+    let mut result: AeadKey = [0; 32];
+    result.copy_from_slice(&hkdf::expand(&prk, &[b"Kyber file encryption", header], 32));
+    return result;
+}
+
+fn record_nonce(index: u64, is_final: bool) -> AeadNonce
+{
+    let mut result: AeadNonce = [0; 12];
+    result[3] = is_final as u8;
+    result[4..12].copy_from_slice(&index.to_be_bytes());
+    return result;
+}
+
+fn record_aad(header: &[u8], is_final: bool, length: u32) -> Vec<u8>
+{
+    let mut result = header.to_vec();
+    result.push(is_final as u8);
+    result.extend_from_slice(&length.to_be_bytes());
+    return result;
+}
+
+/// Reads until the buffer is full or the end of the input is reached, returns the count of read bytes
+fn read_full<R: Read>(input: &mut R, buffer: &mut [u8]) -> std::io::Result<usize>
+{
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match input.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(ref error) if error.kind() == std::io::ErrorKind::Interrupted => {},
+            Err(error) => return Err(error)
+        }
+    }
+    return Ok(filled);
+}
+
+fn read_exact_or_truncated<R: Read>(input: &mut R, buffer: &mut [u8]) -> Result<(), ContainerError>
+{
+    if read_full(input, buffer)? < buffer.len() {
+        return Err(ContainerError::Truncated);
+    }
+    return Ok(());
+}
+
+fn write_record<W: Write>(output: &mut W, key: &AeadKey, header: &[u8], index: u64, is_final: bool, data: &[u8]) -> std::io::Result<()>
+{
+    let length = data.len() as u32;
+    let ciphertext = aead::seal(key, &record_nonce(index, is_final), &record_aad(header, is_final, length), data);
+    output.write_all(&[is_final as u8])?;
+    output.write_all(&length.to_be_bytes())?;
+    output.write_all(&ciphertext)?;
+    return Ok(());
+}
+
+/// Encapsulates a fresh key to the public key and encrypts the whole input with it
+pub fn encrypt_stream<R: Read, W: Write>(pk: &PublicKey, seed: Seed, input: &mut R, output: &mut W) -> std::io::Result<()>
+{
+    let (shared_secret, kem_ciphertext) = kem::encapsulate(pk, seed);
+    let kem_ciphertext_bytes = ciphertext_to_bytes(&kem_ciphertext);
+    let mut header: Vec<u8> = MAGIC.to_vec();
+    header.push(VERSION);
+    header.push(PARAMETER_SET_ID);
+    header.extend_from_slice(&(kem_ciphertext_bytes.len() as u32).to_be_bytes());
+    header.extend_from_slice(&kem_ciphertext_bytes);
+    output.write_all(&header)?;
+
+    let key = derive_file_key(&shared_secret, &header);
+    let mut current = vec![0; CHUNK_SIZE];
+    let mut next = vec![0; CHUNK_SIZE];
+    let mut current_len = read_full(input, &mut current)?;
+    let mut index: u64 = 0;
+    // we read one chunk ahead, so that we know which record is the final one
+    while current_len == CHUNK_SIZE {
+        let next_len = read_full(input, &mut next)?;
+        if next_len == 0 {
+            break;
+        }
+        write_record(output, &key, &header, index, false, &current[..current_len])?;
+        std::mem::swap(&mut current, &mut next);
+        current_len = next_len;
+        index += 1;
+    }
+    write_record(output, &key, &header, index, true, &current[..current_len])?;
+    return output.flush();
+}
+
+/// Decrypts a container created by encrypt_stream(). Records are written to the output
+/// as soon as they are authenticated, so if an error is returned, the data written so far
+/// must be discarded.
+pub fn decrypt_stream<R: Read, W: Write>(sk: &KemSecretKey, input: &mut R, output: &mut W) -> Result<(), ContainerError>
+{
+    let mut fixed_header = [0; 10];
+    read_exact_or_truncated(input, &mut fixed_header)?;
+    if &fixed_header[0..4] != MAGIC {
+        return Err(ContainerError::InvalidMagic);
+    }
+    if fixed_header[4] != VERSION {
+        return Err(ContainerError::UnsupportedVersion(fixed_header[4]));
+    }
+    if fixed_header[5] != PARAMETER_SET_ID {
+        return Err(ContainerError::UnsupportedParameterSet(fixed_header[5]));
+    }
+    let kem_ciphertext_len = u32::from_be_bytes([fixed_header[6], fixed_header[7], fixed_header[8], fixed_header[9]]) as usize;
+    if kem_ciphertext_len != CIPHERTEXT_BYTES {
+        return Err(ContainerError::InvalidHeader);
+    }
+    let mut kem_ciphertext_bytes = vec![0; kem_ciphertext_len];
+    read_exact_or_truncated(input, &mut kem_ciphertext_bytes)?;
+    let kem_ciphertext = validate_ciphertext(&kem_ciphertext_bytes).map_err(|_| ContainerError::InvalidHeader)?;
+    let shared_secret = kem::decapsulate(sk, &kem_ciphertext);
+
+    let mut header = fixed_header.to_vec();
+    header.extend_from_slice(&kem_ciphertext_bytes);
+    let key = derive_file_key(&shared_secret, &header);
+
+    let mut index: u64 = 0;
+    loop {
+        let mut record_header = [0; 5];
+        read_exact_or_truncated(input, &mut record_header)?;
+        let is_final = match record_header[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(ContainerError::AuthenticationFailed)
+        };
+        let length = u32::from_be_bytes([record_header[1], record_header[2], record_header[3], record_header[4]]);
+        if length as usize > CHUNK_SIZE {
+            return Err(ContainerError::AuthenticationFailed);
+        }
+        let mut ciphertext = vec![0; length as usize + TAG_BYTES];
+        read_exact_or_truncated(input, &mut ciphertext)?;
+        let plaintext = aead::open(&key, &record_nonce(index, is_final), &record_aad(&header, is_final, length), &ciphertext)
+            .ok_or(ContainerError::AuthenticationFailed)?;
+        output.write_all(&plaintext)?;
+        if is_final {
+            break;
+        }
+        index += 1;
+    }
+    if read_full(input, &mut [0; 1])? != 0 {
+        return Err(ContainerError::TrailingData);
+    }
+    output.flush()?;
+    return Ok(());
+}
+
+#[cfg(test)]
+fn encrypt_decrypt_test_data(len: usize) {
+    let (sk, pk) = kem::key_gen([1; 32], [2; 32]);
+    let data: Vec<u8> = (0..len).map(|i| (i * 7 + i / 256) as u8).collect();
+    let mut encrypted: Vec<u8> = Vec::new();
+    encrypt_stream(&pk, [3; 32], &mut &data[..], &mut encrypted).unwrap();
+    let mut decrypted: Vec<u8> = Vec::new();
+    decrypt_stream(&sk, &mut &encrypted[..], &mut decrypted).unwrap();
+    assert_eq!(data, decrypted);
+}
+
+#[test]
+fn test_encrypt_decrypt() {
+    encrypt_decrypt_test_data(0);
+    encrypt_decrypt_test_data(1000);
+    encrypt_decrypt_test_data(CHUNK_SIZE);
+    encrypt_decrypt_test_data(2 * CHUNK_SIZE + 17);
+}
+
+#[test]
+fn test_detect_modification() {
+    let (sk, pk) = kem::key_gen([1; 32], [2; 32]);
+    let data = vec![0x42; CHUNK_SIZE + 100];
+    let mut encrypted: Vec<u8> = Vec::new();
+    encrypt_stream(&pk, [3; 32], &mut &data[..], &mut encrypted).unwrap();
+
+    let mut modified = encrypted.clone();
+    let len = modified.len();
+    modified[len - 20] ^= 1;
+    match decrypt_stream(&sk, &mut &modified[..], &mut Vec::new()) {
+        Err(ContainerError::AuthenticationFailed) => {},
+        result => panic!("Expected authentication failure, got {:?}", result)
+    };
+
+    // drop the final record
+    let truncated = &encrypted[..len - 100 - 5 - TAG_BYTES];
+    match decrypt_stream(&sk, &mut &truncated[..], &mut Vec::new()) {
+        Err(ContainerError::Truncated) => {},
+        result => panic!("Expected truncation error, got {:?}", result)
+    };
+
+    let mut extended = encrypted.clone();
+    extended.push(0);
+    match decrypt_stream(&sk, &mut &extended[..], &mut Vec::new()) {
+        Err(ContainerError::TrailingData) => {},
+        result => panic!("Expected trailing data error, got {:?}", result)
+    };
+}
+
+#[test]
+fn test_wrong_header() {
+    let (sk, pk) = kem::key_gen([1; 32], [2; 32]);
+    let mut encrypted: Vec<u8> = Vec::new();
+    encrypt_stream(&pk, [3; 32], &mut &b"data"[..], &mut encrypted).unwrap();
+    encrypted[5] = 0xEE;
+    match decrypt_stream(&sk, &mut &encrypted[..], &mut Vec::new()) {
+        Err(ContainerError::UnsupportedParameterSet(0xEE)) => {},
+        result => panic!("Expected unsupported parameter set, got {:?}", result)
+    };
+}
@@ -0,0 +1,125 @@
+//! COSE_Key (RFC 9052) representation of Kyber keys, the CBOR counterpart of the
+//! JSON Web Keys in jwk.rs. The map contains the key type AKP, the algorithm and the
+//! raw key encodings of io.rs as byte strings, with its keys in deterministic order
+//!
+//! ```text
+//! { 1 (kty): 7 (AKP), 3 (alg): ALGORITHM, -1 (pub): bstr, -2 (priv): bstr }
+//! ```
+
+use super::cbor::*;
+use super::kyber::PublicKey;
+use super::kem::{ KemSecretKey, KEM_SECRET_KEY_BYTES };
+use super::io;
+
+const LABEL_KEY_TYPE: i64 = 1;
+const LABEL_ALGORITHM: i64 = 3;
+const LABEL_PUBLIC: i64 = -1;
+const LABEL_PRIVATE: i64 = -2;
+
+pub const KEY_TYPE_AKP: i64 = 7;
+/// There is no registered algorithm for the round 1 version of Kyber, so we use
+/// a value from the private use range for the parameter set PARAMETER_SET_ID
+pub const ALGORITHM: i64 = -65537;
+
+pub trait CoseKeyEncodable: Sized
+{
+    fn to_cose_key(&self) -> Vec<u8>;
+    fn from_cose_key(data: &[u8]) -> Option<Self>;
+}
+
+fn key_entries(public_key: &PublicKey) -> Vec<(CborValue, CborValue)>
+{
+    vec![
+        (CborValue::Integer(LABEL_KEY_TYPE), CborValue::Integer(KEY_TYPE_AKP)),
+        (CborValue::Integer(LABEL_ALGORITHM), CborValue::Integer(ALGORITHM)),
+        (CborValue::Integer(LABEL_PUBLIC), CborValue::Bytes(io::pk_to_bytes(public_key)))
+    ]
+}
+
+/// Checks the key type and algorithm and returns the key with the public key bytes
+fn parse_key(data: &[u8]) -> Option<(CborValue, Vec<u8>)>
+{
+    let key = parse(data)?;
+    if key.get(LABEL_KEY_TYPE)?.as_integer()? != KEY_TYPE_AKP || key.get(LABEL_ALGORITHM)?.as_integer()? != ALGORITHM {
+        return None;
+    }
+    let public_key = key.get(LABEL_PUBLIC)?.as_bytes()?.to_vec();
+    io::validate_public_key(&public_key).ok()?;
+    return Some((key, public_key));
+}
+
+impl CoseKeyEncodable for PublicKey
+{
+    fn to_cose_key(&self) -> Vec<u8>
+    {
+        write(&CborValue::Map(key_entries(self)))
+    }
+
+    /// Also accepts private keys, but ignores the private part
+    fn from_cose_key(data: &[u8]) -> Option<PublicKey>
+    {
+        let (_, public_key) = parse_key(data)?;
+        return io::pk_from_bytes(&public_key).ok();
+    }
+}
+
+impl CoseKeyEncodable for KemSecretKey
+{
+    fn to_cose_key(&self) -> Vec<u8>
+    {
+        let mut entries = key_entries(&self.1);
+        entries.push((CborValue::Integer(LABEL_PRIVATE), CborValue::Bytes(io::kem_sk_to_bytes(self))));
+        return write(&CborValue::Map(entries));
+    }
+
+    /// The public key entry must match the public key contained in the secret key
+    fn from_cose_key(data: &[u8]) -> Option<KemSecretKey>
+    {
+        let (key, public_key) = parse_key(data)?;
+        let secret_key = key.get(LABEL_PRIVATE)?.as_bytes()?;
+        if secret_key.len() != KEM_SECRET_KEY_BYTES {
+            return None;
+        }
+        let result = io::kem_sk_from_bytes(secret_key).ok()?;
+        if io::pk_to_bytes(&result.1) != public_key {
+            return None;
+        }
+        return Some(result);
+    }
+}
+
+#[cfg(test)]
+use super::kyber::PUBLIC_KEY_BYTES;
+
+#[test]
+fn test_to_from_cose_key() {
+    let (sk, pk) = super::kem::key_gen([6; 32], [7; 32]);
+    let public_key = pk.to_cose_key();
+    let private_key = sk.to_cose_key();
+    assert_eq!(&[0xa3, 0x01, 0x07, 0x03, 0x3a, 0x00, 0x01, 0x00, 0x00, 0x20, 0x59, 0x04, 0x40][..], &public_key[0..13]);
+    assert_eq!(public_key, PublicKey::from_cose_key(&public_key).unwrap().to_cose_key());
+    assert_eq!(public_key, PublicKey::from_cose_key(&private_key).unwrap().to_cose_key());
+    assert_eq!(private_key, KemSecretKey::from_cose_key(&private_key).unwrap().to_cose_key());
+    assert!(KemSecretKey::from_cose_key(&public_key).is_none());
+}
+
+#[test]
+fn test_from_cose_key_rejects_invalid() {
+    let (sk, pk) = super::kem::key_gen([6; 32], [7; 32]);
+    let (other_sk, _) = super::kem::key_gen([8; 32], [9; 32]);
+    let mut wrong_type = pk.to_cose_key();
+    wrong_type[2] = 0x01;
+    assert!(PublicKey::from_cose_key(&wrong_type).is_none());
+
+    let mut entries = key_entries(&pk);
+    entries[2].1 = CborValue::Bytes(vec![0; PUBLIC_KEY_BYTES - 1]);
+    assert!(PublicKey::from_cose_key(&write(&CborValue::Map(entries))).is_none());
+
+    let mut entries = key_entries(&pk);
+    entries.push((CborValue::Integer(LABEL_PRIVATE), CborValue::Bytes(io::kem_sk_to_bytes(&other_sk))));
+    assert!(KemSecretKey::from_cose_key(&write(&CborValue::Map(entries))).is_none());
+
+    let mut trailing = sk.to_cose_key();
+    trailing.push(0);
+    assert!(KemSecretKey::from_cose_key(&trailing).is_none());
+}
@@ -0,0 +1,278 @@
+//! A minimal DER encoder and strict decoder, just enough to wrap the raw key encodings
+//! of io.rs into the X.509 SubjectPublicKeyInfo and the PKCS#8 OneAsymmetricKey
+//! (RFC 5958) structures
+//!
+//! ```text
+//! SubjectPublicKeyInfo ::= SEQUENCE { algorithm AlgorithmIdentifier, subjectPublicKey BIT STRING }
+//! OneAsymmetricKey     ::= SEQUENCE { version INTEGER (0), privateKeyAlgorithm AlgorithmIdentifier, privateKey OCTET STRING }
+//! AlgorithmIdentifier  ::= SEQUENCE { algorithm OBJECT IDENTIFIER }
+//! ```
+//!
+//! As for Ed25519 (RFC 8410), the parameters of the algorithm identifier are absent,
+//! since the OID already determines the parameter set. The decoder only accepts exactly
+//! these structures, with minimal length encodings and without trailing data.
+
+use super::encoding::*;
+use super::kyber::{ PublicKey, PARAMETER_SET_ID, PUBLIC_KEY_BYTES };
+use super::kem::{ KemSecretKey, KEM_SECRET_KEY_BYTES };
+use super::io;
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_BIT_STRING: u8 = 0x03;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+
+/// There are no registered OIDs for the round 1 version of Kyber, so we use an arc below
+/// 2.25, where everyone may create OIDs from a randomly generated UUID (ITU-T X.667),
+/// followed by the parameter set id. Hence the OIDs cannot collide with registered ones,
+/// but they are specific to this implementation, so other implementations will not
+/// accept these keys.
+const KYBER_OID_ARC: [u128; 3] = [2, 25, 0xcf012442_68e5_480f_9f4a_8caf26cbbe7c];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DerError
+{
+    Truncated,
+    UnexpectedTag(u8),
+    InvalidLength,
+    UnknownAlgorithm,
+    UnsupportedVersion,
+    InvalidKeyLength,
+    TrailingData
+}
+
+impl std::fmt::Display for DerError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        match self {
+            DerError::Truncated => write!(f, "DER structure is truncated"),
+            DerError::UnexpectedTag(tag) => write!(f, "Unexpected DER tag 0x{:02x}", tag),
+            DerError::InvalidLength => write!(f, "Invalid or non-minimal DER length"),
+            DerError::UnknownAlgorithm => write!(f, "Unknown algorithm identifier"),
+            DerError::UnsupportedVersion => write!(f, "Unsupported PKCS#8 version"),
+            DerError::InvalidKeyLength => write!(f, "Key has the wrong length for its parameter set"),
+            DerError::TrailingData => write!(f, "Unexpected data after the DER structure")
+        }
+    }
+}
+
+pub fn algorithm_oid(parameter_set: u8) -> Vec<u128>
+{
+    let mut result = KYBER_OID_ARC.to_vec();
+    result.push(parameter_set as u128);
+    return result;
+}
+
+fn encode_oid(oid: &[u128]) -> Vec<u8>
+{
+    let mut result = Vec::new();
+    let mut write_base_128 = |mut value: u128| {
+        let mut digits = vec![(value & 0x7F) as u8];
+        value >>= 7;
+        while value != 0 {
+            digits.push((value & 0x7F) as u8 | 0x80);
+            value >>= 7;
+        }
+        result.extend(digits.iter().rev());
+    };
+    write_base_128(oid[0] * 40 + oid[1]);
+    for component in &oid[2..] {
+        write_base_128(*component);
+    }
+    return result;
+}
+
+fn encode_length<T: Encoder>(encoder: &mut T, length: usize)
+{
+    assert!(length <= 0xFFFF);
+    if length < 0x80 {
+        encoder.encode(length as u8);
+    } else if length <= 0xFF {
+        encoder.encode(0x81);
+        encoder.encode(length as u8);
+    } else {
+        encoder.encode(0x82);
+        encoder.encode((length >> 8) as u8);
+        encoder.encode(length as u8);
+    }
+}
+
+pub fn encode_tlv<T: Encoder>(encoder: &mut T, tag: u8, content: &[u8])
+{
+    encoder.encode(tag);
+    encode_length(encoder, content.len());
+    encoder.encode_bytes(content);
+}
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8>
+{
+    let mut result = Vec::new();
+    encode_tlv(&mut io::bytes_encode(&mut result), tag, content);
+    return result;
+}
+
+fn algorithm_identifier() -> Vec<u8>
+{
+    tlv(TAG_SEQUENCE, &tlv(TAG_OID, &encode_oid(&algorithm_oid(PARAMETER_SET_ID))))
+}
+
+fn decode_length<T: Decoder>(decoder: &mut T) -> Result<usize, DerError>
+{
+    let first = decoder.read().ok_or(DerError::Truncated)?;
+    let length = match first {
+        0x00..=0x7F => return Ok(first as usize),
+        0x81 => decoder.read().ok_or(DerError::Truncated)? as usize,
+        0x82 => ((decoder.read().ok_or(DerError::Truncated)? as usize) << 8) | decoder.read().ok_or(DerError::Truncated)? as usize,
+        _ => return Err(DerError::InvalidLength)
+    };
+    // DER requires the shortest possible encoding
+    if (first == 0x81 && length < 0x80) || (first == 0x82 && length <= 0xFF) {
+        return Err(DerError::InvalidLength);
+    }
+    return Ok(length);
+}
+
+/// Reads one element with the given tag and returns its content
+pub fn decode_tlv<T: Decoder>(decoder: &mut T, expected_tag: u8) -> Result<Vec<u8>, DerError>
+{
+    let tag = decoder.read().ok_or(DerError::Truncated)?;
+    if tag != expected_tag {
+        return Err(DerError::UnexpectedTag(tag));
+    }
+    let length = decode_length(decoder)?;
+    return (0..length).map(|_| decoder.read().ok_or(DerError::Truncated)).collect();
+}
+
+fn expect_end<T: Decoder>(decoder: &mut T) -> Result<(), DerError>
+{
+    match decoder.read() {
+        Some(_) => Err(DerError::TrailingData),
+        None => Ok(())
+    }
+}
+
+fn decode_algorithm_identifier<T: Decoder>(decoder: &mut T) -> Result<(), DerError>
+{
+    let content = decode_tlv(decoder, TAG_SEQUENCE)?;
+    let mut content_decoder = io::bytes_decode(&content);
+    let oid = decode_tlv(&mut content_decoder, TAG_OID)?;
+    if oid != encode_oid(&algorithm_oid(PARAMETER_SET_ID)) {
+        return Err(DerError::UnknownAlgorithm);
+    }
+    return expect_end(&mut content_decoder);
+}
+
+pub fn pk_to_spki(public_key: &PublicKey) -> Vec<u8>
+{
+    let mut bit_string = vec![0];
+    bit_string.extend_from_slice(&io::pk_to_bytes(public_key));
+    let mut content = algorithm_identifier();
+    content.extend_from_slice(&tlv(TAG_BIT_STRING, &bit_string));
+    return tlv(TAG_SEQUENCE, &content);
+}
+
+pub fn pk_from_spki(data: &[u8]) -> Result<PublicKey, DerError>
+{
+    let mut decoder = io::bytes_decode(data);
+    let content = decode_tlv(&mut decoder, TAG_SEQUENCE)?;
+    expect_end(&mut decoder)?;
+
+    let mut content_decoder = io::bytes_decode(&content);
+    decode_algorithm_identifier(&mut content_decoder)?;
+    let bit_string = decode_tlv(&mut content_decoder, TAG_BIT_STRING)?;
+    expect_end(&mut content_decoder)?;
+    // the first byte is the count of unused bits in the last byte
+    if bit_string.len() != PUBLIC_KEY_BYTES + 1 || bit_string[0] != 0 {
+        return Err(DerError::InvalidKeyLength);
+    }
+    return io::validate_public_key(&bit_string[1..]).map_err(|_| DerError::InvalidKeyLength);
+}
+
+pub fn sk_to_pkcs8(secret_key: &KemSecretKey) -> Vec<u8>
+{
+    let mut content = tlv(TAG_INTEGER, &[0]);
+    content.extend_from_slice(&algorithm_identifier());
+    content.extend_from_slice(&tlv(TAG_OCTET_STRING, &io::kem_sk_to_bytes(secret_key)));
+    return tlv(TAG_SEQUENCE, &content);
+}
+
+pub fn sk_from_pkcs8(data: &[u8]) -> Result<KemSecretKey, DerError>
+{
+    let mut decoder = io::bytes_decode(data);
+    let content = decode_tlv(&mut decoder, TAG_SEQUENCE)?;
+    expect_end(&mut decoder)?;
+
+    let mut content_decoder = io::bytes_decode(&content);
+    if decode_tlv(&mut content_decoder, TAG_INTEGER)? != [0] {
+        return Err(DerError::UnsupportedVersion);
+    }
+    decode_algorithm_identifier(&mut content_decoder)?;
+    let key = decode_tlv(&mut content_decoder, TAG_OCTET_STRING)?;
+    // we do not support attributes or the public key of version 2
+    expect_end(&mut content_decoder)?;
+    if key.len() != KEM_SECRET_KEY_BYTES {
+        return Err(DerError::InvalidKeyLength);
+    }
+    return io::kem_sk_from_bytes(&key).map_err(|_| DerError::InvalidKeyLength);
+}
+
+#[test]
+fn test_encode_oid() {
+    assert_eq!(vec![0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x01], encode_oid(&[1, 2, 840, 113549, 1, 1, 1]));
+    assert_eq!(vec![0x69, 0x83, 0x9E, 0x81, 0x92, 0x90, 0xCD, 0x8E, 0xAA, 0xA0, 0x9F, 0x9F, 0xA5, 0xA3, 0x95, 0xF2, 0xB6, 0xAE, 0xFC, 0x7C, 0x01],
+        encode_oid(&algorithm_oid(1)));
+}
+
+#[test]
+fn test_length_encoding() {
+    for length in &[0, 1, 0x7F, 0x80, 0xFF, 0x100, 0xFFFF] {
+        let content = vec![0x55; *length];
+        let encoded = tlv(TAG_OCTET_STRING, &content);
+        assert_eq!(Ok(content), decode_tlv(&mut io::bytes_decode(&encoded), TAG_OCTET_STRING));
+    }
+    assert_eq!(Err(DerError::InvalidLength), decode_tlv(&mut io::bytes_decode(&[0x04, 0x81, 0x05, 0, 0, 0, 0, 0]), TAG_OCTET_STRING));
+    assert_eq!(Err(DerError::InvalidLength), decode_tlv(&mut io::bytes_decode(&[0x04, 0x80, 0, 0]), TAG_OCTET_STRING));
+    assert_eq!(Err(DerError::Truncated), decode_tlv(&mut io::bytes_decode(&[0x04, 0x03, 0, 0]), TAG_OCTET_STRING));
+}
+
+#[test]
+fn test_spki() {
+    let (sk, pk) = super::kem::key_gen([1; 32], [2; 32]);
+    let spki = pk_to_spki(&pk);
+    assert_eq!(&[0x30, 0x82, 0x04, 0x5E, 0x30, 0x17, 0x06, 0x15][..], &spki[0..8]);
+    assert_eq!(io::pk_to_bytes(&pk), io::pk_to_bytes(&pk_from_spki(&spki).unwrap()));
+
+    let pkcs8 = sk_to_pkcs8(&sk);
+    assert_eq!(io::kem_sk_to_bytes(&sk), io::kem_sk_to_bytes(&sk_from_pkcs8(&pkcs8).unwrap()));
+}
+
+#[test]
+fn test_reject_malformed() {
+    let (sk, pk) = super::kem::key_gen([1; 32], [2; 32]);
+    let spki = pk_to_spki(&pk);
+
+    let mut wrong_oid = spki.clone();
+    wrong_oid[28] = 2;
+    assert_eq!(Err(DerError::UnknownAlgorithm), pk_from_spki(&wrong_oid).map(|_| ()));
+
+    let mut trailing = spki.clone();
+    trailing.push(0);
+    assert_eq!(Err(DerError::TrailingData), pk_from_spki(&trailing).map(|_| ()));
+
+    let mut unused_bits = spki.clone();
+    unused_bits[33] = 1;
+    assert_eq!(Err(DerError::InvalidKeyLength), pk_from_spki(&unused_bits).map(|_| ()));
+
+    let mut short_key = spki[..spki.len() - 1].to_vec();
+    short_key[2..4].copy_from_slice(&[0x04, 0x5D]);
+    short_key[31..33].copy_from_slice(&[0x04, 0x40]);
+    assert_eq!(Err(DerError::InvalidKeyLength), pk_from_spki(&short_key).map(|_| ()));
+
+    assert_eq!(Err(DerError::UnexpectedTag(0x30)), sk_from_pkcs8(&spki).map(|_| ()));
+
+    let mut wrong_version = sk_to_pkcs8(&sk);
+    wrong_version[6] = 1;
+    assert_eq!(Err(DerError::UnsupportedVersion), sk_from_pkcs8(&wrong_version).map(|_| ()));
+}
@@ -174,7 +174,8 @@ pub struct Base64Encoder<C>
 {
     consumer: C,
     current_buffer: BigEndianBitQueue,
-    symbol_count_mod_4: u8
+    symbol_count_mod_4: u8,
+    url_safe: bool
 }
 
 impl<C> Base64Encoder<C>
@@ -185,7 +186,20 @@ impl<C> Base64Encoder<C>
         Base64Encoder {
             consumer: consumer,
             current_buffer: BigEndianBitQueue::new(),
-            symbol_count_mod_4: 0
+            symbol_count_mod_4: 0,
+            url_safe: false
+        }
+    }
+
+    /// Creates an encoder for the unpadded base64url encoding (RFC 4648, section 5),
+    /// which uses - and _ instead of + and /
+    pub fn new_url_safe(consumer: C) -> Self
+    {
+        Base64Encoder {
+            consumer: consumer,
+            current_buffer: BigEndianBitQueue::new(),
+            symbol_count_mod_4: 0,
+            url_safe: true
         }
     }
 
@@ -198,8 +212,12 @@ impl<C> Base64Encoder<C>
             (self.consumer)(char::from(97 + (symbol - 26)));
         } else if symbol < 62 {
             (self.consumer)(char::from(48 + (symbol - 52)));
+        } else if symbol == 62 && self.url_safe {
+            (self.consumer)(char::from(45));
         } else if symbol == 62 {
             (self.consumer)(char::from(43));
+        } else if self.url_safe {
+            (self.consumer)(char::from(95));
         } else {
             (self.consumer)(char::from(47));
         }
@@ -216,6 +234,9 @@ impl<C> std::ops::Drop for Base64Encoder<C>
             let new_symbol = (self.current_buffer.read_bits(buffer_len) as u8) << (6 - buffer_len);
             self.append_symbol(new_symbol);
         }
+        if self.url_safe {
+            return;
+        }
         for _ in 0..((4 - self.symbol_count_mod_4) & 0b11) {
             (self.consumer)('=');
         }
@@ -254,7 +275,8 @@ pub struct Base64Decoder<P>
     where P: FnMut() -> Option<char>
 {
     producer: P,
-    current_buffer: BigEndianBitQueue
+    current_buffer: BigEndianBitQueue,
+    url_safe: bool
 }
 
 impl<P> Base64Decoder<P>
@@ -265,9 +287,22 @@ impl<P> Base64Decoder<P>
         Base64Decoder {
             producer: producer,
             current_buffer: BigEndianBitQueue::new(),
+            url_safe: false
+        }
+    }
+
+    /// Creates a decoder for the base64url encoding, see Base64Encoder::new_url_safe()
+    pub fn new_url_safe(producer: P) -> Base64Decoder<P>
+    {
+        Base64Decoder {
+            producer: producer,
+            current_buffer: BigEndianBitQueue::new(),
+            url_safe: true
         }
     }
 
+    /// Returns None at the end of the input and at the first character that is not part
+    /// of the alphabet, e.g. the padding
     fn read_symbol(&mut self) -> Option<u8> 
     {
         let c = (self.producer)()?;
@@ -275,9 +310,11 @@ impl<P> Base64Decoder<P>
             'a'..='z' => -71,
             'A'..='Z' => -65,
             '0'..='9' => 4,
-            '+' => 19,
-            '/' => 16,
-            _ => panic!("Unknown character \"{}\"", c)
+            '+' if !self.url_safe => 19,
+            '/' if !self.url_safe => 16,
+            '-' if self.url_safe => 17,
+            '_' if self.url_safe => -32,
+            _ => return None
         };
         let mut result: [u8; 1] = [0; 1];
         c.encode_utf8(&mut result);
@@ -302,7 +339,15 @@ impl<P> Decoder for Base64Decoder<P>
 pub trait Encodable: Sized
 {
     fn encode<T: Encoder>(&self, encoder: &mut T);
-    fn decode<T: Decoder>(data: &mut T) -> Self;
+
+    /// Returns None if the input is too short or is not a valid encoding, so this can be
+    /// used on untrusted input
+    fn try_decode<T: Decoder>(data: &mut T) -> Option<Self>;
+
+    fn decode<T: Decoder>(data: &mut T) -> Self
+    {
+        Self::try_decode(data).expect("Invalid input")
+    }
 }
 
 #[test]
@@ -323,6 +368,35 @@ fn test_base64_encode_decode() {
     assert_eq!(255, decoder.read().unwrap());
 }
 
+#[test]
+fn test_base64_url_safe() {
+    let mut encoded = String::new();
+    {
+        let mut encoder = Base64Encoder::new_url_safe(|c| encoded.push(c));
+        encoder.encode_bytes(&[0xFB, 0xFF, 0xBF, 0x41]);
+    }
+    assert_eq!("-_-_QQ", encoded);
+    let mut chars = encoded.chars();
+    let mut decoder = Base64Decoder::new_url_safe(|| chars.next());
+    assert_eq!(Some(0xFB), decoder.read());
+    assert_eq!(Some(0xFF), decoder.read());
+    assert_eq!(Some(0xBF), decoder.read());
+    assert_eq!(Some(0x41), decoder.read());
+    assert_eq!(None, decoder.read());
+}
+
+#[test]
+fn test_base64_invalid_character() {
+    let mut chars = "QUJD=QUJD".chars();
+    let mut decoder = Base64Decoder::new(|| chars.next());
+    assert_eq!(Some(65), decoder.read());
+    assert_eq!(Some(66), decoder.read());
+    assert_eq!(Some(67), decoder.read());
+    assert_eq!(None, decoder.read());
+    let mut chars = "+/".chars();
+    assert_eq!(None, Base64Decoder::new_url_safe(|| chars.next()).read());
+}
+
 #[test]
 fn test_bit_queue() {
     let mut queue = LittleEndianBitQueue::new();
@@ -0,0 +1,94 @@
+//! Fingerprints of public keys, i.e. the SHA3-256 hash of the canonical byte encoding
+//! of io.rs, together with some human readable renderings of it.
+
+use super::kyber::PublicKey;
+use super::io;
+use super::util;
+
+use sha3::digest::{ FixedOutput, Input };
+
+pub type Fingerprint = [u8; 32];
+
+/// The count of bytes shown in the short ID and the word rendering
+const SHORT_ID_BYTES: usize = 8;
+const WORDS_BYTES: usize = 16;
+
+const CONSONANTS: &[u8; 16] = b"bdfghjklmnprstvz";
+const VOWELS: &[u8; 4] = b"aiou";
+
+pub fn fingerprint(pk: &PublicKey) -> Fingerprint
+{
+    let mut hasher = sha3::Sha3_256::default();
+    hasher.input(&io::pk_to_bytes(pk));
+    let mut result = [0; 32];
+    result.copy_from_slice(&hasher.fixed_result());
+    return result;
+}
+
+pub fn to_hex(fingerprint: &Fingerprint) -> String
+{
+    util::to_hex(fingerprint)
+}
+
+/// The first 8 bytes in hex, comparable to the long key IDs of OpenPGP
+pub fn short_id(fingerprint: &Fingerprint) -> String
+{
+    util::to_hex(&fingerprint[0..SHORT_ID_BYTES])
+}
+
+/// The first 16 bytes as pronounceable proquints ("PRO-nouncable QUINT-uplets"),
+/// where each group of 16 bits becomes one word of the form consonant-vowel-consonant-
+/// vowel-consonant, e.g. lusab-babad for the bytes 7F 00 00 01
+pub fn to_words(fingerprint: &Fingerprint) -> String
+{
+    let words: Vec<String> = fingerprint[0..WORDS_BYTES].chunks(2).map(|pair| {
+        let value = ((pair[0] as usize) << 8) | pair[1] as usize;
+        [
+            CONSONANTS[value >> 12], VOWELS[(value >> 10) & 0x3], CONSONANTS[(value >> 6) & 0xF],
+            VOWELS[(value >> 4) & 0x3], CONSONANTS[value & 0xF]
+        ].iter().map(|c| *c as char).collect()
+    }).collect();
+    return words.join("-");
+}
+
+/// Checks whether the query identifies the fingerprint. The query may be a prefix of the
+/// hex rendering (including the short ID) with at least 8 digits, or a prefix of the word
+/// rendering of at least two words; case, spaces and colons are ignored.
+pub fn matches(fingerprint: &Fingerprint, query: &str) -> bool
+{
+    let normalized: String = query.chars().filter(|c| *c != ' ' && *c != ':').flat_map(char::to_lowercase).collect();
+    if normalized.len() >= 8 && normalized.chars().all(|c| c.is_ascii_hexdigit()) {
+        return to_hex(fingerprint).starts_with(&normalized);
+    }
+    let words = to_words(fingerprint);
+    return normalized.split('-').count() >= 2 && (words == normalized || words.starts_with(&(normalized + "-")));
+}
+
+#[test]
+fn test_to_words() {
+    let mut fingerprint = [0; 32];
+    fingerprint[0..4].copy_from_slice(&[0x7F, 0x00, 0x00, 0x01]);
+    fingerprint[4..8].copy_from_slice(&[0x3F, 0x54, 0xDC, 0xC1]);
+    assert_eq!("lusab-babad-gutih-tugad-babab-babab-babab-babab", to_words(&fingerprint));
+}
+
+#[test]
+fn test_fingerprint() {
+    let (_, pk) = super::kyber::key_gen([1; 32], [2; 32]);
+    let (_, other_pk) = super::kyber::key_gen([1; 32], [3; 32]);
+    let fingerprint = fingerprint(&pk);
+    assert_eq!(64, to_hex(&fingerprint).len());
+    assert_eq!(16, short_id(&fingerprint).len());
+    assert!(to_hex(&fingerprint).starts_with(&short_id(&fingerprint)));
+    assert_ne!(fingerprint, super::fingerprint::fingerprint(&other_pk));
+
+    assert!(matches(&fingerprint, &to_hex(&fingerprint)));
+    assert!(matches(&fingerprint, &short_id(&fingerprint).to_uppercase()));
+    assert!(matches(&fingerprint, &to_words(&fingerprint)));
+    let words = to_words(&fingerprint);
+    assert!(matches(&fingerprint, &words[0..11]));
+    assert!(!matches(&fingerprint, &words[0..5]));
+    assert!(!matches(&fingerprint, &words[0..9]));
+    assert!(!matches(&fingerprint, &short_id(&fingerprint)[0..6]));
+    assert!(!matches(&fingerprint, &short_id(&super::fingerprint::fingerprint(&other_pk))));
+}
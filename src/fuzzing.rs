@@ -0,0 +1,191 @@
+//! The bodies of the fuzz targets in fuzz/fuzz_targets. They feed arbitrary input to all
+//! parsers of a kind and check that decoding either fails with an error, or gives a value
+//! that encodes to the input again; a panic is a bug. The tests below also run them on the
+//! seed corpus in fuzz/corpus, so they are exercised without cargo-fuzz. Outside of the
+//! tests, this module is only compiled with the feature fuzzing, which fuzz/Cargo.toml
+//! enables.
+
+use super::kyber::*;
+use super::kem;
+use super::kem::{ KemSecretKey, KEM_SECRET_KEY_BYTES, KEM_SECRET_SEED_BYTES };
+use super::hybrid;
+use super::io;
+use super::der;
+use super::pem;
+use super::jwk;
+use super::jwk::JwkEncodable;
+use super::cose::CoseKeyEncodable;
+use super::container;
+use super::encoding::{ Encoder, Decoder, Encodable, Base64Decoder };
+
+/// Public keys, secret keys and secret seeds, in binary form and in all text formats
+pub fn parse_keys(data: &[u8])
+{
+    if let Ok(sk) = io::sk_from_bytes(data) {
+        let mut encoded = Vec::new();
+        sk.encode(&mut io::bytes_encode(&mut encoded));
+        assert_eq!(&data[..SECRET_KEY_BYTES], &encoded[..]);
+    }
+    if let Ok(sk) = io::kem_sk_from_bytes(data) {
+        assert_eq!(&data[..KEM_SECRET_KEY_BYTES], &io::kem_sk_to_bytes(&sk)[..]);
+    }
+    if let Ok(seed) = io::secret_seed_from_bytes(data) {
+        assert_eq!(&data[..KEM_SECRET_SEED_BYTES], &io::secret_seed_to_bytes(&seed)[..]);
+    }
+    if let Ok(pk) = io::pk_from_bytes(data) {
+        let pk: PublicKey = pk;
+        assert_eq!(&data[..PUBLIC_KEY_BYTES], &io::pk_to_bytes(&pk)[..]);
+    }
+    if let Ok(pk) = io::validate_public_key(data) {
+        assert_eq!(data, &io::pk_to_bytes(&pk)[..]);
+    }
+    if let Ok(pk) = hybrid::pk_from_bytes(data) {
+        assert_eq!(data, &hybrid::pk_to_bytes(&pk)[..]);
+    }
+    if let Ok(pk) = der::pk_from_spki(data) {
+        assert_eq!(data, &der::pk_to_spki(&pk)[..]);
+    }
+    if let Ok(sk) = der::sk_from_pkcs8(data) {
+        assert_eq!(data, &der::sk_to_pkcs8(&sk)[..]);
+    }
+    let _ = PublicKey::from_cose_key(data);
+    let _ = KemSecretKey::from_cose_key(data);
+
+    if let Ok(text) = std::str::from_utf8(data) {
+        let _ = io::read_sk(text);
+        let _ = io::read_pk(text);
+        let _ = io::validate_public_key_base64(text);
+        let _ = pem::read_public_key(text);
+        let _ = pem::read_secret_key(text);
+        let _ = pem::read_secret_seed(text);
+        let _ = PublicKey::from_jwk(text);
+        let _ = KemSecretKey::from_jwk(text);
+    }
+}
+
+/// Kyber and hybrid ciphertexts, in binary and base64 form
+pub fn parse_ciphertext(data: &[u8])
+{
+    if let Ok(ciphertext) = io::ciphertext_from_bytes(data) {
+        let ciphertext: Ciphertext = ciphertext;
+        assert_eq!(&data[..CIPHERTEXT_BYTES], &io::ciphertext_to_bytes(&ciphertext)[..]);
+    }
+    if let Ok(ciphertext) = io::validate_ciphertext(data) {
+        assert_eq!(data, &io::ciphertext_to_bytes(&ciphertext)[..]);
+    }
+    if let Ok(ciphertext) = hybrid::validate_ciphertext(data) {
+        assert_eq!(data, &hybrid::ciphertext_to_bytes(&ciphertext)[..]);
+    }
+
+    if let Ok(text) = std::str::from_utf8(data) {
+        let _ = io::read_ciphertext(text);
+        let _ = io::validate_ciphertext_base64(text);
+    }
+}
+
+/// The base64 decoders, with and without the checks of the callers
+pub fn base64(data: &[u8])
+{
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        Err(_) => return
+    };
+    let mut chars = text.chars();
+    let mut decoder = Base64Decoder::new(|| chars.next());
+    while let Some(_) = decoder.read_bits(5) {}
+    let mut chars = text.chars();
+    let mut decoder = Base64Decoder::new_url_safe(|| chars.next());
+    while let Some(_) = decoder.read() {}
+
+    if let Ok(decoded) = io::base64_decode_canonical(text) {
+        let mut encoded = String::new();
+        io::base64_encode(&mut encoded).encode_bytes(&decoded);
+        assert_eq!(text, encoded);
+    }
+    if let Some(decoded) = jwk::base64url_decode(text) {
+        assert_eq!(text, jwk::base64url_encode(&decoded));
+    }
+    if let Some((label, decoded)) = pem::decode(text) {
+        assert!(pem::decode(&pem::encode(&label, &decoded)) == Some((label, decoded)));
+    }
+    let _ = io::read_message(text);
+}
+
+/// The fixed key pair that decrypt() uses, so that the fuzzer only has to find ciphertexts
+pub fn decryption_key() -> hybrid::HybridSecretKey
+{
+    hybrid::key_gen([1; 32], [2; 32], [3; 32]).0
+}
+
+/// Decapsulation of Kyber.KEM and of the hybrid scheme, and decryption of containers
+pub fn decrypt(sk: &hybrid::HybridSecretKey, data: &[u8])
+{
+    if let Ok(ciphertext) = io::ciphertext_from_bytes(data) {
+        let _ = kem::decapsulate(&sk.0, &ciphertext);
+    }
+    if let Ok(ciphertext) = hybrid::validate_ciphertext(data) {
+        let _ = hybrid::decapsulate(sk, &ciphertext);
+    }
+    if let Ok(text) = std::str::from_utf8(data) {
+        if let (Ok(ciphertext), Ok(secret_key)) = (io::read_ciphertext(text), io::read_sk(text)) {
+            let _ = super::kyber::decrypt(secret_key, ciphertext);
+        }
+    }
+    let _ = container::decrypt_stream(&sk.0, &mut &data[..], &mut Vec::new());
+}
+
+/// The files of the seed corpus of the given fuzz target
+#[cfg(test)]
+fn corpus(target: &str) -> Vec<Vec<u8>>
+{
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz").join("corpus").join(target);
+    let mut result: Vec<Vec<u8>> = std::fs::read_dir(path).unwrap().map(|entry| std::fs::read(entry.unwrap().path()).unwrap()).collect();
+    assert!(result.len() > 0);
+    result.sort();
+    return result;
+}
+
+/// Runs the fuzz target on the corpus, on prefixes and on inputs with a flipped bit or a
+/// replaced byte, which is what the fuzzer tries first
+#[cfg(test)]
+fn check_target<F>(target: &str, f: F)
+    where F: Fn(&[u8])
+{
+    for input in corpus(target) {
+        f(&input);
+        let step = std::cmp::max(1, input.len() / 16);
+        for len in (0..input.len()).step_by(step).chain(input.len().checked_sub(1)) {
+            f(&input[..len]);
+        }
+        for i in (0..input.len()).step_by(step) {
+            let mut mutated = input.clone();
+            mutated[i] ^= 1 << (i % 8);
+            f(&mutated);
+            for byte in [0x00, b'=', b'-', 0xFF].iter() {
+                mutated[i] = *byte;
+                f(&mutated);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse_keys() {
+    check_target("parse_keys", parse_keys);
+}
+
+#[test]
+fn test_parse_ciphertext() {
+    check_target("parse_ciphertext", parse_ciphertext);
+}
+
+#[test]
+fn test_base64() {
+    check_target("base64", base64);
+}
+
+#[test]
+fn test_decrypt() {
+    let sk = decryption_key();
+    check_target("decrypt", |data| decrypt(&sk, data));
+}
@@ -0,0 +1,503 @@
+//! Arithmetic in Rq := Zq[X] / (X^N + 1) for arbitrary RingParams, e.g. the NewHope ring
+//! with q = 12289 and N = 1024. In contrast to ref_r.rs, the elements are stored in Vecs
+//! (as array lengths cannot depend on P::N) and the twiddle factors of the NTT are computed
+//! once when creating the Ring, instead of being hard-coded. Hence, this is slower than
+//! ref_r.rs, but can be used to experiment with other parameters. Element and NttElement
+//! implement the Rq traits of ring.rs (except for the compression, which is only defined
+//! for N = 256), using the Ring shared by all threads, see RingParams::ring().
+
+use super::zq::Zq;
+use super::ring_params::{ RingParams, unity_roots, check_params };
+use super::ring::{ RqElementCoefficientRepr, RqElementChineseRemainderRepr };
+use super::encoding;
+
+use std::ops::{ Add, Mul, Sub, AddAssign, MulAssign, SubAssign };
+use std::sync::atomic::{ AtomicPtr, Ordering };
+use std::ptr;
+
+/// The twiddle factors for the parameters P
+pub struct Ring<P: RingParams>
+{
+    /// The powers w^0, ..., w^(2N - 1) of the primitive 2N-th root of unity w
+    unity_roots: Vec<Zq<P>>,
+    /// The powers w^0, w^-1, ..., w^-(2N - 1)
+    rev_unity_roots: Vec<Zq<P>>,
+    inv_n: Zq<P>
+}
+
+/// An element of Rq, given by its N coefficients
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Element<P: RingParams>
+{
+    coefficients: Vec<Zq<P>>
+}
+
+/// An element of Rq, given by its values at the primitive 2N-th roots of unity, i.e. the
+/// value at w^(2i + 1) is stored at index i
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct NttElement<P: RingParams>
+{
+    values: Vec<Zq<P>>
+}
+
+impl<P: RingParams> Ring<P>
+{
+    pub fn new() -> Ring<P>
+    {
+        assert!(check_params::<P>(), "Invalid ring parameters q = {}, N = {}", P::Q, P::N);
+        let unity_roots = unity_roots::<P>();
+        let rev_unity_roots = (0..2 * P::N).map(|i| unity_roots[(2 * P::N - i) % (2 * P::N)]).collect();
+        Ring {
+            unity_roots: unity_roots,
+            rev_unity_roots: rev_unity_roots,
+            inv_n: Zq::one() / Zq::from(P::N as i16)
+        }
+    }
+
+    /// Returns the ring stored in the given static, which is created by the first call; this
+    /// is used to implement RingParams::ring()
+    pub fn get_or_create(ring: &'static AtomicPtr<Ring<P>>) -> &'static Ring<P>
+    {
+        let mut result = ring.load(Ordering::Acquire);
+        if result.is_null() {
+            let created = Box::into_raw(Box::new(Ring::<P>::new()));
+            result = match ring.compare_exchange(ptr::null_mut(), created, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => created,
+                Err(existing) => {
+                    // another thread stored its ring first
+                    drop(unsafe { Box::from_raw(created) });
+                    existing
+                }
+            };
+        }
+        // the stored ring is never freed or modified
+        return unsafe { &*result };
+    }
+
+    pub fn zero(&self) -> Element<P>
+    {
+        Element {
+            coefficients: vec![Zq::zero(); P::N]
+        }
+    }
+
+    /// Transforms the values x_0, ..., x_(N - 1) into x_0 + x_1 w^(2k) + ... for all k,
+    /// where unity_root(i) = w^i, using the iterative Cooley-Tukey FFT
+    fn fft<F>(mut values: Vec<Zq<P>>, unity_root: F) -> Vec<Zq<P>>
+        where F: Fn(usize) -> Zq<P>
+    {
+        let n = values.len();
+        let bits = n.trailing_zeros();
+        for i in 0..n {
+            let j = i.reverse_bits() >> (usize::max_value().count_ones() - bits);
+            if i < j {
+                values.swap(i, j);
+            }
+        }
+        // in each iteration, values consists of the DFTs of length len of the subsequences
+        // [x_j, x_(j + n/len), ...], which are combined using the len-th root of unity
+        // w^(2n/len)
+        let mut len = 2;
+        while len <= n {
+            for start in (0..n).step_by(len) {
+                for k in 0..len / 2 {
+                    let twiddle = unity_root(k * 2 * n / len) * values[start + k + len / 2];
+                    values[start + k + len / 2] = values[start + k] - twiddle;
+                    values[start + k] = values[start + k] + twiddle;
+                }
+            }
+            len <<= 1;
+        }
+        return values;
+    }
+
+    /// Computes the values at the primitive 2N-th roots of unity w^(2k + 1), which are the
+    /// values of sum x_i w^i X^i at the N-th roots of unity w^2k
+    pub fn to_ntt(&self, a: Element<P>) -> NttElement<P>
+    {
+        let mut coefficients = a.coefficients;
+        for i in 0..P::N {
+            coefficients[i] *= self.unity_roots[i];
+        }
+        NttElement {
+            values: Self::fft(coefficients, |i| self.unity_roots[i])
+        }
+    }
+
+    pub fn from_ntt(&self, a: NttElement<P>) -> Element<P>
+    {
+        let mut coefficients = Self::fft(a.values, |i| self.rev_unity_roots[i]);
+        for i in 0..P::N {
+            coefficients[i] *= self.rev_unity_roots[i] * self.inv_n;
+        }
+        Element {
+            coefficients: coefficients
+        }
+    }
+
+    pub fn mul(&self, a: &Element<P>, b: &Element<P>) -> Element<P>
+    {
+        self.from_ntt(self.to_ntt(a.clone()) * &self.to_ntt(b.clone()))
+    }
+}
+
+impl<P: RingParams> Element<P>
+{
+    pub fn coefficients(&self) -> &[Zq<P>]
+    {
+        &self.coefficients
+    }
+}
+
+impl<P: RingParams> NttElement<P>
+{
+    /// The value at w^(2 * zeta_index + 1)
+    pub fn value_at_zeta(&self, zeta_index: usize) -> Zq<P>
+    {
+        self.values[zeta_index]
+    }
+
+    pub fn values(&self) -> &[Zq<P>]
+    {
+        &self.values
+    }
+}
+
+impl<P: RingParams> From<Vec<Zq<P>>> for NttElement<P>
+{
+    fn from(values: Vec<Zq<P>>) -> NttElement<P>
+    {
+        assert_eq!(P::N, values.len());
+        NttElement {
+            values: values
+        }
+    }
+}
+
+impl<P: RingParams> From<Vec<Zq<P>>> for Element<P>
+{
+    fn from(coefficients: Vec<Zq<P>>) -> Element<P>
+    {
+        assert_eq!(P::N, coefficients.len());
+        Element {
+            coefficients: coefficients
+        }
+    }
+}
+
+impl<'a, P: RingParams> From<&'a [i16]> for Element<P>
+{
+    fn from(value: &'a [i16]) -> Element<P>
+    {
+        Element::from(value.iter().map(|x| Zq::from(*x)).collect::<Vec<_>>())
+    }
+}
+
+impl<'a, P: RingParams> From<&'a [Zq<P>]> for Element<P>
+{
+    fn from(value: &'a [Zq<P>]) -> Element<P>
+    {
+        Element::from(value.to_vec())
+    }
+}
+
+impl<'a, P: RingParams> From<&'a [i16]> for NttElement<P>
+{
+    fn from(value: &'a [i16]) -> NttElement<P>
+    {
+        NttElement::from(value.iter().map(|x| Zq::from(*x)).collect::<Vec<_>>())
+    }
+}
+
+impl<'a, P: RingParams> From<&'a [Zq<P>]> for NttElement<P>
+{
+    fn from(value: &'a [Zq<P>]) -> NttElement<P>
+    {
+        NttElement::from(value.to_vec())
+    }
+}
+
+/// Writes the values with P::encode_bits() bits each, as ref_r.rs for KyberParams
+impl<P: RingParams> encoding::Encodable for NttElement<P>
+{
+    fn encode<T: encoding::Encoder>(&self, encoder: &mut T)
+    {
+        for value in &self.values {
+            encoder.encode_bits(value.representative_pos() as u16, P::encode_bits());
+        }
+    }
+
+    fn try_decode<T: encoding::Decoder>(data: &mut T) -> Option<Self>
+    {
+        Some(NttElement {
+            values: (0..P::N).map(|_| Zq::try_decode(data)).collect::<Option<Vec<_>>>()?
+        })
+    }
+}
+
+impl<P: RingParams> RqElementCoefficientRepr<P> for Element<P>
+{
+    type ChineseRemainderRepr = NttElement<P>;
+
+    fn get_zero() -> Element<P>
+    {
+        Element {
+            coefficients: vec![Zq::zero(); P::N]
+        }
+    }
+
+    fn to_chinese_remainder_repr(self) -> NttElement<P>
+    {
+        P::ring().to_ntt(self)
+    }
+}
+
+impl<P: RingParams> RqElementChineseRemainderRepr<P> for NttElement<P>
+{
+    type CoefficientRepr = Element<P>;
+
+    fn get_zero() -> NttElement<P>
+    {
+        NttElement {
+            values: vec![Zq::zero(); P::N]
+        }
+    }
+
+    fn to_coefficient_repr(self) -> Element<P>
+    {
+        P::ring().from_ntt(self)
+    }
+
+    fn value_at_zeta(&self, zeta_index: usize) -> Zq<P>
+    {
+        self.values[zeta_index]
+    }
+
+    fn mul_scalar(&mut self, x: Zq<P>)
+    {
+        for i in 0..P::N {
+            self.values[i] *= x;
+        }
+    }
+
+    fn add_product(&mut self, a: &NttElement<P>, b: &NttElement<P>)
+    {
+        for i in 0..P::N {
+            self.values[i] += a.values[i] * b.values[i];
+        }
+    }
+}
+
+impl<'a, P: RingParams> AddAssign<&'a Element<P>> for Element<P>
+{
+    fn add_assign(&mut self, rhs: &'a Element<P>)
+    {
+        for i in 0..P::N {
+            self.coefficients[i] += rhs.coefficients[i];
+        }
+    }
+}
+
+impl<'a, P: RingParams> SubAssign<&'a Element<P>> for Element<P>
+{
+    fn sub_assign(&mut self, rhs: &'a Element<P>)
+    {
+        for i in 0..P::N {
+            self.coefficients[i] -= rhs.coefficients[i];
+        }
+    }
+}
+
+impl<P: RingParams> MulAssign<Zq<P>> for Element<P>
+{
+    fn mul_assign(&mut self, rhs: Zq<P>)
+    {
+        for i in 0..P::N {
+            self.coefficients[i] *= rhs;
+        }
+    }
+}
+
+impl<'a, P: RingParams> Add<&'a Element<P>> for Element<P>
+{
+    type Output = Element<P>;
+
+    fn add(mut self, rhs: &'a Element<P>) -> Element<P>
+    {
+        self += rhs;
+        return self;
+    }
+}
+
+impl<'a, P: RingParams> Sub<&'a Element<P>> for Element<P>
+{
+    type Output = Element<P>;
+
+    fn sub(mut self, rhs: &'a Element<P>) -> Element<P>
+    {
+        self -= rhs;
+        return self;
+    }
+}
+
+impl<P: RingParams> Mul<Zq<P>> for Element<P>
+{
+    type Output = Element<P>;
+
+    fn mul(mut self, rhs: Zq<P>) -> Element<P>
+    {
+        self *= rhs;
+        return self;
+    }
+}
+
+impl<'a, P: RingParams> AddAssign<&'a NttElement<P>> for NttElement<P>
+{
+    fn add_assign(&mut self, rhs: &'a NttElement<P>)
+    {
+        for i in 0..P::N {
+            self.values[i] += rhs.values[i];
+        }
+    }
+}
+
+impl<'a, P: RingParams> Add<&'a NttElement<P>> for NttElement<P>
+{
+    type Output = NttElement<P>;
+
+    fn add(mut self, rhs: &'a NttElement<P>) -> NttElement<P>
+    {
+        self += rhs;
+        return self;
+    }
+}
+
+impl<'a, P: RingParams> SubAssign<&'a NttElement<P>> for NttElement<P>
+{
+    fn sub_assign(&mut self, rhs: &'a NttElement<P>)
+    {
+        for i in 0..P::N {
+            self.values[i] -= rhs.values[i];
+        }
+    }
+}
+
+impl<'a, P: RingParams> Sub<&'a NttElement<P>> for NttElement<P>
+{
+    type Output = NttElement<P>;
+
+    fn sub(mut self, rhs: &'a NttElement<P>) -> NttElement<P>
+    {
+        self -= rhs;
+        return self;
+    }
+}
+
+impl<'a, P: RingParams> MulAssign<&'a NttElement<P>> for NttElement<P>
+{
+    fn mul_assign(&mut self, rhs: &'a NttElement<P>)
+    {
+        for i in 0..P::N {
+            self.values[i] *= rhs.values[i];
+        }
+    }
+}
+
+impl<'a, P: RingParams> Mul<&'a NttElement<P>> for NttElement<P>
+{
+    type Output = NttElement<P>;
+
+    fn mul(mut self, rhs: &'a NttElement<P>) -> NttElement<P>
+    {
+        self *= rhs;
+        return self;
+    }
+}
+
+#[cfg(test)]
+use super::ring_params::{ KyberParams, NewHopeParams, Toy16Params, Toy64Params };
+
+/// Some fixed element with coefficients spread over Zq
+#[cfg(test)]
+fn test_element<P: RingParams>(seed: u32) -> Element<P>
+{
+    Element::from((0..P::N as u64).map(|i| Zq::from(((i * i * 7919 + seed as u64 * 104729 + i) % P::Q as u64) as i16)).collect::<Vec<_>>())
+}
+
+#[cfg(test)]
+fn schoolbook_mul<P: RingParams>(a: &Element<P>, b: &Element<P>) -> Element<P>
+{
+    let mut result = vec![Zq::zero(); P::N];
+    for i in 0..P::N {
+        for j in 0..P::N {
+            let product = a.coefficients[i] * b.coefficients[j];
+            if i + j < P::N {
+                result[i + j] += product;
+            } else {
+                result[i + j - P::N] -= product;
+            }
+        }
+    }
+    return Element::from(result);
+}
+
+#[cfg(test)]
+fn check_ring<P: RingParams>()
+{
+    let ring = Ring::<P>::new();
+    let a = test_element::<P>(1);
+    let b = test_element::<P>(2);
+    assert_eq!(a, ring.from_ntt(ring.to_ntt(a.clone())));
+    assert_eq!(schoolbook_mul(&a, &b), ring.mul(&a, &b));
+
+    // the value at w is a(w)
+    let w = Zq::<P>::from_perfect(P::ROOT_OF_UNITY as i16);
+    let value = (0..P::N).rev().fold(Zq::zero(), |current, i| current * w + a.coefficients[i]);
+    assert_eq!(value, ring.to_ntt(a).value_at_zeta(0));
+}
+
+#[test]
+fn test_rings()
+{
+    check_ring::<Toy16Params>();
+    check_ring::<Toy64Params>();
+    check_ring::<KyberParams>();
+    check_ring::<NewHopeParams>();
+}
+
+#[test]
+fn test_x_to_n_is_minus_one()
+{
+    let ring = Ring::<NewHopeParams>::new();
+    let mut x = ring.zero();
+    x.coefficients[NewHopeParams::N / 2] = Zq::one();
+    let mut expected = ring.zero();
+    expected.coefficients[0] = -Zq::one();
+    assert_eq!(expected, ring.mul(&x, &x));
+}
+
+#[test]
+fn test_matches_ref_r()
+{
+    use super::kyber::RqElement;
+    use super::ring::{ RqElementCoefficientRepr, RqElementChineseRemainderRepr };
+    use super::util;
+
+    let ring = Ring::<KyberParams>::new();
+    let a = test_element::<KyberParams>(3);
+    let b = test_element::<KyberParams>(4);
+    let to_ref = |x: &Element<KyberParams>| RqElement::from(util::create_array(|i| x.coefficients[i]));
+    let expected = (to_ref(&a).to_chinese_remainder_repr() * &to_ref(&b).to_chinese_remainder_repr()).to_coefficient_repr();
+    assert_eq!(expected, to_ref(&ring.mul(&a, &b)));
+}
+
+#[test]
+fn test_get_reuses_ring()
+{
+    let ring = KyberParams::ring() as *const Ring<KyberParams> as usize;
+    assert_eq!(ring, KyberParams::ring() as *const Ring<KyberParams> as usize);
+    let other_thread = std::thread::spawn(|| KyberParams::ring() as *const Ring<KyberParams> as usize);
+    assert_eq!(ring, other_thread.join().unwrap());
+    assert_eq!(Toy16Params::N, Toy16Params::ring().zero().coefficients().len());
+    assert_eq!(KyberParams::N, KyberParams::ring().zero().coefficients().len());
+}
@@ -0,0 +1,86 @@
+//! HMAC and the extract-then-expand key derivation function HKDF (RFC 5869),
+//! both instantiated with SHA3-256.
+
+use sha3::digest::{ FixedOutput, Input };
+
+pub const HASH_BYTES: usize = 32;
+/// The rate of SHA3-256, which is used as block size for HMAC
+const BLOCK_BYTES: usize = 136;
+
+fn sha3_256(data: &[&[u8]]) -> [u8; HASH_BYTES]
+{
+    let mut hasher = sha3::Sha3_256::default();
+    for part in data {
+        hasher.input(part);
+    }
+    let mut result = [0; HASH_BYTES];
+    result.copy_from_slice(&hasher.fixed_result());
+    return result;
+}
+
+pub fn hmac(key: &[u8], data: &[&[u8]]) -> [u8; HASH_BYTES]
+{
+    let mut block_key = [0; BLOCK_BYTES];
+    if key.len() > BLOCK_BYTES {
+        block_key[0..HASH_BYTES].copy_from_slice(&sha3_256(&[key]));
+    } else {
+        block_key[0..key.len()].copy_from_slice(key);
+    }
+    let inner_key: [u8; BLOCK_BYTES] = super::util::create_array(|i| block_key[i] ^ 0x36);
+    let outer_key: [u8; BLOCK_BYTES] = super::util::create_array(|i| block_key[i] ^ 0x5C);
+
+    let mut inner_data: Vec<&[u8]> = vec![&inner_key];
+    inner_data.extend_from_slice(data);
+    let inner_hash = sha3_256(&inner_data);
+    return sha3_256(&[&outer_key, &inner_hash]);
+}
+
+/// Extracts a pseudorandom key from the input keying material; an empty salt is
+/// replaced by a string of zeros
+pub fn extract(salt: &[u8], ikm: &[&[u8]]) -> [u8; HASH_BYTES]
+{
+    if salt.len() == 0 {
+        hmac(&[0; HASH_BYTES], ikm)
+    } else {
+        hmac(salt, ikm)
+    }
+}
+
+/// Expands the pseudorandom key to the given length, which may be at most 255 * 32 bytes
+pub fn expand(prk: &[u8; HASH_BYTES], info: &[&[u8]], length: usize) -> Vec<u8>
+{
+    assert!(length <= 255 * HASH_BYTES, "HKDF output length too large");
+    let mut result: Vec<u8> = Vec::with_capacity(length + HASH_BYTES);
+    let mut block: Vec<u8> = Vec::new();
+    let mut counter: u8 = 1;
+    while result.len() < length {
+        let counter_data = [counter];
+        let mut block_data: Vec<&[u8]> = vec![&block];
+        block_data.extend_from_slice(info);
+        block_data.push(&counter_data);
+        block = hmac(prk, &block_data).to_vec();
+        result.extend_from_slice(&block);
+        // wraps only after the last of at most 255 blocks
+        counter = counter.wrapping_add(1);
+    }
+    result.truncate(length);
+    return result;
+}
+
+#[test]
+fn test_hmac() {
+    let mac = hmac(b"key", &[b"The quick brown fox ", b"jumps over the lazy dog"]);
+    assert_eq!("8c6e0683409427f8931711b10ca92a506eb1fafa48fadd66d76126f47ac2c333", super::util::to_hex(&mac));
+    let mac = hmac(&[0xAA; 200], &[b"Test Using Larger Than Block-Size Key - Hash Key First"]);
+    assert_eq!("49ad92b02124fdac9627ae45e008a696182ab6bfb8470457777c744aeb9df06f", super::util::to_hex(&mac));
+}
+
+#[test]
+fn test_hkdf() {
+    let salt: Vec<u8> = (0..13).collect();
+    let info: Vec<u8> = (0xF0..0xFA).collect();
+    let prk = extract(&salt, &[&[0x0B; 22]]);
+    assert_eq!("7d4194836f7a113a44677abc825640ade07af1c1d69a9a4b109b280a8fe54ef0", super::util::to_hex(&prk));
+    let okm = expand(&prk, &[&info], 42);
+    assert_eq!("0c5160501d65021deaf2c14f5abce04c5bd2635abceeba61c2edb6e8ed72674900557728f2c9f2c4c179", super::util::to_hex(&okm));
+}
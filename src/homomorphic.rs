@@ -0,0 +1,298 @@
+//! Additively homomorphic operations on Kyber.CPA ciphertexts. Before compression, a
+//! ciphertext (u, v) with v - s^T u = q/p * m + noise can be added to another one,
+//! multiplied by a public scalar or shifted by a public plaintext, and the result still
+//! decrypts to the corresponding combination of the messages modulo p = 2^D, as long as
+//! the accumulated noise stays below q/2p. Each ciphertext therefore carries an estimate
+//! of its noise, which tells the caller when decryption might fail.
+//!
+//! This is only a prototype: the operations are neither constant time nor do they hide
+//! which operations were applied, and all ciphertexts must belong to the same public key.
+
+use super::kyber::*;
+use super::zq::*;
+use super::rqvec::DIM;
+use super::ring::{ RqElementCompression, CompressedRq, N };
+use super::util;
+
+/// Variance of the centered binomial distribution with parameter eta
+const ERROR_VARIANCE: f64 = ETA as f64 / 2.;
+
+/// The noise of a ciphertext, modelled as a centered random part with the given variance
+/// per coefficient plus a deterministic part that is at most `rounding` in absolute value
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct NoiseBound
+{
+    variance: f64,
+    rounding: f64
+}
+
+/// Variance of the error introduced by compressing a uniform element of Zq to D bits
+fn compression_variance(d: u16) -> f64
+{
+    let step = Q as f64 / (1u32 << d) as f64;
+    return step * step / 12.;
+}
+
+/// Worst-case error introduced by compressing an element of Zq to D bits
+fn compression_bound(d: u16) -> f64
+{
+    Q as f64 / (1u32 << (d + 1)) as f64
+}
+
+/// Variance of a coefficient of x^T y, where x, y are vectors of DIM polynomials with
+/// independent coefficients of the given variances
+fn inner_product_variance(x: f64, y: f64) -> f64
+{
+    (DIM * N) as f64 * x * y
+}
+
+/// The noise of v - s^T u for a fresh encryption, i.e. e^T r + e2 - s^T e1 + d^T r
+/// where d is the error of compressing t
+fn fresh_noise() -> NoiseBound
+{
+    NoiseBound {
+        variance: inner_product_variance(ERROR_VARIANCE, ERROR_VARIANCE) * 2. + ERROR_VARIANCE +
+            inner_product_variance(compression_variance(COMPRESSED_VECTOR_BIT_SIZE), ERROR_VARIANCE),
+        // the message is encoded as the nearest integer to q/p * m
+        rounding: 0.5
+    }
+}
+
+/// A Kyber.CPA ciphertext before compression, whose message is an element of
+/// (Z / 2^D Z)[X] / (X^N + 1), encoded as in `RqElement::decompress`
+#[derive(Clone, Debug)]
+pub struct UncompressedCiphertext<const D: u16>
+{
+    u: RqVector,
+    v: RqElement,
+    noise: NoiseBound
+}
+
+/// The result of the noise estimator
+#[derive(Clone, Copy, Debug)]
+pub struct NoiseEstimate
+{
+    /// Standard deviation of the random part of the noise, per coefficient
+    pub standard_deviation: f64,
+    /// How much random noise a coefficient can take before it decrypts wrongly, i.e.
+    /// q/2p minus the deterministic part of the noise
+    pub margin: f64,
+    /// An (approximate) upper bound on the probability that at least one coefficient
+    /// decrypts wrongly, using a Gaussian approximation of the noise and a union bound
+    pub failure_probability: f64
+}
+
+impl<const D: u16> UncompressedCiphertext<D>
+{
+    pub fn u(&self) -> &RqVector
+    {
+        &self.u
+    }
+
+    pub fn v(&self) -> &RqElement
+    {
+        &self.v
+    }
+
+    pub fn noise_estimate(&self) -> NoiseEstimate
+    {
+        let standard_deviation = self.noise.variance.sqrt();
+        let margin = Q as f64 / (1u32 << (D + 1)) as f64 - self.noise.rounding;
+        let failure_probability = if margin <= 0. {
+            1.
+        } else if standard_deviation == 0. {
+            0.
+        } else {
+            (N as f64 * erfc(margin / (standard_deviation * std::f64::consts::SQRT_2))).min(1.)
+        };
+        return NoiseEstimate {
+            standard_deviation: standard_deviation,
+            margin: margin,
+            failure_probability: failure_probability
+        };
+    }
+
+    /// Whether the estimated probability of a decryption failure exceeds the given one
+    pub fn might_fail(&self, max_failure_probability: f64) -> bool
+    {
+        self.noise_estimate().failure_probability > max_failure_probability
+    }
+}
+
+impl UncompressedCiphertext<1>
+{
+    /// Decompresses a Kyber.CPA ciphertext, whose message is a bit vector. Note that the
+    /// compression error of v is large, so there is less room for further operations than
+    /// for a ciphertext obtained by `encrypt`.
+    pub fn from_compressed(c: &Ciphertext) -> UncompressedCiphertext<1>
+    {
+        let fresh = fresh_noise();
+        UncompressedCiphertext {
+            u: RqVector::decompress(&c.0),
+            v: RqElement::decompress(&c.1),
+            noise: NoiseBound {
+                variance: fresh.variance + inner_product_variance(compression_variance(COMPRESSED_VECTOR_BIT_SIZE), ERROR_VARIANCE),
+                rounding: fresh.rounding + compression_bound(COMPRESSED_RING_ELEMENT_BIT_SIZE)
+            }
+        }
+    }
+
+    pub fn to_compressed(self) -> Ciphertext
+    {
+        (self.u.compress(), self.v.compress())
+    }
+}
+
+pub fn message_from_values<const D: u16>(values: &[u16; N]) -> CompressedRq<D>
+{
+    CompressedRq {
+        data: util::create_array(|i| CompressedZq { data: values[i] % (1 << D) })
+    }
+}
+
+pub fn message_values<const D: u16>(m: &CompressedRq<D>) -> [u16; N]
+{
+    util::create_array(|i| m.data[i].data)
+}
+
+pub fn encrypt<const D: u16>(pk: &PublicKey, m: &CompressedRq<D>, enc_seed: Seed) -> UncompressedCiphertext<D>
+{
+    let (u, v) = encrypt_uncompressed(pk, &RqElement::decompress(m), enc_seed);
+    return UncompressedCiphertext {
+        u: u,
+        v: v,
+        noise: fresh_noise()
+    };
+}
+
+pub fn decrypt<const D: u16>(sk: &SecretKey, c: &UncompressedCiphertext<D>) -> CompressedRq<D>
+{
+    decrypt_uncompressed(sk, &c.u, c.v.clone()).compress()
+}
+
+/// Returns an encryption of a + b; both must be encrypted under the same public key
+/// with independent randomness
+pub fn ct_add<const D: u16>(a: &UncompressedCiphertext<D>, b: &UncompressedCiphertext<D>) -> UncompressedCiphertext<D>
+{
+    UncompressedCiphertext {
+        u: a.u.clone() + &b.u,
+        v: a.v.clone() + &b.v,
+        noise: NoiseBound {
+            variance: a.noise.variance + b.noise.variance,
+            rounding: a.noise.rounding + b.noise.rounding
+        }
+    }
+}
+
+/// Returns an encryption of scalar * a
+pub fn ct_scalar_mul<const D: u16>(a: &UncompressedCiphertext<D>, scalar: i16) -> UncompressedCiphertext<D>
+{
+    let factor = ZqElement::from(scalar);
+    let mut u = a.u.clone();
+    u *= factor;
+    let scalar = scalar as f64;
+    return UncompressedCiphertext {
+        u: u,
+        v: a.v.clone() * factor,
+        noise: NoiseBound {
+            variance: a.noise.variance * scalar * scalar,
+            rounding: a.noise.rounding * scalar.abs()
+        }
+    };
+}
+
+/// Returns an encryption of a + m for a public plaintext m
+pub fn ct_plain_add<const D: u16>(a: &UncompressedCiphertext<D>, m: &CompressedRq<D>) -> UncompressedCiphertext<D>
+{
+    UncompressedCiphertext {
+        u: a.u.clone(),
+        v: a.v.clone() + &RqElement::decompress(m),
+        noise: NoiseBound {
+            variance: a.noise.variance,
+            rounding: a.noise.rounding + 0.5
+        }
+    }
+}
+
+/// The complementary error function, with a relative error below 1.2 * 10^-7
+/// (see Numerical Recipes, erfcc)
+fn erfc(x: f64) -> f64
+{
+    let z = x.abs();
+    let t = 1. / (1. + 0.5 * z);
+    let result = t * (-z * z - 1.26551223 + t * (1.00002368 + t * (0.37409196 + t * (0.09678418 +
+        t * (-0.18628806 + t * (0.27886807 + t * (-1.13520398 + t * (1.48851587 +
+        t * (-0.82215223 + t * 0.17087277))))))))).exp();
+    return if x >= 0. { result } else { 2. - result };
+}
+
+#[cfg(test)]
+fn test_bits(i: u8) -> [u16; N]
+{
+    let mut hasher = expand_randomness_shake_256(test_seed(i));
+    let mut bytes = [0; N];
+    sha3::digest::XofReader::read(&mut hasher, &mut bytes);
+    return util::create_array(|j| (bytes[j] & 1) as u16);
+}
+
+#[test]
+fn test_erfc()
+{
+    assert!((erfc(0.) - 1.).abs() < 1e-7);
+    assert!((erfc(1.) - 0.157299207).abs() < 1e-7);
+    assert!((erfc(-1.) - 1.842700793).abs() < 1e-7);
+    assert!((erfc(5.) / 1.5374597944e-12 - 1.).abs() < 1e-6);
+}
+
+#[test]
+fn test_weighted_sum_of_bit_vectors()
+{
+    let (sk, pk) = key_gen(test_seed(0), test_seed(1));
+    let bits = [test_bits(2), test_bits(3), test_bits(4)];
+    let offset: [u16; N] = util::create_array(|i| (i % 4) as u16);
+
+    let a = encrypt::<2>(&pk, &message_from_values(&bits[0]), test_seed(5));
+    let b = encrypt::<2>(&pk, &message_from_values(&bits[1]), test_seed(6));
+    let c = encrypt::<2>(&pk, &message_from_values(&bits[2]), test_seed(7));
+    assert!(!a.might_fail(1e-20));
+
+    let sum = ct_plain_add(&ct_add(&ct_add(&a, &ct_scalar_mul(&b, 2)), &ct_scalar_mul(&c, -1)), &message_from_values(&offset));
+    assert!(!sum.might_fail(1e-2));
+
+    let result = message_values(&decrypt(&sk, &sum));
+    for i in 0..N {
+        let expected = (bits[0][i] + 2 * bits[1][i] + 4 - bits[2][i] + offset[i]) % 4;
+        assert_eq!(expected, result[i]);
+    }
+}
+
+#[test]
+fn test_noise_estimate_grows()
+{
+    let (_sk, pk) = key_gen(test_seed(0), test_seed(1));
+    let a = encrypt::<2>(&pk, &message_from_values(&test_bits(2)), test_seed(5));
+    let fresh = a.noise_estimate();
+    assert!(fresh.failure_probability < 1e-20);
+
+    assert!(ct_scalar_mul(&a, 20).might_fail(1e-3));
+    let mut sum = a.clone();
+    for i in 0..100 {
+        sum = ct_add(&sum, &encrypt::<2>(&pk, &message_from_values(&[0; N]), test_seed(10 + i)));
+    }
+    assert!(sum.noise_estimate().standard_deviation > 10. * fresh.standard_deviation);
+    assert!(sum.might_fail(1e-3));
+}
+
+#[test]
+fn test_compressed_roundtrip()
+{
+    let (sk, pk) = key_gen(test_seed(0), test_seed(1));
+    let m = CompressedRq::<1>::from_data([0xA7; 32]);
+    let a = UncompressedCiphertext::from_compressed(&super::kyber::encrypt(&pk, m.get_data(), test_seed(5)));
+    assert!(!a.might_fail(1e-20));
+    assert_eq!(m.get_data(), decrypt(&sk, &a).get_data());
+
+    let b = encrypt::<1>(&pk, &CompressedRq::from_data([0x0F; 32]), test_seed(6));
+    let sum = ct_add(&a, &b).to_compressed();
+    assert_eq!([0xA8; 32], super::kyber::decrypt(sk, sum));
+}
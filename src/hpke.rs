@@ -0,0 +1,375 @@
+//! Hybrid public key encryption following RFC 9180, with Kyber.KEM as KEM,
+//! HKDF-SHA3-256 as KDF and ChaCha20-Poly1305 as AEAD.
+//!
+//! The base mode uses plain Kyber keys. Kyber has no static-static operation like
+//! Diffie-Hellman, so the auth mode uses the hybrid keys from hybrid.rs instead:
+//! the sender is authenticated by an X25519 exchange between both static keys, while
+//! the ephemeral X25519 exchange and the Kyber encapsulation provide the secrecy,
+//! analogous to AuthEncap of DHKEM.
+//! PSK modes are not supported.
+
+use super::kyber::{ PublicKey, Seed, expand_randomness_shake_256 };
+use super::kem;
+use super::kem::KemSecretKey;
+use super::hybrid;
+use super::hybrid::{ HybridPublicKey, HybridSecretKey };
+use super::x25519::*;
+use super::hkdf;
+use super::chacha20poly1305 as aead;
+use super::chacha20poly1305::{ AeadKey, AeadNonce };
+use super::io;
+
+use sha3::digest::XofReader;
+
+const MODE_BASE: u8 = 0x00;
+const MODE_AUTH: u8 = 0x02;
+
+// The identifiers of the KEMs and the KDF are not registered with IANA
+const KEM_ID_KYBER: u16 = 0xFF01;
+const KEM_ID_KYBER_X25519: u16 = 0xFF02;
+const KDF_ID_HKDF_SHA3_256: u16 = 0xFF03;
+const AEAD_ID_CHACHA20_POLY1305: u16 = 0x0003;
+
+const VERSION_LABEL: &[u8] = b"HPKE-v1";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HpkeError
+{
+    /// The ciphertext could not be authenticated
+    OpenError,
+    /// The sequence number would overflow, so the context must not be used anymore
+    MessageLimitReached,
+    /// The encapsulated key has the wrong length or is not canonically encoded
+    DeserializeError,
+    /// The requested length of an exported secret is larger than MAX_EXPORT_LENGTH
+    InvalidLength
+}
+
+/// The maximal length of an exported secret, i.e. 255 * Nh for the hash length Nh
+pub const MAX_EXPORT_LENGTH: usize = 255 * hkdf::HASH_BYTES;
+
+fn labeled_extract(suite_id: &[u8], salt: &[u8], label: &[u8], ikm: &[u8]) -> [u8; hkdf::HASH_BYTES]
+{
+    hkdf::extract(salt, &[VERSION_LABEL, suite_id, label, ikm])
+}
+
+/// The length must be at most MAX_EXPORT_LENGTH, so that it fits into the two bytes of the
+/// labeled info
+fn labeled_expand(suite_id: &[u8], prk: &[u8; hkdf::HASH_BYTES], label: &[u8], info: &[u8], length: usize) -> Vec<u8>
+{
+    debug_assert!(length <= MAX_EXPORT_LENGTH);
+    let encoded_length = (length as u16).to_be_bytes();
+    hkdf::expand(prk, &[&encoded_length, VERSION_LABEL, suite_id, label, info], length)
+}
+
+fn kem_suite_id(kem_id: u16) -> Vec<u8>
+{
+    let mut result = b"KEM".to_vec();
+    result.extend_from_slice(&kem_id.to_be_bytes());
+    return result;
+}
+
+fn hpke_suite_id(kem_id: u16) -> Vec<u8>
+{
+    let mut result = b"HPKE".to_vec();
+    result.extend_from_slice(&kem_id.to_be_bytes());
+    result.extend_from_slice(&KDF_ID_HKDF_SHA3_256.to_be_bytes());
+    result.extend_from_slice(&AEAD_ID_CHACHA20_POLY1305.to_be_bytes());
+    return result;
+}
+
+fn extract_and_expand(kem_id: u16, kem_secret: &[u8], kem_context: &[u8]) -> [u8; 32]
+{
+    let suite_id = kem_suite_id(kem_id);
+    let eae_prk = labeled_extract(&suite_id, b"", b"eae_prk", kem_secret);
+    let mut result = [0; 32];
+    result.copy_from_slice(&labeled_expand(&suite_id, &eae_prk, b"shared_secret", kem_context, 32));
+    return result;
+}
+
+/// The state shared by sender and recipient after the key schedule
+struct Context
+{
+    suite_id: Vec<u8>,
+    key: AeadKey,
+    base_nonce: AeadNonce,
+    exporter_secret: [u8; hkdf::HASH_BYTES],
+    sequence_number: u64
+}
+
+impl Context
+{
+    fn key_schedule(mode: u8, kem_id: u16, shared_secret: &[u8; 32], info: &[u8]) -> Context
+    {
+        let suite_id = hpke_suite_id(kem_id);
+        let psk_id_hash = labeled_extract(&suite_id, b"", b"psk_id_hash", b"");
+        let info_hash = labeled_extract(&suite_id, b"", b"info_hash", info);
+        let mut key_schedule_context = vec![mode];
+        key_schedule_context.extend_from_slice(&psk_id_hash);
+        key_schedule_context.extend_from_slice(&info_hash);
+
+        let secret = labeled_extract(&suite_id, shared_secret, b"secret", b"");
+        let mut key: AeadKey = [0; 32];
+        let mut base_nonce: AeadNonce = [0; 12];
+        let mut exporter_secret = [0; hkdf::HASH_BYTES];
+        key.copy_from_slice(&labeled_expand(&suite_id, &secret, b"key", &key_schedule_context, 32));
+        base_nonce.copy_from_slice(&labeled_expand(&suite_id, &secret, b"base_nonce", &key_schedule_context, 12));
+        exporter_secret.copy_from_slice(&labeled_expand(&suite_id, &secret, b"exp", &key_schedule_context, hkdf::HASH_BYTES));
+        return Context {
+            suite_id: suite_id,
+            key: key,
+            base_nonce: base_nonce,
+            exporter_secret: exporter_secret,
+            sequence_number: 0
+        };
+    }
+
+    /// The base nonce, xored with the big-endian encoding of the sequence number
+    fn compute_nonce(&self) -> AeadNonce
+    {
+        let mut result = self.base_nonce;
+        let sequence_bytes = self.sequence_number.to_be_bytes();
+        for i in 0..8 {
+            result[4 + i] ^= sequence_bytes[i];
+        }
+        return result;
+    }
+
+    fn increment_sequence_number(&mut self) -> Result<(), HpkeError>
+    {
+        if self.sequence_number == u64::MAX {
+            return Err(HpkeError::MessageLimitReached);
+        }
+        self.sequence_number += 1;
+        return Ok(());
+    }
+
+    fn export(&self, exporter_context: &[u8], length: usize) -> Result<Vec<u8>, HpkeError>
+    {
+        if length > MAX_EXPORT_LENGTH {
+            return Err(HpkeError::InvalidLength);
+        }
+        return Ok(labeled_expand(&self.suite_id, &self.exporter_secret, b"sec", exporter_context, length));
+    }
+}
+
+pub struct SenderContext
+{
+    context: Context
+}
+
+impl SenderContext
+{
+    pub fn seal(&mut self, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, HpkeError>
+    {
+        let ciphertext = aead::seal(&self.context.key, &self.context.compute_nonce(), aad, plaintext);
+        self.context.increment_sequence_number()?;
+        return Ok(ciphertext);
+    }
+
+    /// Derives a secret of the given length, which must be at most MAX_EXPORT_LENGTH
+    pub fn export(&self, exporter_context: &[u8], length: usize) -> Result<Vec<u8>, HpkeError>
+    {
+        self.context.export(exporter_context, length)
+    }
+}
+
+pub struct RecipientContext
+{
+    context: Context
+}
+
+impl RecipientContext
+{
+    /// Decrypts the next message; the sequence number only advances if it could be authenticated
+    pub fn open(&mut self, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, HpkeError>
+    {
+        let plaintext = aead::open(&self.context.key, &self.context.compute_nonce(), aad, ciphertext).ok_or(HpkeError::OpenError)?;
+        self.context.increment_sequence_number()?;
+        return Ok(plaintext);
+    }
+
+    /// Derives a secret of the given length, which must be at most MAX_EXPORT_LENGTH
+    pub fn export(&self, exporter_context: &[u8], length: usize) -> Result<Vec<u8>, HpkeError>
+    {
+        self.context.export(exporter_context, length)
+    }
+}
+
+/// Returns the encapsulated key enc, which has to be sent to the recipient, and the context
+pub fn setup_base_s(pk_r: &PublicKey, info: &[u8], seed: Seed) -> (Vec<u8>, SenderContext)
+{
+    let (kem_secret, ciphertext) = kem::encapsulate(pk_r, seed);
+    let enc = io::ciphertext_to_bytes(&ciphertext);
+    let mut kem_context = enc.clone();
+    kem_context.extend_from_slice(&io::pk_to_bytes(pk_r));
+    let shared_secret = extract_and_expand(KEM_ID_KYBER, &kem_secret, &kem_context);
+    let context = Context::key_schedule(MODE_BASE, KEM_ID_KYBER, &shared_secret, info);
+    return (enc, SenderContext { context: context });
+}
+
+pub fn setup_base_r(enc: &[u8], sk_r: &KemSecretKey, info: &[u8]) -> Result<RecipientContext, HpkeError>
+{
+    let kem_ciphertext = io::validate_ciphertext(enc).map_err(|_| HpkeError::DeserializeError)?;
+    let kem_secret = kem::decapsulate(sk_r, &kem_ciphertext);
+    let mut kem_context = enc.to_vec();
+    kem_context.extend_from_slice(&io::pk_to_bytes(&sk_r.1));
+    let shared_secret = extract_and_expand(KEM_ID_KYBER, &kem_secret, &kem_context);
+    let context = Context::key_schedule(MODE_BASE, KEM_ID_KYBER, &shared_secret, info);
+    return Ok(RecipientContext { context: context });
+}
+
+fn hybrid_public_key(sk: &HybridSecretKey) -> HybridPublicKey
+{
+    ((sk.0).1.clone(), x25519_base(&sk.1))
+}
+
+fn auth_kem_context(enc: &[u8], pk_r: &HybridPublicKey, pk_s: &HybridPublicKey) -> Vec<u8>
+{
+    let mut result = enc.to_vec();
+    result.extend_from_slice(&hybrid::pk_to_bytes(pk_r));
+    result.extend_from_slice(&hybrid::pk_to_bytes(pk_s));
+    return result;
+}
+
+pub fn setup_auth_s(pk_r: &HybridPublicKey, info: &[u8], sk_s: &HybridSecretKey, seed: Seed) -> (Vec<u8>, SenderContext)
+{
+    let mut random = expand_randomness_shake_256(seed);
+    let mut kem_seed: Seed = [0; 32];
+    let mut ephemeral_secret: X25519Key = [0; 32];
+    random.read(&mut kem_seed);
+    random.read(&mut ephemeral_secret);
+
+    let (kyber_secret, kem_ciphertext) = kem::encapsulate(&pk_r.0, kem_seed);
+    let enc = hybrid::ciphertext_to_bytes(&(kem_ciphertext, x25519_base(&ephemeral_secret)));
+    let mut kem_secret = kyber_secret.to_vec();
+    kem_secret.extend_from_slice(&x25519(&ephemeral_secret, &pk_r.1));
+    kem_secret.extend_from_slice(&x25519(&sk_s.1, &pk_r.1));
+    let kem_context = auth_kem_context(&enc, pk_r, &hybrid_public_key(sk_s));
+
+    let shared_secret = extract_and_expand(KEM_ID_KYBER_X25519, &kem_secret, &kem_context);
+    let context = Context::key_schedule(MODE_AUTH, KEM_ID_KYBER_X25519, &shared_secret, info);
+    return (enc, SenderContext { context: context });
+}
+
+pub fn setup_auth_r(enc: &[u8], sk_r: &HybridSecretKey, info: &[u8], pk_s: &HybridPublicKey) -> Result<RecipientContext, HpkeError>
+{
+    let (kem_ciphertext, ephemeral_pk) = hybrid::validate_ciphertext(enc).map_err(|_| HpkeError::DeserializeError)?;
+    let kyber_secret = kem::decapsulate(&sk_r.0, &kem_ciphertext);
+    let mut kem_secret = kyber_secret.to_vec();
+    kem_secret.extend_from_slice(&x25519(&sk_r.1, &ephemeral_pk));
+    kem_secret.extend_from_slice(&x25519(&sk_r.1, &pk_s.1));
+    let kem_context = auth_kem_context(enc, &hybrid_public_key(sk_r), pk_s);
+
+    let shared_secret = extract_and_expand(KEM_ID_KYBER_X25519, &kem_secret, &kem_context);
+    let context = Context::key_schedule(MODE_AUTH, KEM_ID_KYBER_X25519, &shared_secret, info);
+    return Ok(RecipientContext { context: context });
+}
+
+#[cfg(test)]
+use super::kyber::test_seed;
+
+#[test]
+fn test_base_mode_sequence() {
+    let (sk, pk) = kem::key_gen(test_seed(0), test_seed(1));
+    let (enc, mut sender) = setup_base_s(&pk, b"test info", test_seed(2));
+    let mut recipient = setup_base_r(&enc, &sk, b"test info").unwrap();
+    for i in 0..5_u8 {
+        let aad = [i];
+        let ciphertext = sender.seal(&aad, b"a message").unwrap();
+        assert_eq!(b"a message".to_vec(), recipient.open(&aad, &ciphertext).unwrap());
+    }
+    assert_eq!(5, sender.context.sequence_number);
+    assert_eq!(5, recipient.context.sequence_number);
+}
+
+#[test]
+fn test_out_of_order_open_fails() {
+    let (sk, pk) = kem::key_gen(test_seed(0), test_seed(1));
+    let (enc, mut sender) = setup_base_s(&pk, b"", test_seed(2));
+    let mut recipient = setup_base_r(&enc, &sk, b"").unwrap();
+    let first = sender.seal(b"", b"first").unwrap();
+    let second = sender.seal(b"", b"second").unwrap();
+    assert_eq!(Err(HpkeError::OpenError), recipient.open(b"", &second));
+    // a failed open does not advance the sequence number
+    assert_eq!(b"first".to_vec(), recipient.open(b"", &first).unwrap());
+    assert_eq!(b"second".to_vec(), recipient.open(b"", &second).unwrap());
+}
+
+#[test]
+fn test_wrong_info_fails() {
+    let (sk, pk) = kem::key_gen(test_seed(0), test_seed(1));
+    let (enc, mut sender) = setup_base_s(&pk, b"info", test_seed(2));
+    let mut recipient = setup_base_r(&enc, &sk, b"other info").unwrap();
+    let ciphertext = sender.seal(b"", b"message").unwrap();
+    assert_eq!(Err(HpkeError::OpenError), recipient.open(b"", &ciphertext));
+}
+
+#[test]
+fn test_invalid_encapsulation() {
+    let (sk, pk) = kem::key_gen(test_seed(0), test_seed(1));
+    let (mut enc, _) = setup_base_s(&pk, b"", test_seed(2));
+    enc.push(0);
+    assert_eq!(Err(HpkeError::DeserializeError), setup_base_r(&enc, &sk, b"").map(|_| ()));
+    let (sk_r, _) = hybrid::key_gen(test_seed(0), test_seed(3), test_seed(4));
+    let (_, pk_s) = hybrid::key_gen(test_seed(0), test_seed(1), test_seed(2));
+    assert_eq!(Err(HpkeError::DeserializeError), setup_auth_r(&enc[1..], &sk_r, b"", &pk_s).map(|_| ()));
+}
+
+#[test]
+fn test_nonce_computation() {
+    let (_sk, pk) = kem::key_gen(test_seed(0), test_seed(1));
+    let (_enc, mut sender) = setup_base_s(&pk, b"", test_seed(2));
+    let base_nonce = sender.context.base_nonce;
+    assert_eq!(base_nonce, sender.context.compute_nonce());
+    sender.context.sequence_number = 0x0102;
+    let nonce = sender.context.compute_nonce();
+    assert_eq!(base_nonce[0..10], nonce[0..10]);
+    assert_eq!(base_nonce[10] ^ 0x01, nonce[10]);
+    assert_eq!(base_nonce[11] ^ 0x02, nonce[11]);
+}
+
+#[test]
+fn test_message_limit() {
+    let (sk, pk) = kem::key_gen(test_seed(0), test_seed(1));
+    let (enc, mut sender) = setup_base_s(&pk, b"", test_seed(2));
+    let mut recipient = setup_base_r(&enc, &sk, b"").unwrap();
+    sender.context.sequence_number = u64::MAX - 1;
+    recipient.context.sequence_number = u64::MAX - 1;
+    let ciphertext = sender.seal(b"", b"last message").unwrap();
+    assert_eq!(b"last message".to_vec(), recipient.open(b"", &ciphertext).unwrap());
+    // the nonce with the sequence number u64::MAX would be valid, but the sequence
+    // number cannot be incremented after using it
+    let last_nonce_ciphertext = aead::seal(&sender.context.key, &sender.context.compute_nonce(), b"", b"too much");
+    assert_eq!(Err(HpkeError::MessageLimitReached), sender.seal(b"", b"too much"));
+    assert_eq!(Err(HpkeError::MessageLimitReached), recipient.open(b"", &last_nonce_ciphertext));
+    assert_eq!(Err(HpkeError::OpenError), recipient.open(b"", &ciphertext));
+}
+
+#[test]
+fn test_export() {
+    let (sk, pk) = kem::key_gen(test_seed(0), test_seed(1));
+    let (enc, sender) = setup_base_s(&pk, b"", test_seed(2));
+    let recipient = setup_base_r(&enc, &sk, b"").unwrap();
+    assert_eq!(sender.export(b"context", 45), recipient.export(b"context", 45));
+    assert_ne!(sender.export(b"context", 32), recipient.export(b"other context", 32));
+    assert_eq!(MAX_EXPORT_LENGTH, sender.export(b"context", MAX_EXPORT_LENGTH).unwrap().len());
+    assert_eq!(sender.export(b"context", MAX_EXPORT_LENGTH), recipient.export(b"context", MAX_EXPORT_LENGTH));
+    assert_eq!(Err(HpkeError::InvalidLength), sender.export(b"context", MAX_EXPORT_LENGTH + 1));
+    assert_eq!(Err(HpkeError::InvalidLength), recipient.export(b"context", 1 << 16));
+}
+
+#[test]
+fn test_auth_mode() {
+    let (sk_s, pk_s) = hybrid::key_gen(test_seed(0), test_seed(1), test_seed(2));
+    let (sk_r, pk_r) = hybrid::key_gen(test_seed(0), test_seed(3), test_seed(4));
+    let (enc, mut sender) = setup_auth_s(&pk_r, b"info", &sk_s, test_seed(5));
+    let mut recipient = setup_auth_r(&enc, &sk_r, b"info", &pk_s).unwrap();
+    let ciphertext = sender.seal(b"aad", b"authenticated message").unwrap();
+    assert_eq!(b"authenticated message".to_vec(), recipient.open(b"aad", &ciphertext).unwrap());
+
+    // a recipient expecting another sender cannot open the message
+    let (_other_sk, other_pk) = hybrid::key_gen(test_seed(0), test_seed(6), test_seed(16));
+    let mut recipient = setup_auth_r(&enc, &sk_r, b"info", &other_pk).unwrap();
+    assert_eq!(Err(HpkeError::OpenError), recipient.open(b"aad", &ciphertext));
+}
@@ -0,0 +1,186 @@
+//! Hybrid key encapsulation, running Kyber.KEM and X25519 side by side. Both
+//! shared secrets are fed, together with the ciphertext and the public key, into
+//! SHA3-256, so the combined key stays secret as long as one of the components
+//! is not broken.
+
+use super::kyber::{ PublicKey, Ciphertext, Seed, PUBLIC_KEY_BYTES, CIPHERTEXT_BYTES, expand_randomness_shake_256 };
+use super::kem;
+use super::kem::{ KemSecretKey, SharedSecret };
+use super::x25519::*;
+use super::io;
+
+use sha3::digest::{ FixedOutput, Input, XofReader };
+
+pub type HybridPublicKey = (PublicKey, X25519Key);
+pub type HybridSecretKey = (KemSecretKey, X25519Key);
+pub type HybridCiphertext = (Ciphertext, X25519Key);
+
+const COMBINER_LABEL: &[u8] = b"Kyber-X25519 hybrid";
+
+fn combine(kyber_secret: &SharedSecret, dh_secret: &X25519Key, ciphertext: &[u8], public_key: &[u8]) -> SharedSecret
+{
+    let mut hasher = sha3::Sha3_256::default();
+    hasher.input(COMBINER_LABEL);
+    hasher.input(kyber_secret);
+    hasher.input(dh_secret);
+    hasher.input(ciphertext);
+    hasher.input(public_key);
+    let mut result = [0; 32];
+    result.copy_from_slice(&hasher.fixed_result());
+    return result;
+}
+
+pub fn key_gen(matrix_seed: Seed, secret_seed: Seed, dh_secret: X25519Key) -> (HybridSecretKey, HybridPublicKey)
+{
+    let (kem_sk, kem_pk) = kem::key_gen(matrix_seed, secret_seed);
+    let dh_pk = x25519_base(&dh_secret);
+    return ((kem_sk, dh_secret), (kem_pk, dh_pk));
+}
+
+pub fn encapsulate(pk: &HybridPublicKey, seed: Seed) -> (SharedSecret, HybridCiphertext)
+{
+    let mut random = expand_randomness_shake_256(seed);
+    let mut kem_seed: Seed = [0; 32];
+    let mut ephemeral_secret: X25519Key = [0; 32];
+    random.read(&mut kem_seed);
+    random.read(&mut ephemeral_secret);
+
+    let (kyber_secret, kem_ciphertext) = kem::encapsulate(&pk.0, kem_seed);
+    let ciphertext = (kem_ciphertext, x25519_base(&ephemeral_secret));
+    let dh_secret = x25519(&ephemeral_secret, &pk.1);
+    let key = combine(&kyber_secret, &dh_secret, &ciphertext_to_bytes(&ciphertext), &pk_to_bytes(pk));
+    return (key, ciphertext);
+}
+
+pub fn decapsulate(sk: &HybridSecretKey, ciphertext: &HybridCiphertext) -> SharedSecret
+{
+    let kyber_secret = kem::decapsulate(&sk.0, &ciphertext.0);
+    let dh_secret = x25519(&sk.1, &ciphertext.1);
+    let pk: HybridPublicKey = ((sk.0).1.clone(), x25519_base(&sk.1));
+    return combine(&kyber_secret, &dh_secret, &ciphertext_to_bytes(ciphertext), &pk_to_bytes(&pk));
+}
+
+/// The Kyber public key, followed by the 32 bytes of the X25519 public key
+pub fn pk_to_bytes(pk: &HybridPublicKey) -> Vec<u8>
+{
+    let mut result = io::pk_to_bytes(&pk.0);
+    result.extend_from_slice(&pk.1);
+    return result;
+}
+
+/// Decodes the public key from exactly PUBLIC_KEY_BYTES + 32 bytes, with the checks of
+/// io::validate_public_key(), so that no two accepted encodings yield the same key
+pub fn pk_from_bytes(data: &[u8]) -> Result<HybridPublicKey, io::ValidationError>
+{
+    if data.len() != PUBLIC_KEY_BYTES + 32 {
+        return Err(io::ValidationError::InvalidLength { expected: PUBLIC_KEY_BYTES + 32, actual: data.len() });
+    }
+    let (kyber_part, dh_part) = data.split_at(PUBLIC_KEY_BYTES);
+    let mut dh_pk: X25519Key = [0; 32];
+    dh_pk.copy_from_slice(dh_part);
+    return Ok((io::validate_public_key(kyber_part)?, dh_pk));
+}
+
+/// The Kyber ciphertext, followed by the 32 bytes of the ephemeral X25519 public key
+pub fn ciphertext_to_bytes(ciphertext: &HybridCiphertext) -> Vec<u8>
+{
+    let mut result = io::ciphertext_to_bytes(&ciphertext.0);
+    result.extend_from_slice(&ciphertext.1);
+    return result;
+}
+
+/// Decodes the ciphertext, after checking its length and the encoding of the Kyber part.
+/// As decapsulate() hashes the encoding of the decoded ciphertext, accepting any other
+/// encoding would allow to modify a ciphertext without changing its key.
+pub fn validate_ciphertext(data: &[u8]) -> Result<HybridCiphertext, io::ValidationError>
+{
+    if data.len() != CIPHERTEXT_BYTES + 32 {
+        return Err(io::ValidationError::InvalidLength { expected: CIPHERTEXT_BYTES + 32, actual: data.len() });
+    }
+    let (kyber_part, dh_part) = data.split_at(CIPHERTEXT_BYTES);
+    let mut dh_ciphertext: X25519Key = [0; 32];
+    dh_ciphertext.copy_from_slice(dh_part);
+    return Ok((io::validate_ciphertext(kyber_part)?, dh_ciphertext));
+}
+
+#[cfg(test)]
+use super::kyber::test_seed;
+
+#[test]
+fn test_encapsulate_decapsulate() {
+    let (sk, pk) = key_gen(test_seed(0), test_seed(1), test_seed(2));
+    let (key, ciphertext) = encapsulate(&pk, test_seed(3));
+    assert_eq!(key, decapsulate(&sk, &ciphertext));
+}
+
+#[test]
+fn test_serialization() {
+    let (sk, pk) = key_gen(test_seed(0), test_seed(1), test_seed(2));
+    let (key, ciphertext) = encapsulate(&pk_from_bytes(&pk_to_bytes(&pk)).unwrap(), test_seed(3));
+    let ciphertext_bytes = ciphertext_to_bytes(&ciphertext);
+    assert_eq!(super::kyber::CIPHERTEXT_BYTES + 32, ciphertext_bytes.len());
+    assert_eq!(super::kyber::PUBLIC_KEY_BYTES + 32, pk_to_bytes(&pk).len());
+    assert_eq!(key, decapsulate(&sk, &validate_ciphertext(&ciphertext_bytes).unwrap()));
+}
+
+#[test]
+fn test_combiner_depends_on_each_component() {
+    // if only one component secret changes, the key changes, so it is enough that one of
+    // them stays secret
+    let (_, pk) = key_gen(test_seed(0), test_seed(1), test_seed(2));
+    let (_, ciphertext) = encapsulate(&pk, test_seed(3));
+    let ciphertext_bytes = ciphertext_to_bytes(&ciphertext);
+    let pk_bytes = pk_to_bytes(&pk);
+    let key = combine(&test_seed(4), &test_seed(5), &ciphertext_bytes, &pk_bytes);
+    assert_ne!(key, combine(&test_seed(6), &test_seed(5), &ciphertext_bytes, &pk_bytes));
+    assert_ne!(key, combine(&test_seed(4), &test_seed(6), &ciphertext_bytes, &pk_bytes));
+    assert_ne!(key, combine(&test_seed(5), &test_seed(4), &ciphertext_bytes, &pk_bytes));
+}
+
+#[test]
+fn test_mixed_ciphertexts_change_key() {
+    // replacing only the Kyber or only the X25519 ciphertext changes the key
+    let (sk, pk) = key_gen(test_seed(0), test_seed(1), test_seed(2));
+    let (key, ciphertext) = encapsulate(&pk, test_seed(3));
+    let (other_key, other_ciphertext) = encapsulate(&pk, test_seed(4));
+    let kyber_replaced = decapsulate(&sk, &(other_ciphertext.0.clone(), ciphertext.1));
+    let x25519_replaced = decapsulate(&sk, &(ciphertext.0.clone(), other_ciphertext.1));
+    let mut keys = vec![key, other_key, kyber_replaced, x25519_replaced];
+    keys.sort();
+    keys.dedup();
+    assert_eq!(4, keys.len());
+}
+
+#[test]
+fn test_different_ciphertexts_give_different_keys() {
+    let (sk, pk) = key_gen(test_seed(0), test_seed(1), test_seed(2));
+    let (key, ciphertext) = encapsulate(&pk, test_seed(3));
+    let bytes = ciphertext_to_bytes(&ciphertext);
+    let mut keys = vec![key];
+    for index in &[0, 7, CIPHERTEXT_BYTES - 1, CIPHERTEXT_BYTES, bytes.len() - 4, bytes.len() - 1] {
+        let mut modified = bytes.clone();
+        modified[*index] ^= 0x10;
+        keys.push(decapsulate(&sk, &validate_ciphertext(&modified).unwrap()));
+    }
+    keys.sort();
+    keys.dedup();
+    assert_eq!(7, keys.len());
+    // bytes inserted between or after the parts must not yield another encoding of the
+    // same ciphertext
+    assert!(validate_ciphertext(&[&bytes[..CIPHERTEXT_BYTES], &[0; 5], &bytes[CIPHERTEXT_BYTES..]].concat()).is_err());
+    assert!(validate_ciphertext(&[&bytes[..], &[0]].concat()).is_err());
+}
+
+#[test]
+fn test_from_bytes_exact_length() {
+    let (_, pk) = key_gen(test_seed(0), test_seed(1), test_seed(2));
+    let pk_bytes = pk_to_bytes(&pk);
+    assert!(pk_from_bytes(&pk_bytes).is_ok());
+    assert!(pk_from_bytes(&pk_bytes[1..]).is_err());
+    assert!(pk_from_bytes(&[&pk_bytes[..PUBLIC_KEY_BYTES], &[0; 5], &pk_bytes[PUBLIC_KEY_BYTES..]].concat()).is_err());
+    assert!(pk_from_bytes(&[0; 31]).is_err());
+    assert!(validate_ciphertext(&[0; 31]).is_err());
+    assert!(validate_ciphertext(&[0; CIPHERTEXT_BYTES + 31]).is_err());
+    assert!(validate_ciphertext(&[0; CIPHERTEXT_BYTES + 33]).is_err());
+    assert!(validate_ciphertext(&[0; CIPHERTEXT_BYTES + 32]).is_ok());
+}
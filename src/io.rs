@@ -1,5 +1,6 @@
 use super::encoding::*;
 use super::kyber::*;
+use super::kem::{ KemSecretKey, KemSecretSeed, KEM_SECRET_KEY_BYTES, KEM_SECRET_SEED_BYTES };
 use super::rqvec::*;
 use super::ring::*;
 
@@ -16,10 +17,51 @@ pub fn base64_decode<'a>(data: &'a str) -> impl Decoder + 'a
     ByteStreamDecoder::new(move || base64_decoder.read())
 }
 
-pub fn read_sk(data: &str) -> SecretKey
+pub fn bytes_encode<'a>(result: &'a mut Vec<u8>) -> impl Encoder + 'a
 {
-    let mut decoder = base64_decode(data);
-    return RqVector::decode(&mut decoder);
+    ByteStreamEncoder::new(move |byte| result.push(byte))
+}
+
+pub fn bytes_decode<'a>(data: &'a [u8]) -> impl Decoder + 'a
+{
+    let mut input_iter = data.iter();
+    ByteStreamDecoder::new(move || input_iter.next().copied())
+}
+
+/// Decodes base64 with or without padding. In contrast to base64_decode_canonical(), this
+/// accepts arbitrary values in the unused bits of the last character.
+fn base64_to_bytes(data: &str) -> Result<Vec<u8>, ValidationError>
+{
+    let content = data.trim_end_matches('=');
+    if !content.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/') {
+        return Err(ValidationError::InvalidCharacter);
+    }
+    let mut decoder = base64_decode(content);
+    return Ok(std::iter::from_fn(|| decoder.read()).collect());
+}
+
+/// Checks that data is long enough to decode a value of the given size from its beginning
+fn check_min_length(data: &[u8], expected: usize) -> Result<(), ValidationError>
+{
+    if data.len() < expected {
+        return Err(ValidationError::InvalidLength { expected: expected, actual: data.len() });
+    }
+    return Ok(());
+}
+
+/// Decodes the secret key from the beginning of data. As the secret key of Kyber.KEM starts
+/// with the secret key of the encryption scheme, this also accepts the former.
+pub fn sk_from_bytes(data: &[u8]) -> Result<SecretKey, ValidationError>
+{
+    check_min_length(data, SECRET_KEY_BYTES)?;
+    // the values at the roots of unity are encoded with 13 bits, so not all encodings are
+    // values in Zq
+    return RqVector::try_decode(&mut bytes_decode(data)).ok_or(ValidationError::NonCanonicalEncoding);
+}
+
+pub fn read_sk(data: &str) -> Result<SecretKey, ValidationError>
+{
+    sk_from_bytes(&base64_to_bytes(data)?)
 }
 
 pub fn write_sk(secret_key: &SecretKey) -> String
@@ -32,10 +74,51 @@ pub fn write_sk(secret_key: &SecretKey) -> String
     return result;
 }
 
-pub fn read_pk(public_key: &str) -> PublicKey
+/// The secret key of Kyber.KEM starts with the secret key of the encryption scheme,
+/// so its encoding can also be read by read_sk()
+pub fn kem_sk_to_bytes(secret_key: &KemSecretKey) -> Vec<u8>
+{
+    let mut result: Vec<u8> = Vec::new();
+    {
+        let mut encoder = bytes_encode(&mut result);
+        secret_key.0.encode(&mut encoder);
+        (secret_key.1).0.encode(&mut encoder);
+        encoder.encode_bytes(&(secret_key.1).1);
+        encoder.encode_bytes(&secret_key.2);
+        encoder.encode_bytes(&secret_key.3);
+    }
+    return result;
+}
+
+pub fn kem_sk_from_bytes(data: &[u8]) -> Result<KemSecretKey, ValidationError>
+{
+    check_min_length(data, KEM_SECRET_KEY_BYTES)?;
+    let sk = sk_from_bytes(data)?;
+    // the length is checked and every encoding of the other parts is valid
+    let mut decoder = bytes_decode(&data[SECRET_KEY_BYTES..]);
+    let pk = (CompressedRqVector::decode(&mut decoder), decoder.read_bytes().expect("Input too short"));
+    let pk_hash = decoder.read_bytes().expect("Input too short");
+    let z = decoder.read_bytes().expect("Input too short");
+    return Ok((sk, pk, pk_hash, z));
+}
+
+pub fn secret_seed_to_bytes(seed: &KemSecretSeed) -> Vec<u8>
+{
+    let mut result = seed.0.to_vec();
+    result.extend_from_slice(&seed.1);
+    return result;
+}
+
+pub fn secret_seed_from_bytes(data: &[u8]) -> Result<KemSecretSeed, ValidationError>
 {
-    let mut decoder = base64_decode(public_key);
-    return (CompressedRqVector::decode(&mut decoder), decoder.read_bytes().expect("Input too short"));
+    check_min_length(data, KEM_SECRET_SEED_BYTES)?;
+    let mut decoder = bytes_decode(data);
+    return Ok((decoder.read_bytes().expect("Input too short"), decoder.read_bytes().expect("Input too short")));
+}
+
+pub fn read_pk(public_key: &str) -> Result<PublicKey, ValidationError>
+{
+    pk_from_bytes(&base64_to_bytes(public_key)?)
 }
 
 pub fn write_pk(public_key: &PublicKey) -> String
@@ -49,10 +132,29 @@ pub fn write_pk(public_key: &PublicKey) -> String
     return result;
 }
 
-pub fn read_ciphertext(ciphertext: &str) -> Ciphertext
+pub fn pk_to_bytes<const DT: u16>(public_key: &GenericPublicKey<DT>) -> Vec<u8>
 {
-    let mut decoder = base64_decode(ciphertext);
-    return (CompressedRqVector::decode(&mut decoder), CompressedRq::decode(&mut decoder));
+    let mut result: Vec<u8> = Vec::new();
+    {
+        let mut encoder = bytes_encode(&mut result);
+        public_key.0.encode(&mut encoder);
+        encoder.encode_bytes(&public_key.1);
+    }
+    return result;
+}
+
+/// Decodes the public key from the beginning of data. Every encoding of the right length is
+/// valid, see also validate_public_key().
+pub fn pk_from_bytes<const DT: u16>(public_key: &[u8]) -> Result<GenericPublicKey<DT>, ValidationError>
+{
+    check_min_length(public_key, public_key_bytes(DT))?;
+    let mut decoder = bytes_decode(public_key);
+    return Ok((CompressedRqVector::decode(&mut decoder), decoder.read_bytes().expect("Input too short")));
+}
+
+pub fn read_ciphertext(ciphertext: &str) -> Result<Ciphertext, ValidationError>
+{
+    ciphertext_from_bytes(&base64_to_bytes(ciphertext)?)
 }
 
 pub fn write_ciphertext(ciphertext: &Ciphertext) -> String
@@ -66,10 +168,115 @@ pub fn write_ciphertext(ciphertext: &Ciphertext) -> String
     return result;
 }
 
-pub fn read_message(plaintext: &str) -> Plaintext
+pub fn ciphertext_to_bytes<const DU: u16, const DV: u16>(ciphertext: &GenericCiphertext<DU, DV>) -> Vec<u8>
 {
-    let mut decoder = base64_decode(plaintext);
-    return decoder.read_bytes().expect("Input too short");
+    let mut result: Vec<u8> = Vec::new();
+    {
+        let mut encoder = bytes_encode(&mut result);
+        ciphertext.0.encode(&mut encoder);
+        ciphertext.1.encode(&mut encoder);
+    }
+    return result;
+}
+
+/// Decodes the ciphertext from the beginning of data, see also validate_ciphertext()
+pub fn ciphertext_from_bytes<const DU: u16, const DV: u16>(ciphertext: &[u8]) -> Result<GenericCiphertext<DU, DV>, ValidationError>
+{
+    check_min_length(ciphertext, ciphertext_bytes(DU, DV))?;
+    let mut decoder = bytes_decode(ciphertext);
+    return Ok((CompressedRqVector::decode(&mut decoder), CompressedRq::decode(&mut decoder)));
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ValidationError
+{
+    InvalidLength { expected: usize, actual: usize },
+    InvalidCharacter,
+    NonCanonicalEncoding
+}
+
+impl std::fmt::Display for ValidationError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        match self {
+            ValidationError::InvalidLength { expected, actual } => write!(f, "Expected {} bytes, got {}", expected, actual),
+            ValidationError::InvalidCharacter => write!(f, "Input contains characters that are not allowed in base64"),
+            ValidationError::NonCanonicalEncoding => write!(f, "Input is not canonically encoded")
+        }
+    }
+}
+
+/// Decodes padded base64, but only accepts the unique encoding that Base64Encoder
+/// produces, i.e. without whitespace, with correct padding and with zeros in the
+/// unused bits of the last character
+pub fn base64_decode_canonical(data: &str) -> Result<Vec<u8>, ValidationError>
+{
+    let content = data.trim_end_matches('=');
+    if !content.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/') {
+        return Err(ValidationError::InvalidCharacter);
+    }
+    let byte_count = content.len() * 6 / 8;
+    let mut decoder = base64_decode(content);
+    let result: Vec<u8> = (0..byte_count).map(|_| decoder.read().unwrap()).collect();
+    let mut reencoded = String::new();
+    base64_encode(&mut reencoded).encode_bytes(&result);
+    if reencoded != data {
+        return Err(ValidationError::NonCanonicalEncoding);
+    }
+    return Ok(result);
+}
+
+fn check_length(data: &[u8], expected: usize) -> Result<(), ValidationError>
+{
+    if data.len() != expected {
+        return Err(ValidationError::InvalidLength { expected: expected, actual: data.len() });
+    }
+    return Ok(());
+}
+
+/// Decodes a public key from exactly PUBLIC_KEY_BYTES bytes. Every compressed coefficient
+/// and every matrix seed is valid, but we nevertheless check that encoding the result
+/// again gives the input, so that no two accepted encodings yield the same key.
+pub fn validate_public_key(data: &[u8]) -> Result<PublicKey, ValidationError>
+{
+    check_length(data, PUBLIC_KEY_BYTES)?;
+    let result = pk_from_bytes(data)?;
+    if pk_to_bytes(&result) != data {
+        return Err(ValidationError::NonCanonicalEncoding);
+    }
+    return Ok(result);
+}
+
+pub fn validate_public_key_base64(data: &str) -> Result<PublicKey, ValidationError>
+{
+    validate_public_key(&base64_decode_canonical(data)?)
+}
+
+/// Decodes a ciphertext from exactly CIPHERTEXT_BYTES bytes, with the same checks as
+/// validate_public_key()
+pub fn validate_ciphertext(data: &[u8]) -> Result<Ciphertext, ValidationError>
+{
+    check_length(data, CIPHERTEXT_BYTES)?;
+    let result = ciphertext_from_bytes(data)?;
+    if ciphertext_to_bytes(&result) != data {
+        return Err(ValidationError::NonCanonicalEncoding);
+    }
+    return Ok(result);
+}
+
+pub fn validate_ciphertext_base64(data: &str) -> Result<Ciphertext, ValidationError>
+{
+    validate_ciphertext(&base64_decode_canonical(data)?)
+}
+
+/// Decodes a message of exactly 32 base64 encoded bytes, longer data has to be encrypted
+/// with the encrypt-file command instead
+pub fn read_message(plaintext: &str) -> Result<Plaintext, ValidationError>
+{
+    let data = base64_to_bytes(plaintext)?;
+    check_length(&data, 32)?;
+    return Ok(bytes_decode(&data).read_bytes().expect("Input too short"));
 }
 
 pub fn write_message(plaintext: &Plaintext) -> String
@@ -98,7 +305,7 @@ fn test_read_write_sk() {
         hvKObHD0UCUmEKUKBFrERfDKh0rWXQZpjdrDBZalxeZ37YUau9HLtvt4E3LMM6KxUH6wT0XhNvskKGqZNW+lThgFBpu15jw7ioSQlEp2xjIFuuKs8T7Q7vjeHibc8VwXgYRcBFYsMA\
         nXK8d11+0hm29upL504GczN+p5gn04Ily0lOcaYv8PS6U5GUbtCho+0ToEZ1+GDEBlymfS040QQKoKxJi/NecIt8b40rPWCNp8/EiPrHrYJ/NtBDbONwjOCRzJRJeOUFvs2s4AOkGN\
         QBodmOqd";
-    let sk = read_sk(sk_str);
+    let sk = read_sk(sk_str).unwrap();
     assert_eq!(sk_str, write_sk(&sk));
 }
 
@@ -116,7 +323,7 @@ fn test_read_write_pk() {
         Fsy/p3HRFcgGTrSBa3p5N1qHOMOV7DC3VvX+YajKf5obMXcPDpJdhhEkY3OZe34DAn4ZCquvN2SNbkWYo6n07YA76WssJSFrDcbuqDRuU1NRxjgJUGet19+ItOOVGcfp/tWWg1M\
         3SKfp3fR3nXmJzfBVOPnQq0wazMcaoT0O5oLY/9rWl6JF+URu9HwCkC0M99qhRNFK7qGf/XV6C3+lZcvO9LdPtjAKfKh0TBRZSx6ADu3FR9hQ+VBqn5JPThyZYEMql7SJdMdROX\
         PLLVO29vwmC+eNis7VSHjzDa14TL8x1KnqL5hHAPB5Md2v3enquzF/Go/6pXvMFaWDnLo8Io54OInP3q+a+XEHSLZxWotXpefN8Vo=";
-    let pk = read_pk(pk_str);
+    let pk = read_pk(pk_str).unwrap();
     assert_eq!(pk_str, write_pk(&pk));
 }
 
@@ -135,13 +342,73 @@ fn test_read_write_ciphertext() {
         c0QzqUr5oTv2TOd4CYGEzUS3VCGHVKRuzHzvCZtstid8sfw+g333g4ZXH1LczacZZJQbouGlhcXCW3B/Xnokjc2PmR6675Gfo+EBgC5bzwrDKTnP2nGh0r91pXqJnivOlOva\
         pSejPrqgarishjJHi0Sky802uIC2aNz5wb5DphUHrTibMrfEoiepEsdMGNQBy6yZI5lnNNEcxbBtm5hB190oVBhJtcAT7ILdtHpu1gt02XFd1wO9HWA9O/46h7rFXGJ2Q+HM\
         ZjB++kxiGOjdj5bKDZARXM3ImEXSIGGevkebkhOhI6o+envMgWfymSKgaUQIhb9PAZbidzN9H5nKFh7in+3i";
-    let ciphertext = read_ciphertext(ciphertext_str);
+    let ciphertext = read_ciphertext(ciphertext_str).unwrap();
     assert_eq!(ciphertext_str, write_ciphertext(&ciphertext));
 }
 
 #[test]
 fn test_read_message_ciphertext() {
     let message_str = "AAAABBBBAAAABBBBAAAABBBBAAAABBBBAAAABBBBCCA=";
-    let message = read_message(message_str);
+    let message = read_message(message_str).unwrap();
     assert_eq!(message_str, write_message(&message));
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_validate_public_key() {
+    let (_, pk) = super::kyber::key_gen([1; 32], [2; 32]);
+    let pk_str = write_pk(&pk);
+    assert_eq!(pk_to_bytes(&pk), pk_to_bytes(&validate_public_key_base64(&pk_str).unwrap()));
+    assert_eq!(PUBLIC_KEY_BYTES, pk_to_bytes(&pk).len());
+
+    assert_eq!(Err(ValidationError::InvalidCharacter), validate_public_key_base64(&format!("{} ", pk_str)).map(|_| ()));
+    assert_eq!(Err(ValidationError::InvalidCharacter), validate_public_key_base64(&format!("{}=A", pk_str)).map(|_| ()));
+    assert_eq!(Err(ValidationError::NonCanonicalEncoding), validate_public_key_base64(pk_str.trim_end_matches('=')).map(|_| ()));
+    // 1088 bytes leave 2 unused bits in the last character, set the lowest one
+    let alphabet: Vec<char> = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/".chars().collect();
+    let mut non_canonical = pk_str.clone();
+    assert_eq!(Some('='), non_canonical.pop());
+    let last = non_canonical.pop().unwrap();
+    non_canonical.push(alphabet[alphabet.iter().position(|c| *c == last).unwrap() | 1]);
+    non_canonical.push('=');
+    assert_eq!(Err(ValidationError::NonCanonicalEncoding), validate_public_key_base64(&non_canonical).map(|_| ()));
+
+    let mut bytes = pk_to_bytes(&pk);
+    bytes.push(0);
+    assert_eq!(Err(ValidationError::InvalidLength { expected: PUBLIC_KEY_BYTES, actual: PUBLIC_KEY_BYTES + 1 }), validate_public_key(&bytes).map(|_| ()));
+    assert!(validate_public_key(&bytes[..PUBLIC_KEY_BYTES - 1]).is_err());
+}
+
+#[test]
+fn test_validate_ciphertext() {
+    let (_, pk) = super::kyber::key_gen([1; 32], [2; 32]);
+    let ciphertext = encrypt(&pk, [3; 32], [4; 32]);
+    let bytes = ciphertext_to_bytes(&ciphertext);
+    assert_eq!(bytes, ciphertext_to_bytes(&validate_ciphertext(&bytes).unwrap()));
+    assert_eq!(bytes, ciphertext_to_bytes(&validate_ciphertext_base64(&write_ciphertext(&ciphertext)).unwrap()));
+    assert!(validate_ciphertext(&bytes[1..]).is_err());
+    assert!(validate_ciphertext_base64(&write_ciphertext(&ciphertext)[4..]).is_err());
+}
+
+#[test]
+fn test_read_invalid() {
+    let (sk, pk) = super::kem::key_gen([1; 32], [2; 32]);
+    let sk_bytes = kem_sk_to_bytes(&sk);
+    let sk_str = write_sk(&sk.0);
+    assert_eq!(Err(ValidationError::InvalidCharacter), read_sk(&format!("{}!", sk_str)).map(|_| ()));
+    assert_eq!(Err(ValidationError::InvalidLength { expected: SECRET_KEY_BYTES, actual: 3 }), read_sk("AAAA").map(|_| ()));
+    assert_eq!(Err(ValidationError::InvalidLength { expected: 32, actual: 0 }), read_message("").map(|_| ()));
+    assert_eq!(Err(ValidationError::InvalidLength { expected: 32, actual: 33 }), read_message(&"A".repeat(44)).map(|_| ()));
+    assert_eq!(Err(ValidationError::InvalidLength { expected: CIPHERTEXT_BYTES, actual: 0 }), read_ciphertext("=").map(|_| ()));
+    assert!(read_pk(&write_pk(&pk)[1..]).is_err());
+    assert!(pk_from_bytes::<11>(&pk_to_bytes(&pk)[..PUBLIC_KEY_BYTES - 1]).is_err());
+    assert!(kem_sk_from_bytes(&sk_bytes[..KEM_SECRET_KEY_BYTES - 1]).is_err());
+    assert!(secret_seed_from_bytes(&[0; KEM_SECRET_SEED_BYTES - 1]).is_err());
+
+    // the first value of the secret key is encoded in the lowest 13 bits, set it to q
+    let mut bytes = sk_bytes.clone();
+    bytes[0] = (super::zq::Q & 0xFF) as u8;
+    bytes[1] = (bytes[1] & 0xE0) | (super::zq::Q >> 8) as u8;
+    assert_eq!(Err(ValidationError::NonCanonicalEncoding), kem_sk_from_bytes(&bytes).map(|_| ()));
+    bytes[0] -= 1;
+    assert!(kem_sk_from_bytes(&bytes).is_ok());
+}
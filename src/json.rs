@@ -0,0 +1,331 @@
+//! A minimal JSON (RFC 8259) writer and reader, as required for JSON Web Keys.
+//! The reader is strict: it rejects duplicate member names, trailing data
+//! and documents that are nested too deeply.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue
+{
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    /// The members in the order in which they appear in the document
+    Object(Vec<(String, JsonValue)>)
+}
+
+const MAX_DEPTH: usize = 64;
+
+impl JsonValue
+{
+    /// Returns the member with the given name, if this is an object that contains it
+    pub fn get(&self, name: &str) -> Option<&JsonValue>
+    {
+        match self {
+            JsonValue::Object(members) => members.iter().find(|member| member.0 == name).map(|member| &member.1),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str>
+    {
+        match self {
+            JsonValue::String(value) => Some(value),
+            _ => None
+        }
+    }
+}
+
+fn write_string(value: &str, result: &mut String)
+{
+    result.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c)
+        }
+    }
+    result.push('"');
+}
+
+fn write_value(value: &JsonValue, result: &mut String)
+{
+    match value {
+        JsonValue::Null => result.push_str("null"),
+        JsonValue::Bool(value) => result.push_str(if *value { "true" } else { "false" }),
+        JsonValue::Number(value) => {
+            assert!(value.is_finite(), "JSON cannot represent {}", value);
+            result.push_str(&format!("{}", value));
+        },
+        JsonValue::String(value) => write_string(value, result),
+        JsonValue::Array(elements) => {
+            result.push('[');
+            for (i, element) in elements.iter().enumerate() {
+                if i != 0 {
+                    result.push(',');
+                }
+                write_value(element, result);
+            }
+            result.push(']');
+        },
+        JsonValue::Object(members) => {
+            result.push('{');
+            for (i, (name, member)) in members.iter().enumerate() {
+                if i != 0 {
+                    result.push(',');
+                }
+                write_string(name, result);
+                result.push(':');
+                write_value(member, result);
+            }
+            result.push('}');
+        }
+    }
+}
+
+/// Writes the value without any insignificant whitespace
+pub fn write(value: &JsonValue) -> String
+{
+    let mut result = String::new();
+    write_value(value, &mut result);
+    return result;
+}
+
+struct Parser<'a>
+{
+    data: &'a [u8],
+    index: usize
+}
+
+impl<'a> Parser<'a>
+{
+    fn peek(&self) -> Option<u8>
+    {
+        self.data.get(self.index).copied()
+    }
+
+    fn next(&mut self) -> Option<u8>
+    {
+        let result = self.peek()?;
+        self.index += 1;
+        return Some(result);
+    }
+
+    fn skip_whitespace(&mut self)
+    {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.index += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &[u8]) -> Option<()>
+    {
+        if self.data[self.index..].starts_with(literal) {
+            self.index += literal.len();
+            return Some(());
+        }
+        return None;
+    }
+
+    fn skip_digits(&mut self) -> usize
+    {
+        let start = self.index;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.index += 1;
+        }
+        return self.index - start;
+    }
+
+    fn parse_number(&mut self) -> Option<JsonValue>
+    {
+        let start = self.index;
+        if self.peek() == Some(b'-') {
+            self.index += 1;
+        }
+        match self.peek()? {
+            b'0' => self.index += 1,
+            b'1'..=b'9' => { self.skip_digits(); },
+            _ => return None
+        };
+        if self.peek() == Some(b'.') {
+            self.index += 1;
+            if self.skip_digits() == 0 {
+                return None;
+            }
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            self.index += 1;
+            if let Some(b'+') | Some(b'-') = self.peek() {
+                self.index += 1;
+            }
+            if self.skip_digits() == 0 {
+                return None;
+            }
+        }
+        let text = std::str::from_utf8(&self.data[start..self.index]).ok()?;
+        return text.parse::<f64>().ok().filter(|value| value.is_finite()).map(JsonValue::Number);
+    }
+
+    fn parse_hex4(&mut self) -> Option<u32>
+    {
+        let mut result = 0;
+        for _ in 0..4 {
+            result = (result << 4) | (self.next()? as char).to_digit(16)?;
+        }
+        return Some(result);
+    }
+
+    fn parse_string(&mut self) -> Option<String>
+    {
+        if self.next()? != b'"' {
+            return None;
+        }
+        let mut result: Vec<u8> = Vec::new();
+        loop {
+            match self.next()? {
+                b'"' => break,
+                b'\\' => {
+                    let c = match self.next()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let high = self.parse_hex4()?;
+                            if high >= 0xD800 && high < 0xDC00 {
+                                self.expect(b"\\u")?;
+                                let low = self.parse_hex4()?;
+                                if low < 0xDC00 || low >= 0xE000 {
+                                    return None;
+                                }
+                                std::char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))?
+                            } else {
+                                std::char::from_u32(high)?
+                            }
+                        },
+                        _ => return None
+                    };
+                    let mut buffer = [0; 4];
+                    result.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                },
+                byte if byte < 0x20 => return None,
+                byte => result.push(byte)
+            }
+        }
+        return String::from_utf8(result).ok();
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Option<JsonValue>
+    {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        self.skip_whitespace();
+        let result = match self.peek()? {
+            b'n' => self.expect(b"null").map(|_| JsonValue::Null),
+            b't' => self.expect(b"true").map(|_| JsonValue::Bool(true)),
+            b'f' => self.expect(b"false").map(|_| JsonValue::Bool(false)),
+            b'"' => self.parse_string().map(JsonValue::String),
+            b'[' => {
+                self.index += 1;
+                let mut elements = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b']') {
+                    self.index += 1;
+                } else {
+                    loop {
+                        elements.push(self.parse_value(depth + 1)?);
+                        self.skip_whitespace();
+                        match self.next()? {
+                            b',' => {},
+                            b']' => break,
+                            _ => return None
+                        };
+                    }
+                }
+                Some(JsonValue::Array(elements))
+            },
+            b'{' => {
+                self.index += 1;
+                let mut members: Vec<(String, JsonValue)> = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b'}') {
+                    self.index += 1;
+                } else {
+                    loop {
+                        self.skip_whitespace();
+                        let name = self.parse_string()?;
+                        if members.iter().any(|member| member.0 == name) {
+                            return None;
+                        }
+                        self.skip_whitespace();
+                        if self.next()? != b':' {
+                            return None;
+                        }
+                        members.push((name, self.parse_value(depth + 1)?));
+                        self.skip_whitespace();
+                        match self.next()? {
+                            b',' => {},
+                            b'}' => break,
+                            _ => return None
+                        };
+                    }
+                }
+                Some(JsonValue::Object(members))
+            },
+            _ => self.parse_number()
+        };
+        self.skip_whitespace();
+        return result;
+    }
+}
+
+/// Parses a complete JSON document, returns None if it is not valid
+pub fn parse(text: &str) -> Option<JsonValue>
+{
+    let mut parser = Parser { data: text.as_bytes(), index: 0 };
+    let result = parser.parse_value(0)?;
+    if parser.index != parser.data.len() {
+        return None;
+    }
+    return Some(result);
+}
+
+#[test]
+fn test_write_parse() {
+    let value = JsonValue::Object(vec![
+        ("kty".to_owned(), JsonValue::String("AKP".to_owned())),
+        ("list".to_owned(), JsonValue::Array(vec![JsonValue::Null, JsonValue::Bool(true), JsonValue::Number(-12.5), JsonValue::Number(3.0)])),
+        ("escaped".to_owned(), JsonValue::String("a\"b\\c\nd\u{1}\u{e9}".to_owned())),
+        ("empty".to_owned(), JsonValue::Object(vec![]))
+    ]);
+    let text = write(&value);
+    assert_eq!("{\"kty\":\"AKP\",\"list\":[null,true,-12.5,3],\"escaped\":\"a\\\"b\\\\c\\nd\\u0001\u{e9}\",\"empty\":{}}", text);
+    assert_eq!(Some(value), parse(&text));
+}
+
+#[test]
+fn test_parse() {
+    let value = parse(" { \"a\" : [ 1e2 , \"\\u00e9\\ud83d\\ude00\\/\" ] , \"b\":{ } } ").unwrap();
+    assert_eq!(Some(&JsonValue::Array(vec![JsonValue::Number(100.), JsonValue::String("\u{e9}\u{1F600}/".to_owned())])), value.get("a"));
+    assert_eq!(Some(&JsonValue::Object(vec![])), value.get("b"));
+    assert_eq!(None, value.get("c"));
+}
+
+#[test]
+fn test_parse_rejects_invalid() {
+    for text in &["", "{", "{\"a\":1,}", "[1,]", "{\"a\":1,\"a\":2}", "01", "1.", "-", "\"\\ud83d\"", "\"a\u{1}\"", "nul", "[] []", "{a:1}", "\"\\x\""] {
+        assert_eq!(None, parse(text), "{}", text);
+    }
+    let nested = "[".repeat(100) + &"]".repeat(100);
+    assert_eq!(None, parse(&nested));
+}
@@ -0,0 +1,154 @@
+//! JSON Web Key (RFC 7517) representation of Kyber keys. As in the JOSE drafts for
+//! ML-KEM, we use the key type AKP ("algorithm key pair"), where the parameter set
+//! is determined by the algorithm and the raw key encodings of io.rs are stored
+//! base64url encoded in the members pub and priv, e.g.
+//!
+//! ```text
+//! {"kty":"AKP","alg":"KYBER768-R1","pub":"...","priv":"..."}
+//! ```
+
+use super::encoding::*;
+use super::json::*;
+use super::kyber::PublicKey;
+use super::kem::{ KemSecretKey, KEM_SECRET_KEY_BYTES };
+use super::io;
+
+pub const KEY_TYPE: &str = "AKP";
+/// There is no registered algorithm name for the round 1 version of Kyber; this one
+/// identifies the parameter set PARAMETER_SET_ID
+pub const ALGORITHM: &str = "KYBER768-R1";
+
+pub trait JwkEncodable: Sized
+{
+    fn to_jwk(&self) -> String;
+    fn from_jwk(jwk: &str) -> Option<Self>;
+}
+
+pub fn base64url_encode(data: &[u8]) -> String
+{
+    let mut result = String::new();
+    {
+        let mut encoder = Base64Encoder::new_url_safe(|c| result.push(c));
+        encoder.encode_bytes(data);
+    }
+    return result;
+}
+
+/// Decodes unpadded base64url, returns None if the text contains other characters
+/// or is not the canonical encoding of its content
+pub fn base64url_decode(text: &str) -> Option<Vec<u8>>
+{
+    if text.len() % 4 == 1 || !text.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return None;
+    }
+    let mut chars = text.chars();
+    let mut decoder = Base64Decoder::new_url_safe(|| chars.next());
+    let result = (0..(text.len() * 6 / 8)).map(|_| decoder.read()).collect::<Option<Vec<u8>>>()?;
+    // the unused bits of the last character must be zero
+    if base64url_encode(&result) != text {
+        return None;
+    }
+    return Some(result);
+}
+
+fn key_members(public_key: &PublicKey) -> Vec<(String, JsonValue)>
+{
+    vec![
+        ("kty".to_owned(), JsonValue::String(KEY_TYPE.to_owned())),
+        ("alg".to_owned(), JsonValue::String(ALGORITHM.to_owned())),
+        ("pub".to_owned(), JsonValue::String(base64url_encode(&io::pk_to_bytes(public_key))))
+    ]
+}
+
+/// Checks the key type and algorithm and returns the key with the public key bytes
+fn parse_key(jwk: &str) -> Option<(JsonValue, Vec<u8>)>
+{
+    let key = parse(jwk)?;
+    if key.get("kty")?.as_str()? != KEY_TYPE || key.get("alg")?.as_str()? != ALGORITHM {
+        return None;
+    }
+    let public_key = base64url_decode(key.get("pub")?.as_str()?)?;
+    io::validate_public_key(&public_key).ok()?;
+    return Some((key, public_key));
+}
+
+impl JwkEncodable for PublicKey
+{
+    fn to_jwk(&self) -> String
+    {
+        write(&JsonValue::Object(key_members(self)))
+    }
+
+    /// Also accepts private keys, but ignores the private part
+    fn from_jwk(jwk: &str) -> Option<PublicKey>
+    {
+        let (_, public_key) = parse_key(jwk)?;
+        return io::pk_from_bytes(&public_key).ok();
+    }
+}
+
+impl JwkEncodable for KemSecretKey
+{
+    fn to_jwk(&self) -> String
+    {
+        let mut members = key_members(&self.1);
+        members.push(("priv".to_owned(), JsonValue::String(base64url_encode(&io::kem_sk_to_bytes(self)))));
+        return write(&JsonValue::Object(members));
+    }
+
+    /// The public key member must match the public key contained in the secret key
+    fn from_jwk(jwk: &str) -> Option<KemSecretKey>
+    {
+        let (key, public_key) = parse_key(jwk)?;
+        let secret_key = base64url_decode(key.get("priv")?.as_str()?)?;
+        if secret_key.len() != KEM_SECRET_KEY_BYTES {
+            return None;
+        }
+        let result = io::kem_sk_from_bytes(&secret_key).ok()?;
+        if io::pk_to_bytes(&result.1) != public_key {
+            return None;
+        }
+        return Some(result);
+    }
+}
+
+#[test]
+fn test_base64url() {
+    assert_eq!("", base64url_encode(&[]));
+    assert_eq!("Zm9vYg", base64url_encode(b"foob"));
+    assert_eq!(Some(b"foob".to_vec()), base64url_decode("Zm9vYg"));
+    assert_eq!(Some(vec![0xFB, 0xFF]), base64url_decode("-_8"));
+    assert_eq!(None, base64url_decode("Zm9vYg=="));
+    assert_eq!(None, base64url_decode("Zm9vYh"));
+    assert_eq!(None, base64url_decode("Zm9vY"));
+    assert_eq!(None, base64url_decode("+/8"));
+}
+
+#[test]
+fn test_to_from_jwk() {
+    let (sk, pk) = super::kem::key_gen([6; 32], [7; 32]);
+    let public_jwk = pk.to_jwk();
+    let private_jwk = sk.to_jwk();
+    assert!(public_jwk.starts_with("{\"kty\":\"AKP\",\"alg\":\"KYBER768-R1\",\"pub\":\""));
+    assert_eq!(public_jwk, PublicKey::from_jwk(&public_jwk).unwrap().to_jwk());
+    assert_eq!(public_jwk, PublicKey::from_jwk(&private_jwk).unwrap().to_jwk());
+    assert_eq!(private_jwk, KemSecretKey::from_jwk(&private_jwk).unwrap().to_jwk());
+    assert!(KemSecretKey::from_jwk(&public_jwk).is_none());
+}
+
+#[test]
+fn test_from_jwk_rejects_invalid() {
+    let (sk, pk) = super::kem::key_gen([6; 32], [7; 32]);
+    let (other_sk, _) = super::kem::key_gen([8; 32], [9; 32]);
+    let pub_value = base64url_encode(&io::pk_to_bytes(&pk));
+    assert!(PublicKey::from_jwk(&format!("{{\"kty\":\"OKP\",\"alg\":\"{}\",\"pub\":\"{}\"}}", ALGORITHM, pub_value)).is_none());
+    assert!(PublicKey::from_jwk(&format!("{{\"kty\":\"AKP\",\"alg\":\"ML-KEM-768\",\"pub\":\"{}\"}}", pub_value)).is_none());
+    assert!(PublicKey::from_jwk(&format!("{{\"kty\":\"AKP\",\"alg\":\"{}\",\"pub\":\"{}AA\"}}", ALGORITHM, pub_value)).is_none());
+    assert!(PublicKey::from_jwk(&format!("{{\"kty\":\"AKP\",\"alg\":\"{}\",\"pub\":\"{}\",\"pub\":\"{}\"}}", ALGORITHM, pub_value, pub_value)).is_none());
+    assert!(PublicKey::from_jwk(&format!("{{\"kty\":\"AKP\",\"alg\":\"{}\",\"pub\":\"{}\",\"kid\":\"1\"}}", ALGORITHM, pub_value)).is_some());
+
+    let mismatched = format!("{{\"kty\":\"AKP\",\"alg\":\"{}\",\"pub\":\"{}\",\"priv\":\"{}\"}}",
+        ALGORITHM, pub_value, base64url_encode(&io::kem_sk_to_bytes(&other_sk)));
+    assert!(KemSecretKey::from_jwk(&mismatched).is_none());
+    assert!(KemSecretKey::from_jwk(&sk.to_jwk().replace("\"priv\":\"", "\"priv\":\"AAAA")).is_none());
+}
@@ -0,0 +1,134 @@
+//! The CCA-secure key encapsulation mechanism Kyber.KEM, obtained by applying
+//! a variant of the Fujisaki-Okamoto transform to the CPA-secure encryption
+//! scheme in kyber.rs. Invalid ciphertexts are rejected implicitly, i.e. they
+//! yield a pseudorandom key derived from the secret value z.
+
+use super::kyber::*;
+use super::io;
+
+use sha3::digest::{ ExtendableOutput, FixedOutput, Input, XofReader };
+
+pub type SharedSecret = [u8; 32];
+
+/// The secret key (s, pk, H(pk), z)
+pub type KemSecretKey = (SecretKey, PublicKey, [u8; 32], Seed);
+
+pub const KEM_SECRET_KEY_BYTES: usize = SECRET_KEY_BYTES + PUBLIC_KEY_BYTES + 64;
+
+/// The compact form (matrix_seed, secret_seed) of the secret key, from which key_gen()
+/// derives everything else. Note that the expanded secret key only contains the hash z
+/// of the secret seed, so it cannot be converted back into this form.
+pub type KemSecretSeed = (Seed, Seed);
+
+pub const KEM_SECRET_SEED_BYTES: usize = 64;
+
+fn hash_h(data: &[&[u8]]) -> [u8; 32]
+{
+    let mut hasher = sha3::Sha3_256::default();
+    for part in data {
+        hasher.input(part);
+    }
+    let mut result = [0; 32];
+    result.copy_from_slice(&hasher.fixed_result());
+    return result;
+}
+
+fn hash_g(data: &[&[u8]]) -> ([u8; 32], Seed)
+{
+    let mut hasher = sha3::Sha3_512::default();
+    for part in data {
+        hasher.input(part);
+    }
+    let digest = hasher.fixed_result();
+    let mut fst = [0; 32];
+    let mut snd = [0; 32];
+    fst.copy_from_slice(&digest[0..32]);
+    snd.copy_from_slice(&digest[32..64]);
+    return (fst, snd);
+}
+
+fn kdf(pre_key: &[u8; 32], ciphertext_hash: &[u8; 32]) -> SharedSecret
+{
+    let mut hasher = sha3::Shake256::default();
+    hasher.input(pre_key);
+    hasher.input(ciphertext_hash);
+    let mut result = [0; 32];
+    hasher.xof_result().read(&mut result);
+    return result;
+}
+
+/// Generates a key pair. The rejection value z is derived from the secret seed, so
+/// the whole secret key is determined by the two seeds.
+pub fn key_gen(matrix_seed: Seed, secret_seed: Seed) -> (KemSecretKey, PublicKey)
+{
+    let (sk, pk) = super::kyber::key_gen(matrix_seed, secret_seed);
+    let pk_hash = hash_h(&[&io::pk_to_bytes(&pk)]);
+    let z = hash_h(&[&secret_seed]);
+    return ((sk, pk.clone(), pk_hash, z), pk);
+}
+
+/// Recomputes s (and the public key) from the compact form of the secret key
+pub fn expand_secret_seed(seed: &KemSecretSeed) -> KemSecretKey
+{
+    key_gen(seed.0, seed.1).0
+}
+
+pub fn encapsulate(pk: &PublicKey, seed: Seed) -> (SharedSecret, Ciphertext)
+{
+    let m: Plaintext = hash_h(&[&seed]);
+    let pk_hash = hash_h(&[&io::pk_to_bytes(pk)]);
+    let (pre_key, enc_seed) = hash_g(&[&m, &pk_hash]);
+    let ciphertext = encrypt(pk, m, enc_seed);
+    let ciphertext_hash = hash_h(&[&io::ciphertext_to_bytes(&ciphertext)]);
+    return (kdf(&pre_key, &ciphertext_hash), ciphertext);
+}
+
+pub fn decapsulate(sk: &KemSecretKey, ciphertext: &Ciphertext) -> SharedSecret
+{
+    let (s, pk, pk_hash, z) = sk;
+    let m = decrypt(s.clone(), ciphertext.clone());
+    let (pre_key, enc_seed) = hash_g(&[&m, pk_hash]);
+    let ciphertext_bytes = io::ciphertext_to_bytes(ciphertext);
+    let reencrypted_bytes = io::ciphertext_to_bytes(&encrypt(pk, m, enc_seed));
+
+    // do not exit early, so that the running time does not depend on the position of the first difference
+    let difference = ciphertext_bytes.iter().zip(reencrypted_bytes.iter()).fold(0, |acc, (a, b)| acc | (a ^ b));
+    let mask: u8 = ((difference as u16).wrapping_sub(1) >> 8) as u8;
+    let chosen_key: [u8; 32] = super::util::create_array(|i| (pre_key[i] & mask) | (z[i] & !mask));
+    return kdf(&chosen_key, &hash_h(&[&ciphertext_bytes]));
+}
+
+#[cfg(test)]
+const TEST_SEED: Seed = [
+    0x12, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x07
+];
+
+#[test]
+fn test_encapsulate_decapsulate() {
+    let (sk, pk) = key_gen(TEST_SEED, TEST_SEED);
+    let (key, ciphertext) = encapsulate(&pk, TEST_SEED);
+    assert_eq!(key, decapsulate(&sk, &ciphertext));
+}
+
+#[test]
+fn test_implicit_rejection() {
+    let (sk, pk) = key_gen(TEST_SEED, TEST_SEED);
+    let (key, ciphertext) = encapsulate(&pk, TEST_SEED);
+    let mut bytes = io::ciphertext_to_bytes(&ciphertext);
+    bytes[5] ^= 0x10;
+    let tampered = io::ciphertext_from_bytes(&bytes).unwrap();
+    let rejected_key = decapsulate(&sk, &tampered);
+    assert_ne!(key, rejected_key);
+    // the rejection is deterministic
+    assert_eq!(rejected_key, decapsulate(&sk, &tampered));
+}
+
+#[test]
+fn test_expand_secret_seed() {
+    let (sk, pk) = key_gen([3; 32], [4; 32]);
+    let expanded = expand_secret_seed(&([3; 32], [4; 32]));
+    assert_eq!(io::kem_sk_to_bytes(&sk), io::kem_sk_to_bytes(&expanded));
+    let (key, ciphertext) = encapsulate(&pk, [5; 32]);
+    assert_eq!(key, decapsulate(&expanded, &ciphertext));
+}
@@ -0,0 +1,613 @@
+//! A directory of named keys. Each key is stored in the file NAME.json, which contains
+//! its metadata, the public key and optionally the secret key, encrypted under a
+//! passphrase:
+//!
+//! ```text
+//! {"version":1,"name":"alice","created":1790000000,"parameter_set":1,"fingerprint":"<hex>",
+//!  "public_key":"<base64url>",
+//!  "secret_key":{"kdf":"scrypt-sha3-256","log_n":15,"r":8,"p":1,"salt":"<base64url>","ciphertext":"<base64url>"}}
+//! ```
+//!
+//! The AEAD key and nonce are derived from the passphrase with scrypt, and the fingerprint
+//! is authenticated as additional data, so an encrypted secret key cannot be moved to
+//! another public key. The plaintext is a format byte, followed by the expanded secret key
+//! or by the seed, depending on the form in which the key was imported.
+
+use super::kyber::{ PublicKey, Seed, PARAMETER_SET_ID };
+use super::kem::{ KemSecretKey, KemSecretSeed, KEM_SECRET_KEY_BYTES, KEM_SECRET_SEED_BYTES, expand_secret_seed };
+use super::fingerprint;
+use super::fingerprint::Fingerprint;
+use super::json::*;
+use super::jwk::{ base64url_encode, base64url_decode };
+use super::scrypt;
+use super::chacha20poly1305 as aead;
+use super::chacha20poly1305::{ AeadKey, AeadNonce };
+use super::io;
+use super::util;
+
+use std::io::Write;
+use std::path::{ Path, PathBuf };
+
+const VERSION: f64 = 1.;
+const KDF_NAME: &str = "scrypt-sha3-256";
+const AAD_LABEL: &[u8] = b"Kyber keystore v1";
+
+const FORMAT_EXPANDED: u8 = 0;
+const FORMAT_SEED: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScryptParams
+{
+    pub log_n: u8,
+    pub r: usize,
+    pub p: usize
+}
+
+impl ScryptParams
+{
+    /// 128 * r * 2^log_n bytes of memory, which are hashed p times
+    pub fn work(&self) -> u64
+    {
+        (self.p as u64 * self.r as u64) << (self.log_n as u32 + 7)
+    }
+}
+
+/// 32 MiB of memory
+pub const DEFAULT_SCRYPT_PARAMS: ScryptParams = ScryptParams { log_n: 15, r: 8, p: 1 };
+/// Do not derive keys with parameters from a key file that require more than 256 MiB of
+/// memory (8 times the default), or hashing that memory more than once, i.e. p times the
+/// memory must not exceed 256 MiB. Like maxmem of OpenSSL, this prevents a modified or
+/// foreign key file from exhausting the memory.
+pub const MAX_SCRYPT_WORK: u64 = 1 << 28;
+
+#[derive(Debug)]
+pub enum KeystoreError
+{
+    Io(std::io::Error),
+    InvalidName(String),
+    NotFound(String),
+    AlreadyExists(String),
+    Ambiguous(String, usize),
+    Corrupted(String),
+    NoSecretKey(String),
+    ScryptTooExpensive(String, ScryptParams),
+    WrongPassphrase
+}
+
+impl From<std::io::Error> for KeystoreError
+{
+    fn from(error: std::io::Error) -> KeystoreError
+    {
+        KeystoreError::Io(error)
+    }
+}
+
+impl std::fmt::Display for KeystoreError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        match self {
+            KeystoreError::Io(error) => write!(f, "IO error: {}", error),
+            KeystoreError::InvalidName(name) => write!(f, "Invalid key name {}, use only letters, digits, ., _ and -", name),
+            KeystoreError::NotFound(name) => write!(f, "There is no key {} in the keystore", name),
+            KeystoreError::AlreadyExists(name) => write!(f, "The keystore already contains a key {}", name),
+            KeystoreError::Ambiguous(query, count) => write!(f, "The fingerprint {} matches {} keys in the keystore, give more digits", query, count),
+            KeystoreError::Corrupted(name) => write!(f, "The keystore entry {} is corrupted", name),
+            KeystoreError::NoSecretKey(name) => write!(f, "The keystore only contains the public key of {}", name),
+            KeystoreError::ScryptTooExpensive(name, params) => write!(f, "The keystore entry {} requires scrypt with log_n = {}, r = {}, p = {}, i.e. {} MiB of memory hashed {} times, which exceeds the limit of {} MiB",
+                name, params.log_n, params.r, params.p, (params.work() / params.p as u64) >> 20, params.p, MAX_SCRYPT_WORK >> 20),
+            KeystoreError::WrongPassphrase => write!(f, "Wrong passphrase")
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct EncryptedSecretKey
+{
+    params: ScryptParams,
+    salt: Seed,
+    ciphertext: Vec<u8>
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyEntry
+{
+    pub name: String,
+    /// Seconds since the unix epoch
+    pub created: u64,
+    pub parameter_set: u8,
+    pub fingerprint: Fingerprint,
+    pub public_key: PublicKey,
+    secret_key: Option<EncryptedSecretKey>
+}
+
+fn derive_key(passphrase: &[u8], salt: &Seed, params: &ScryptParams) -> (AeadKey, AeadNonce)
+{
+    let output = scrypt::scrypt(passphrase, salt, params.log_n, params.r, params.p, 44);
+    let mut key: AeadKey = [0; 32];
+    let mut nonce: AeadNonce = [0; 12];
+    key.copy_from_slice(&output[0..32]);
+    nonce.copy_from_slice(&output[32..44]);
+    return (key, nonce);
+}
+
+fn additional_data(fingerprint: &Fingerprint) -> Vec<u8>
+{
+    let mut result = AAD_LABEL.to_vec();
+    result.extend_from_slice(fingerprint);
+    return result;
+}
+
+impl KeyEntry
+{
+    pub fn has_secret_key(&self) -> bool
+    {
+        self.secret_key.is_some()
+    }
+
+    /// Returns the secret key and, if it was imported in seed form, the seed
+    pub fn decrypt_secret_key(&self, passphrase: &[u8]) -> Result<(KemSecretKey, Option<KemSecretSeed>), KeystoreError>
+    {
+        let encrypted = self.secret_key.as_ref().ok_or(KeystoreError::NoSecretKey(self.name.clone()))?;
+        if encrypted.params.work() > MAX_SCRYPT_WORK {
+            return Err(KeystoreError::ScryptTooExpensive(self.name.clone(), encrypted.params));
+        }
+        let (key, nonce) = derive_key(passphrase, &encrypted.salt, &encrypted.params);
+        let plaintext = aead::open(&key, &nonce, &additional_data(&self.fingerprint), &encrypted.ciphertext)
+            .ok_or(KeystoreError::WrongPassphrase)?;
+        let (sk, seed) = match (plaintext.first().copied(), plaintext.len().saturating_sub(1)) {
+            (Some(FORMAT_EXPANDED), KEM_SECRET_KEY_BYTES) => {
+                (io::kem_sk_from_bytes(&plaintext[1..]).map_err(|_| KeystoreError::Corrupted(self.name.clone()))?, None)
+            },
+            (Some(FORMAT_SEED), KEM_SECRET_SEED_BYTES) => {
+                let seed = io::secret_seed_from_bytes(&plaintext[1..]).map_err(|_| KeystoreError::Corrupted(self.name.clone()))?;
+                (expand_secret_seed(&seed), Some(seed))
+            },
+            _ => return Err(KeystoreError::Corrupted(self.name.clone()))
+        };
+        if fingerprint::fingerprint(&sk.1) != self.fingerprint {
+            return Err(KeystoreError::Corrupted(self.name.clone()));
+        }
+        return Ok((sk, seed));
+    }
+
+    fn to_json(&self) -> JsonValue
+    {
+        let mut members = vec![
+            ("version".to_owned(), JsonValue::Number(VERSION)),
+            ("name".to_owned(), JsonValue::String(self.name.clone())),
+            ("created".to_owned(), JsonValue::Number(self.created as f64)),
+            ("parameter_set".to_owned(), JsonValue::Number(self.parameter_set as f64)),
+            ("fingerprint".to_owned(), JsonValue::String(fingerprint::to_hex(&self.fingerprint))),
+            ("public_key".to_owned(), JsonValue::String(base64url_encode(&io::pk_to_bytes(&self.public_key))))
+        ];
+        if let Some(encrypted) = &self.secret_key {
+            members.push(("secret_key".to_owned(), JsonValue::Object(vec![
+                ("kdf".to_owned(), JsonValue::String(KDF_NAME.to_owned())),
+                ("log_n".to_owned(), JsonValue::Number(encrypted.params.log_n as f64)),
+                ("r".to_owned(), JsonValue::Number(encrypted.params.r as f64)),
+                ("p".to_owned(), JsonValue::Number(encrypted.params.p as f64)),
+                ("salt".to_owned(), JsonValue::String(base64url_encode(&encrypted.salt))),
+                ("ciphertext".to_owned(), JsonValue::String(base64url_encode(&encrypted.ciphertext)))
+            ])));
+        }
+        return JsonValue::Object(members);
+    }
+
+    fn from_json(value: &JsonValue) -> Option<KeyEntry>
+    {
+        let number = |value: &JsonValue, name: &str, max: f64| match value.get(name)? {
+            JsonValue::Number(x) if *x >= 0. && *x <= max && x.fract() == 0. => Some(*x as u64),
+            _ => None
+        };
+        if number(value, "version", VERSION)? != VERSION as u64 {
+            return None;
+        }
+        let public_key = io::validate_public_key(&base64url_decode(value.get("public_key")?.as_str()?)?).ok()?;
+        let fingerprint = fingerprint::fingerprint(&public_key);
+        if value.get("fingerprint")?.as_str()? != fingerprint::to_hex(&fingerprint) {
+            return None;
+        }
+        let secret_key = match value.get("secret_key") {
+            None => None,
+            Some(encrypted) => {
+                if encrypted.get("kdf")?.as_str()? != KDF_NAME {
+                    return None;
+                }
+                let params = ScryptParams {
+                    log_n: number(encrypted, "log_n", 31.)? as u8,
+                    r: number(encrypted, "r", 1024.)? as usize,
+                    p: number(encrypted, "p", 1024.)? as usize
+                };
+                // parameters above MAX_SCRYPT_WORK are rejected by decrypt_secret_key(), so
+                // that the public key can still be used
+                if params.log_n == 0 || params.r == 0 || params.p == 0 {
+                    return None;
+                }
+                let salt_bytes = base64url_decode(encrypted.get("salt")?.as_str()?)?;
+                if salt_bytes.len() != 32 {
+                    return None;
+                }
+                let mut salt: Seed = [0; 32];
+                salt.copy_from_slice(&salt_bytes);
+                let ciphertext = base64url_decode(encrypted.get("ciphertext")?.as_str()?)?;
+                Some(EncryptedSecretKey { params: params, salt: salt, ciphertext: ciphertext })
+            }
+        };
+        return Some(KeyEntry {
+            name: value.get("name")?.as_str()?.to_owned(),
+            created: number(value, "created", 9007199254740991.)?,
+            parameter_set: number(value, "parameter_set", 255.)? as u8,
+            fingerprint: fingerprint,
+            public_key: public_key,
+            secret_key: secret_key
+        });
+    }
+}
+
+pub fn is_valid_name(name: &str) -> bool
+{
+    name.len() > 0 && name.len() <= 64 && !name.starts_with('.') &&
+        name.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-')
+}
+
+/// Formats the seconds since the unix epoch as UTC date and time
+pub fn format_timestamp(timestamp: u64) -> String
+{
+    let days = (timestamp / 86400) as i64;
+    let seconds = timestamp % 86400;
+    // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    return format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day, seconds / 3600, (seconds / 60) % 60, seconds % 60);
+}
+
+/// Creates the directory, if it does not exist yet, such that only the owner can access it
+fn create_private_dir(dir: &Path) -> std::io::Result<()>
+{
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(dir)
+}
+
+fn write_temp_file(temp_path: &Path, entry: &KeyEntry) -> Result<(), KeystoreError>
+{
+    let mut file = util::create_secret_file(temp_path)?;
+    file.write_all(write(&entry.to_json()).as_bytes())?;
+    file.sync_all()?;
+    return Ok(());
+}
+
+pub struct Keystore
+{
+    dir: PathBuf
+}
+
+impl Keystore
+{
+    pub fn open<P: AsRef<Path>>(dir: P) -> Keystore
+    {
+        Keystore { dir: dir.as_ref().to_path_buf() }
+    }
+
+    pub fn dir(&self) -> &Path
+    {
+        &self.dir
+    }
+
+    fn entry_path(&self, name: &str) -> Result<PathBuf, KeystoreError>
+    {
+        if !is_valid_name(name) {
+            return Err(KeystoreError::InvalidName(name.to_owned()));
+        }
+        return Ok(self.dir.join(format!("{}.json", name)));
+    }
+
+    /// Returns all entries, sorted by name, and the errors of the entries that could not be
+    /// read, which are skipped, so that a single corrupted file does not hide all other keys;
+    /// a missing directory is an empty keystore
+    pub fn list(&self) -> Result<(Vec<KeyEntry>, Vec<KeystoreError>), KeystoreError>
+    {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(ref error) if error.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), Vec::new())),
+            Err(error) => return Err(KeystoreError::Io(error))
+        };
+        let mut names = Vec::new();
+        for entry in entries {
+            let file_name = entry?.file_name();
+            match file_name.to_str().and_then(|file_name| file_name.strip_suffix(".json")) {
+                Some(name) if is_valid_name(name) => names.push(name.to_owned()),
+                _ => continue
+            };
+        }
+        names.sort();
+        let mut result = Vec::new();
+        let mut errors = Vec::new();
+        for name in names {
+            match self.get(&name) {
+                Ok(entry) => result.push(entry),
+                Err(error) => errors.push(error)
+            };
+        }
+        return Ok((result, errors));
+    }
+
+    pub fn get(&self, name: &str) -> Result<KeyEntry, KeystoreError>
+    {
+        let text = match std::fs::read_to_string(self.entry_path(name)?) {
+            Ok(text) => text,
+            Err(ref error) if error.kind() == std::io::ErrorKind::NotFound => return Err(KeystoreError::NotFound(name.to_owned())),
+            Err(error) => return Err(KeystoreError::Io(error))
+        };
+        return parse(&text).as_ref().and_then(KeyEntry::from_json)
+            .filter(|entry| entry.name == name)
+            .ok_or(KeystoreError::Corrupted(name.to_owned()));
+    }
+
+    /// Finds the key with the given name or, if there is none, the key whose fingerprint
+    /// matches the query (see fingerprint::matches())
+    pub fn find(&self, query: &str) -> Result<KeyEntry, KeystoreError>
+    {
+        if is_valid_name(query) && self.entry_path(query)?.is_file() {
+            return self.get(query);
+        }
+        let mut found: Vec<KeyEntry> = self.list()?.0.into_iter().filter(|entry| fingerprint::matches(&entry.fingerprint, query)).collect();
+        match found.len() {
+            0 => Err(KeystoreError::NotFound(query.to_owned())),
+            1 => Ok(found.pop().unwrap()),
+            count => Err(KeystoreError::Ambiguous(query.to_owned(), count))
+        }
+    }
+
+    fn write_entry(&self, entry: &KeyEntry) -> Result<(), KeystoreError>
+    {
+        let path = self.entry_path(&entry.name)?;
+        create_private_dir(&self.dir)?;
+        // write to a temporary file first, so that there are never partially written entries;
+        // the random suffix keeps concurrent writers of the same name apart
+        let temp_path = self.dir.join(format!(".{}.{}.json.tmp", entry.name, util::to_hex(&util::random_seed()[..8])));
+        let result = write_temp_file(&temp_path, entry).and_then(|_| {
+            // unlike rename(), hard_link() fails if the entry exists, so an entry that was
+            // added concurrently is never replaced
+            match std::fs::hard_link(&temp_path, &path) {
+                Err(ref error) if error.kind() == std::io::ErrorKind::AlreadyExists => Err(KeystoreError::AlreadyExists(entry.name.clone())),
+                result => Ok(result?)
+            }
+        });
+        let _ = std::fs::remove_file(&temp_path);
+        return result;
+    }
+
+    pub fn add_public_key(&self, name: &str, public_key: &PublicKey, created: u64) -> Result<KeyEntry, KeystoreError>
+    {
+        let entry = KeyEntry {
+            name: name.to_owned(),
+            created: created,
+            parameter_set: PARAMETER_SET_ID,
+            fingerprint: fingerprint::fingerprint(public_key),
+            public_key: public_key.clone(),
+            secret_key: None
+        };
+        self.write_entry(&entry)?;
+        return Ok(entry);
+    }
+
+    /// Stores the secret key encrypted under the passphrase; if the seed is given, only
+    /// the seed is stored. The salt must be chosen randomly.
+    pub fn add_secret_key(&self, name: &str, secret_key: &KemSecretKey, seed: Option<&KemSecretSeed>, passphrase: &[u8],
+        params: ScryptParams, salt: Seed, created: u64) -> Result<KeyEntry, KeystoreError>
+    {
+        assert!(params.work() <= MAX_SCRYPT_WORK, "The scrypt parameters exceed the limit for decryption");
+        let fingerprint = fingerprint::fingerprint(&secret_key.1);
+        let mut plaintext = match seed {
+            Some(seed) => {
+                assert!(fingerprint::fingerprint(&expand_secret_seed(seed).1) == fingerprint, "Seed does not belong to the secret key");
+                let mut result = vec![FORMAT_SEED];
+                result.extend_from_slice(&io::secret_seed_to_bytes(seed));
+                result
+            },
+            None => {
+                let mut result = vec![FORMAT_EXPANDED];
+                result.extend_from_slice(&io::kem_sk_to_bytes(secret_key));
+                result
+            }
+        };
+        let (key, nonce) = derive_key(passphrase, &salt, &params);
+        let ciphertext = aead::seal(&key, &nonce, &additional_data(&fingerprint), &plaintext);
+        for byte in plaintext.iter_mut() {
+            *byte = 0;
+        }
+        let entry = KeyEntry {
+            name: name.to_owned(),
+            created: created,
+            parameter_set: PARAMETER_SET_ID,
+            fingerprint: fingerprint,
+            public_key: secret_key.1.clone(),
+            secret_key: Some(EncryptedSecretKey { params: params, salt: salt, ciphertext: ciphertext })
+        };
+        self.write_entry(&entry)?;
+        return Ok(entry);
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), KeystoreError>
+    {
+        match std::fs::remove_file(self.entry_path(name)?) {
+            Err(ref error) if error.kind() == std::io::ErrorKind::NotFound => Err(KeystoreError::NotFound(name.to_owned())),
+            result => Ok(result?)
+        }
+    }
+}
+
+#[cfg(test)]
+const TEST_PARAMS: ScryptParams = ScryptParams { log_n: 4, r: 1, p: 1 };
+
+#[cfg(test)]
+fn test_keystore(test_name: &str) -> Keystore
+{
+    let dir = std::env::temp_dir().join(format!("kyber_keystore_{}_{}", test_name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    return Keystore::open(dir);
+}
+
+#[test]
+fn test_add_list_delete() {
+    let keystore = test_keystore("list");
+    assert_eq!(0, keystore.list().unwrap().0.len());
+    let (sk, pk) = super::kem::key_gen([1; 32], [2; 32]);
+    let (_, other_pk) = super::kem::key_gen([1; 32], [3; 32]);
+    keystore.add_secret_key("alice", &sk, None, b"passphrase", TEST_PARAMS, [4; 32], 1000).unwrap();
+    keystore.add_public_key("bob", &other_pk, 2000).unwrap();
+    match keystore.add_public_key("bob", &pk, 3000) {
+        Err(KeystoreError::AlreadyExists(_)) => {},
+        result => panic!("Expected error, got {:?}", result)
+    };
+    match keystore.add_public_key("../bob", &pk, 3000) {
+        Err(KeystoreError::InvalidName(_)) => {},
+        result => panic!("Expected error, got {:?}", result)
+    };
+
+    let (entries, errors) = keystore.list().unwrap();
+    assert!(errors.is_empty());
+    assert_eq!(vec!["alice", "bob"], entries.iter().map(|entry| entry.name.as_str()).collect::<Vec<_>>());
+    assert_eq!(1000, entries[0].created);
+    assert_eq!(PARAMETER_SET_ID, entries[0].parameter_set);
+    assert_eq!(fingerprint::fingerprint(&pk), entries[0].fingerprint);
+    assert!(entries[0].has_secret_key());
+    assert!(!entries[1].has_secret_key());
+
+    assert_eq!("bob", keystore.find(&fingerprint::short_id(&fingerprint::fingerprint(&other_pk))).unwrap().name);
+    assert_eq!("alice", keystore.find("alice").unwrap().name);
+    keystore.delete("alice").unwrap();
+    assert_eq!(1, keystore.list().unwrap().0.len());
+    match keystore.find("alice") {
+        Err(KeystoreError::NotFound(_)) => {},
+        result => panic!("Expected error, got {:?}", result)
+    };
+    std::fs::remove_dir_all(keystore.dir()).unwrap();
+}
+
+#[test]
+fn test_add_does_not_replace_entry() {
+    let keystore = test_keystore("replace");
+    let (sk, pk) = super::kem::key_gen([1; 32], [2; 32]);
+    let (_, other_pk) = super::kem::key_gen([1; 32], [3; 32]);
+    keystore.add_secret_key("alice", &sk, None, b"passphrase", TEST_PARAMS, [4; 32], 1000).unwrap();
+    match keystore.add_public_key("alice", &other_pk, 2000) {
+        Err(KeystoreError::AlreadyExists(_)) => {},
+        result => panic!("Expected error, got {:?}", result)
+    };
+    let entry = keystore.get("alice").unwrap();
+    assert_eq!(1000, entry.created);
+    assert_eq!(fingerprint::fingerprint(&pk), entry.fingerprint);
+    assert!(entry.decrypt_secret_key(b"passphrase").is_ok());
+    // no temporary files are left behind
+    assert_eq!(1, std::fs::read_dir(keystore.dir()).unwrap().count());
+    std::fs::remove_dir_all(keystore.dir()).unwrap();
+}
+
+#[test]
+fn test_encrypted_secret_key() {
+    let keystore = test_keystore("encrypted");
+    let seed = ([5; 32], [6; 32]);
+    let sk = expand_secret_seed(&seed);
+    keystore.add_secret_key("expanded", &sk, None, b"passphrase", TEST_PARAMS, [7; 32], 0).unwrap();
+    keystore.add_secret_key("seed", &sk, Some(&seed), b"passphrase", TEST_PARAMS, [8; 32], 0).unwrap();
+
+    let (decrypted, no_seed) = keystore.get("expanded").unwrap().decrypt_secret_key(b"passphrase").unwrap();
+    assert_eq!(io::kem_sk_to_bytes(&sk), io::kem_sk_to_bytes(&decrypted));
+    assert!(no_seed.is_none());
+    let (decrypted, decrypted_seed) = keystore.get("seed").unwrap().decrypt_secret_key(b"passphrase").unwrap();
+    assert_eq!(io::kem_sk_to_bytes(&sk), io::kem_sk_to_bytes(&decrypted));
+    assert_eq!(Some(seed), decrypted_seed);
+
+    match keystore.get("seed").unwrap().decrypt_secret_key(b"wrong passphrase") {
+        Err(KeystoreError::WrongPassphrase) => {},
+        result => panic!("Expected error, got {:?}", result.map(|_| ()))
+    };
+    // the secret key file is not readable by others
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(keystore.dir().join("seed.json")).unwrap().permissions().mode();
+        assert_eq!(0o600, mode & 0o777);
+    }
+    std::fs::remove_dir_all(keystore.dir()).unwrap();
+}
+
+#[test]
+fn test_secret_key_bound_to_public_key() {
+    let keystore = test_keystore("bound");
+    let (sk, _) = super::kem::key_gen([1; 32], [2; 32]);
+    let (_, other_pk) = super::kem::key_gen([1; 32], [3; 32]);
+    let entry = keystore.add_secret_key("alice", &sk, None, b"passphrase", TEST_PARAMS, [4; 32], 0).unwrap();
+    let mut json = write(&entry.to_json());
+    json = json.replace(&fingerprint::to_hex(&entry.fingerprint), &fingerprint::to_hex(&fingerprint::fingerprint(&other_pk)));
+    json = json.replace(&base64url_encode(&io::pk_to_bytes(&sk.1)), &base64url_encode(&io::pk_to_bytes(&other_pk)));
+    let moved = KeyEntry::from_json(&parse(&json).unwrap()).unwrap();
+    match moved.decrypt_secret_key(b"passphrase") {
+        Err(KeystoreError::WrongPassphrase) => {},
+        result => panic!("Expected error, got {:?}", result.map(|_| ()))
+    };
+    std::fs::remove_dir_all(keystore.dir()).unwrap();
+}
+
+#[test]
+fn test_list_skips_corrupted_entry() {
+    let keystore = test_keystore("corrupted");
+    let (_, pk) = super::kem::key_gen([1; 32], [2; 32]);
+    keystore.add_public_key("bob", &pk, 0).unwrap();
+    std::fs::write(keystore.dir().join("alice.json"), "{\"version\":1,").unwrap();
+
+    let (entries, errors) = keystore.list().unwrap();
+    assert_eq!(vec!["bob"], entries.iter().map(|entry| entry.name.as_str()).collect::<Vec<_>>());
+    match &errors[..] {
+        [KeystoreError::Corrupted(name)] => assert_eq!("alice", name),
+        errors => panic!("Expected one error, got {:?}", errors)
+    };
+    assert_eq!("bob", keystore.find(&fingerprint::short_id(&fingerprint::fingerprint(&pk))).unwrap().name);
+    std::fs::remove_dir_all(keystore.dir()).unwrap();
+}
+
+#[test]
+fn test_reject_expensive_scrypt_params() {
+    let keystore = test_keystore("expensive");
+    let (sk, _) = super::kem::key_gen([1; 32], [2; 32]);
+    let json = write(&keystore.add_secret_key("alice", &sk, None, b"passphrase", TEST_PARAMS, [4; 32], 0).unwrap().to_json());
+    let with_params = |log_n: u32, r: u32, p: u32| KeyEntry::from_json(&parse(&json
+        .replace("\"log_n\":4", &format!("\"log_n\":{}", log_n))
+        .replace("\"r\":1", &format!("\"r\":{}", r))
+        .replace("\"p\":1", &format!("\"p\":{}", p))).unwrap());
+    assert!(with_params(4, 1, 1).unwrap().decrypt_secret_key(b"passphrase").is_ok());
+    assert!(with_params(4, 1, 0).is_none());
+    // 256 MiB of memory, hashed once, is the maximum
+    assert!(ScryptParams { log_n: 18, r: 8, p: 1 }.work() <= MAX_SCRYPT_WORK);
+    assert!(ScryptParams { log_n: 15, r: 8, p: 8 }.work() <= MAX_SCRYPT_WORK);
+    for (log_n, r, p) in &[(19, 8, 1), (18, 8, 2), (25, 8, 1), (20, 8, 1024), (31, 1, 1)] {
+        // the entry is still read, but its secret key is not derived
+        match with_params(*log_n, *r, *p).unwrap().decrypt_secret_key(b"passphrase") {
+            Err(KeystoreError::ScryptTooExpensive(_, params)) => assert_eq!(ScryptParams { log_n: *log_n as u8, r: *r as usize, p: *p as usize }, params),
+            result => panic!("Expected error, got {:?}", result.map(|_| ()))
+        };
+    }
+    std::fs::remove_dir_all(keystore.dir()).unwrap();
+}
+
+#[test]
+fn test_format_timestamp() {
+    assert_eq!("1970-01-01 00:00:00 UTC", format_timestamp(0));
+    assert_eq!("2000-02-29 12:34:56 UTC", format_timestamp(951827696));
+    assert_eq!("2038-01-19 03:14:08 UTC", format_timestamp(1 << 31));
+}
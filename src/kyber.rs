@@ -1,36 +1,87 @@
 use super::util;
 
-#[cfg(not(target_feature = "avx2"))]
+#[cfg(all(not(target_feature = "avx2"), not(feature = "coefficient_domain")))]
 use super::ref_r;
-#[cfg(target_feature = "avx2")]
+#[cfg(all(target_feature = "avx2", not(feature = "coefficient_domain")))]
 use super::avx_r;
+#[cfg(feature = "coefficient_domain")]
+use super::coeff_r;
 
 use super::rqvec;
 use super::zq::*;
 use super::rqvec::*;
-use super::ring::{ RqElementChineseRemainderRepr, RqElementCoefficientRepr, CompressedRq, N };
+use super::ring::{ RqElementChineseRemainderRepr, RqElementCoefficientRepr, RqElementCompression, CompressedRq, N };
 
 use sha3::digest::{ ExtendableOutput, Input, XofReader };
 
-#[cfg(not(target_feature = "avx2"))]
+#[cfg(all(not(target_feature = "avx2"), not(feature = "coefficient_domain")))]
 pub type RqElement = ref_r::RqElementCoefficientReprImpl;
-#[cfg(target_feature = "avx2")]
+#[cfg(all(target_feature = "avx2", not(feature = "coefficient_domain")))]
 pub type RqElement = avx_r::RqElementCoefficientReprImpl;
+#[cfg(feature = "coefficient_domain")]
+pub type RqElement = coeff_r::RqElementCoefficientReprImpl<coeff_r::ToomCook4>;
 
 pub type RqVector = rqvec::RqVector3<RqElement>;
 pub type RqMatrix = rqvec::RqSquareMatrix3<RqElement>;
 
 /// Each public key and ciphertext element is compressed using this count of bits
-const COMPRESSED_VECTOR_BIT_SIZE: u16 = 11;
-const COMPRESSED_RING_ELEMENT_BIT_SIZE: u16 = 3;
+pub const COMPRESSED_VECTOR_BIT_SIZE: u16 = 11;
+pub const COMPRESSED_RING_ELEMENT_BIT_SIZE: u16 = 3;
+/// Compressing to ENCODE_BITS bits is lossless, as 2^ENCODE_BITS > q. Hence, using this
+/// for t yields public keys that contain t uncompressed.
+pub const UNCOMPRESSED_BIT_SIZE: u16 = ENCODE_BITS as u16;
+
+/// The parameter of the centered binomial distribution of secret and error coefficients
+pub const ETA: u16 = 4;
+/// The largest supported value of eta, see sample_centered_binomial_element()
+pub const MAX_ETA: u16 = 5;
+
+/// Identifies the parameter set (k = 3, q = 7681, eta = 4 and the compression above)
+/// in serialized containers
+pub const PARAMETER_SET_ID: u8 = 1;
+
+/// The sizes of the byte encodings, see io.rs
+pub const SECRET_KEY_BYTES: usize = DIM * N * ENCODE_BITS / 8;
+pub const PUBLIC_KEY_BYTES: usize = public_key_bytes(COMPRESSED_VECTOR_BIT_SIZE);
+pub const CIPHERTEXT_BYTES: usize = ciphertext_bytes(COMPRESSED_VECTOR_BIT_SIZE, COMPRESSED_RING_ELEMENT_BIT_SIZE);
+
+pub const fn public_key_bytes(dt: u16) -> usize
+{
+    DIM * N * dt as usize / 8 + 32
+}
+
+pub const fn ciphertext_bytes(du: u16, dv: u16) -> usize
+{
+    (DIM * N * du as usize + N * dv as usize) / 8
+}
 
 pub type Seed = [u8; 32];
-pub type PublicKey = (CompressedRqVector<COMPRESSED_VECTOR_BIT_SIZE>, Seed);
+/// A public key whose vector t is compressed to DT bits
+pub type GenericPublicKey<const DT: u16> = (CompressedRqVector<DT>, Seed);
+/// A ciphertext whose vector u is compressed to DU bits and whose element v to DV bits
+pub type GenericCiphertext<const DU: u16, const DV: u16> = (CompressedRqVector<DU>, CompressedRq<DV>);
+pub type PublicKey = GenericPublicKey<COMPRESSED_VECTOR_BIT_SIZE>;
 pub type SecretKey = RqVector;
-pub type Ciphertext = (CompressedRqVector<COMPRESSED_VECTOR_BIT_SIZE>, CompressedRq<COMPRESSED_RING_ELEMENT_BIT_SIZE>);
+pub type Ciphertext = GenericCiphertext<COMPRESSED_VECTOR_BIT_SIZE, COMPRESSED_RING_ELEMENT_BIT_SIZE>;
 pub type Plaintext = [u8; 32];
 
 pub fn encrypt(pk: &PublicKey, plaintext: Plaintext, enc_seed: Seed) -> Ciphertext
+{
+    encrypt_generic(pk, plaintext, enc_seed)
+}
+
+/// Encrypts with the compression (DT, DU, DV) instead of the one of the implemented
+/// parameter set
+pub fn encrypt_generic<const DT: u16, const DU: u16, const DV: u16>(pk: &GenericPublicKey<DT>, plaintext: Plaintext, enc_seed: Seed) -> GenericCiphertext<DU, DV>
+{
+    let message = RqElement::decompress(&CompressedRq::from_data(plaintext));
+    let (u, v) = encrypt_uncompressed(pk, &message, enc_seed);
+    return (u.compress(), v.compress());
+}
+
+/// Returns u = A^T r + e1 and v = t^T r + e2 + message, before compression. The message
+/// is added as it is, so it must already be scaled (e.g. by decompressing it).
+pub fn encrypt_uncompressed<const DT: u16>(pk: &GenericPublicKey<DT>, message: &RqElement, enc_seed: Seed) -> (RqVector, RqElement)
 {
     let t = RqVector::decompress(&pk.0);
     let A = sample_uniform_matrix(&pk.1);
@@ -39,20 +90,54 @@ pub fn encrypt(pk: &PublicKey, plaintext: Plaintext, enc_seed: Seed) -> Cipherte
     let e1 = sample_error_distribution_vector(&mut noise_random);
     let e2 = sample_error_distribution_element(&mut noise_random);
     let u = A.transpose() * &r + &e1;
-    let message = RqElement::decompress(&CompressedRq::from_data(plaintext));
-    let v = (&t * &r).to_coefficient_repr() + &e2 + &message;
-    return (u.compress(), v.compress());
+    let v = (&t * &r).to_coefficient_repr() + &e2 + message;
+    return (u, v);
 }
 
 pub fn decrypt(sk: SecretKey, c: Ciphertext) -> Plaintext
+{
+    decrypt_generic(&sk, &c)
+}
+
+pub fn decrypt_generic<const DU: u16, const DV: u16>(sk: &SecretKey, c: &GenericCiphertext<DU, DV>) -> Plaintext
 {
     let u = RqVector::decompress(&c.0);
     let v = RqElement::decompress(&c.1);
-    let m = v - &(&sk * &u).to_coefficient_repr();
-    return m.compress().get_data();
+    return decrypt_uncompressed(sk, &u, v).compress().get_data();
+}
+
+/// Returns v - s^T u, i.e. the scaled message plus noise
+pub fn decrypt_uncompressed(sk: &SecretKey, u: &RqVector, v: RqElement) -> RqElement
+{
+    v - &(sk * u).to_coefficient_repr()
+}
+
+/// Re-randomizes a ciphertext without knowing its message, by adding a fresh encryption
+/// of zero to the decompressed (u, v) and compressing the result again.
+///
+/// This adds the noise of a fresh encryption and a second compression error of u and v,
+/// so the noise variance roughly doubles and a re-randomized ciphertext is not distributed
+/// exactly like a fresh one. As the error of compressing v to 3 bits (up to q/16) dominates,
+/// the failure probability grows quickly: as computed by
+/// analysis::rerandomized_failure_probability(), it is about 2^-142.8 for a fresh
+/// ciphertext, 2^-38.5 after one re-randomization, 2^-12.2 after two and 2^-4.0 after
+/// three. Therefore, a ciphertext should be re-randomized at most once.
+pub fn rerandomize(pk: &PublicKey, c: &Ciphertext, enc_seed: Seed) -> Ciphertext
+{
+    let (zero_u, zero_v) = encrypt_uncompressed(pk, &RqElement::get_zero(), enc_seed);
+    let u = RqVector::decompress(&c.0) + &zero_u;
+    let v = RqElement::decompress(&c.1) + &zero_v;
+    return (u.compress(), v.compress());
 }
 
 pub fn key_gen(matrix_seed: Seed, secret_seed: Seed) -> (SecretKey, PublicKey)
+{
+    key_gen_generic(matrix_seed, secret_seed)
+}
+
+/// Generates a key pair whose public key t is compressed to DT bits, which may be
+/// UNCOMPRESSED_BIT_SIZE
+pub fn key_gen_generic<const DT: u16>(matrix_seed: Seed, secret_seed: Seed) -> (SecretKey, GenericPublicKey<DT>)
 {
     let A: RqMatrix = sample_uniform_matrix(&matrix_seed);
     let mut noise = expand_randomness_shake_256(secret_seed);
@@ -77,7 +162,7 @@ fn sample_uniform_zq<T: XofReader>(mut reader: T) -> impl Iterator<Item = ZqElem
     })
 }
 
-fn sample_uniform_matrix(seed: &Seed) -> RqMatrix
+pub fn sample_uniform_matrix(seed: &Seed) -> RqMatrix
 {
     let mut buffer = [0; 34];
     for k in 0..32 {
@@ -97,33 +182,54 @@ fn sample_uniform_matrix(seed: &Seed) -> RqMatrix
     ))
 }
 
-fn sample_centered_binomial_distribution(random: u8) -> ZqElement
+/// Samples from the centered binomial distribution with parameter ETA, i.e. the sum of the
+/// lowest ETA bits of random minus the sum of the next ETA bits
+fn sample_centered_binomial_distribution<const ETA: u16>(random: u32) -> ZqElement
 {
-    let mut value = (random << 4).count_ones() as i16 - (random >> 4).count_ones() as i16;
+    let mask = (1 << ETA) - 1;
+    let mut value = (random & mask).count_ones() as i16 - ((random >> ETA) & mask).count_ones() as i16;
     if value < 0 {
         value += Q as i16;
     }
     return ZqElement::from_perfect(value);
 }
 
-fn sample_error_distribution_vector<T: XofReader>(reader: &mut T) -> RqVector
+pub fn sample_error_distribution_vector<T: XofReader>(reader: &mut T) -> RqVector
+{
+    sample_centered_binomial_vector::<ETA, T>(reader)
+}
+
+pub fn sample_error_distribution_element<T: XofReader>(reader: &mut T) -> RqElement
+{
+    sample_centered_binomial_element::<ETA, T>(reader)
+}
+
+pub fn sample_centered_binomial_vector<const ETA: u16, T: XofReader>(reader: &mut T) -> RqVector
 {
     let data = util::create_array(|_| {
-        sample_error_distribution_element(reader).to_chinese_remainder_repr()
+        sample_centered_binomial_element::<ETA, T>(reader).to_chinese_remainder_repr()
     });
     return RqVector::from(data);
 }
 
-fn sample_error_distribution_element<T: XofReader>(reader: &mut T) -> RqElement
+/// Samples each coefficient from the centered binomial distribution with parameter ETA in
+/// 2..=5, using the bits 2 * ETA * i, ..., 2 * ETA * (i + 1) - 1 of the (little endian) bit
+/// stream of the reader for coefficient i. Hence, this consumes N * ETA / 4 bytes.
+pub fn sample_centered_binomial_element<const ETA: u16, T: XofReader>(reader: &mut T) -> RqElement
 {
-    let mut buffer: [u8; N] = [0; N];
-    reader.read(&mut buffer);
-    return RqElement::from(util::create_array(|i| 
-        sample_centered_binomial_distribution(buffer[i])
-    ));
+    debug_assert!(ETA >= 2 && ETA <= MAX_ETA, "Got eta = {} which is not in range 2..={}", ETA, MAX_ETA);
+    // two more bytes, so that we can always read three bytes starting at the current one
+    let mut buffer: [u8; N * MAX_ETA as usize / 4 + 2] = [0; N * MAX_ETA as usize / 4 + 2];
+    reader.read(&mut buffer[..N * ETA as usize / 4]);
+    return RqElement::from(util::create_array(|i| {
+        let position = 2 * ETA as usize * i;
+        let bytes = &buffer[position / 8..position / 8 + 3];
+        let window = bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16;
+        sample_centered_binomial_distribution::<ETA>(window >> (position % 8))
+    }));
 }
 
-fn expand_randomness_shake_256(seed: Seed) -> sha3::Sha3XofReader
+pub fn expand_randomness_shake_256(seed: Seed) -> sha3::Sha3XofReader
 {
     let mut hasher = sha3::Shake256::default();
     hasher.input(&seed);
@@ -142,6 +248,16 @@ const TEST_SEED: Seed = [
     0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00
 ];
 
+/// TEST_SEED with the first byte replaced by i, to get distinct seeds in the tests of all
+/// modules
+#[cfg(test)]
+pub fn test_seed(i: u8) -> Seed
+{
+    let mut seed = TEST_SEED;
+    seed[0] = i;
+    return seed;
+}
+
 #[test]
 fn test_roundtrip() 
 {
@@ -151,6 +267,161 @@ fn test_roundtrip()
     assert_eq!(TEST_MESSAGE, message);
 }
 
+#[test]
+fn test_uncompressed_public_key()
+{
+    let (sk, pk) = key_gen_generic::<UNCOMPRESSED_BIT_SIZE>(TEST_SEED, TEST_SEED);
+    let (_, compressed_pk) = key_gen(TEST_SEED, TEST_SEED);
+    assert_eq!(public_key_bytes(UNCOMPRESSED_BIT_SIZE), super::io::pk_to_bytes(&pk).len());
+    let recompressed: PublicKey = (RqVector::decompress(&pk.0).compress(), pk.1);
+    assert_eq!(super::io::pk_to_bytes(&compressed_pk), super::io::pk_to_bytes(&recompressed));
+
+    let ciphertext: Ciphertext = encrypt_generic(&pk, TEST_MESSAGE, TEST_SEED);
+    assert_eq!(TEST_MESSAGE, decrypt(sk.clone(), ciphertext));
+
+    let ciphertext: GenericCiphertext<10, 4> = encrypt_generic(&pk, TEST_MESSAGE, TEST_SEED);
+    assert_eq!(ciphertext_bytes(10, 4), super::io::ciphertext_to_bytes(&ciphertext).len());
+    assert_eq!(TEST_MESSAGE, decrypt_generic(&sk, &ciphertext));
+    let decoded: GenericCiphertext<10, 4> = super::io::ciphertext_from_bytes(&super::io::ciphertext_to_bytes(&ciphertext)).unwrap();
+    assert_eq!(TEST_MESSAGE, decrypt_generic(&sk, &decoded));
+}
+
+#[test]
+fn test_rerandomize()
+{
+    let (sk, pk) = key_gen(TEST_SEED, TEST_SEED);
+    let ciphertext = encrypt(&pk, TEST_MESSAGE, TEST_SEED);
+    let rerandomized = rerandomize(&pk, &ciphertext, test_seed(1));
+    assert!(super::io::ciphertext_to_bytes(&ciphertext)[..] != super::io::ciphertext_to_bytes(&rerandomized)[..]);
+    assert_eq!(TEST_MESSAGE, decrypt(sk, rerandomized));
+}
+
+/// Returns the noise of each coefficient of v - s^T u, up to an error of q/2^13, and the
+/// count of wrongly decrypted bits
+#[cfg(test)]
+fn measure_noise(sk: &SecretKey, c: &Ciphertext, plaintext: &Plaintext) -> (Vec<f64>, usize)
+{
+    let u = RqVector::decompress(&c.0);
+    let v = RqElement::decompress(&c.1);
+    let noisy = decrypt_uncompressed(sk, &u, v).compress::<12>();
+    let message = CompressedRq::from_data(*plaintext);
+    let mut noise = Vec::with_capacity(N);
+    let mut errors = 0;
+    for i in 0..N {
+        let centered = (noisy.data[i].data as i32 - 2048 * message.data[i].data as i32 + 2048).rem_euclid(4096) - 2048;
+        noise.push(centered as f64 * Q as f64 / 4096.);
+        if centered.abs() > 1024 {
+            errors += 1;
+        }
+    }
+    return (noise, errors);
+}
+
+#[test]
+fn test_rerandomize_failure_rate()
+{
+    // The observed noise variance and bit error rate after repeatedly re-randomizing
+    // 64 ciphertexts; analysis::rerandomized_noise_distribution() predicts variances of
+    // about 87000, 174000, 261000, ... and bit error rates of about 4 * 10^-46,
+    // 10^-14, 9 * 10^-7, 2 * 10^-4 and 2 * 10^-3 for up to 4 re-randomizations
+    const CIPHERTEXTS: u8 = 64;
+    const ROUNDS: usize = 5;
+    let (sk, pk) = key_gen(TEST_SEED, TEST_SEED);
+    let mut variances = [0.; ROUNDS];
+    let mut errors = [0; ROUNDS];
+    for i in 0..CIPHERTEXTS {
+        let mut plaintext = [0; 32];
+        expand_randomness_shake_256(test_seed(i)).read(&mut plaintext);
+        let mut ciphertext = encrypt(&pk, plaintext, test_seed(i));
+        for round in 0..ROUNDS {
+            if round > 0 {
+                let mut seed = test_seed(i);
+                seed[1] = round as u8;
+                ciphertext = rerandomize(&pk, &ciphertext, seed);
+            }
+            let (noise, wrong_bits) = measure_noise(&sk, &ciphertext, &plaintext);
+            variances[round] += noise.iter().map(|x| x * x).sum::<f64>() / (N * CIPHERTEXTS as usize) as f64;
+            errors[round] += wrong_bits;
+        }
+    }
+    for round in 0..ROUNDS {
+        let expected = super::analysis::rerandomized_noise_distribution(&super::params::IMPLEMENTED, round).variance();
+        assert!((variances[round] / expected - 1.).abs() < 0.1,
+            "noise variance by count of re-randomizations: {:?}", variances);
+    }
+    assert!(errors[0] == 0 && errors[1] == 0 && errors[ROUNDS - 1] > 0,
+        "wrong bits (of {}) by count of re-randomizations: {:?}", N * CIPHERTEXTS as usize, errors);
+}
+
+#[cfg(test)]
+fn sample_statistics<const ETA: u16>() -> (f64, f64, i64)
+{
+    let mut reader = expand_randomness_shake_256(test_seed(ETA as u8));
+    let mut sum = 0;
+    let mut square_sum = 0;
+    let mut max = 0;
+    const ELEMENTS: usize = 64;
+    for _ in 0..ELEMENTS {
+        let element = sample_centered_binomial_element::<ETA, _>(&mut reader);
+        for x in super::analysis::centered_coefficients(&element).iter() {
+            sum += x;
+            square_sum += x * x;
+            max = std::cmp::max(max, x.abs());
+        }
+    }
+    let count = (ELEMENTS * N) as f64;
+    let mean = sum as f64 / count;
+    return (mean, square_sum as f64 / count - mean * mean, max);
+}
+
+#[test]
+fn test_centered_binomial_statistics()
+{
+    // the variance is eta/2 and with 16384 samples, the standard deviation of the
+    // sample mean is below 0.013 and the one of the sample variance below 2%
+    let statistics = [sample_statistics::<2>(), sample_statistics::<3>(), sample_statistics::<4>(), sample_statistics::<5>()];
+    for (i, (mean, variance, max)) in statistics.iter().enumerate() {
+        let eta = i as i64 + 2;
+        assert!(mean.abs() < 0.06, "eta = {}: mean {}", eta, mean);
+        assert!((variance / (eta as f64 / 2.) - 1.).abs() < 0.08, "eta = {}: variance {}", eta, variance);
+        assert_eq!(eta, *max);
+    }
+}
+
+#[test]
+fn test_centered_binomial_consumption()
+{
+    // for eta = 4, each byte yields one coefficient as the difference of the popcounts of
+    // its lower and upper nibble
+    let mut reader = expand_randomness_shake_256(TEST_SEED);
+    let element = sample_centered_binomial_element::<4, _>(&mut reader);
+    let mut expected_reader = expand_randomness_shake_256(TEST_SEED);
+    let mut bytes = [0; N];
+    expected_reader.read(&mut bytes);
+    let expected = RqElement::from(util::create_array(|i|
+        ZqElement::from((bytes[i] & 0xF).count_ones() as i16 - (bytes[i] >> 4).count_ones() as i16)
+    ));
+    assert!(expected == element);
+
+    // afterwards, both readers are at the same position
+    let mut next = [0; 8];
+    let mut expected_next = [0; 8];
+    reader.read(&mut next);
+    expected_reader.read(&mut expected_next);
+    assert_eq!(expected_next, next);
+
+    // for eta = 3 and eta = 5, this consumes 192 and 320 bytes
+    let mut reader = expand_randomness_shake_256(TEST_SEED);
+    sample_centered_binomial_element::<3, _>(&mut reader);
+    sample_centered_binomial_element::<5, _>(&mut reader);
+    reader.read(&mut next);
+    let mut expected_reader = expand_randomness_shake_256(TEST_SEED);
+    let mut skipped = [0; 512];
+    expected_reader.read(&mut skipped);
+    expected_reader.read(&mut expected_next);
+    assert_eq!(expected_next, next);
+}
+
 #[bench]
 fn benchmark_all(bencher: &mut test::Bencher) 
 {
@@ -0,0 +1,69 @@
+//! The Kyber implementation as a library, which is used by the command line interface in
+//! main.rs and by the fuzz targets in fuzz/
+
+#![allow(non_snake_case)]
+#![feature(test)]
+#![feature(const_generics)]
+#![feature(try_trait)]
+
+extern crate test;
+extern crate sha3;
+
+#[macro_use]
+pub mod util;
+#[cfg(target_feature = "avx2")]
+pub mod avx_util;
+
+pub mod encoding;
+
+pub mod ring_params;
+pub mod zq;
+pub mod ring;
+pub mod generic_ring;
+
+#[cfg(not(target_feature = "avx2"))]
+#[cfg_attr(feature = "coefficient_domain", allow(unused))]
+pub mod ref_r;
+pub mod coeff_r;
+#[cfg(test)]
+mod ring_tests;
+pub mod rqvec;
+
+#[cfg(target_feature = "avx2")]
+pub mod avx_zq;
+// avx implementation does not work in compatibility mode
+#[cfg(all(target_feature = "avx2", not(feature = "compatibility")))]
+#[cfg_attr(feature = "coefficient_domain", allow(unused))]
+pub mod avx_r;
+
+pub mod kyber;
+pub mod mkem;
+pub mod kem;
+pub mod x25519;
+pub mod hybrid;
+pub mod hkdf;
+pub mod chacha20poly1305;
+pub mod hpke;
+pub mod container;
+pub mod der;
+pub mod pem;
+pub mod json;
+pub mod cbor;
+pub mod jwk;
+pub mod cose;
+pub mod fingerprint;
+pub mod scrypt;
+pub mod keystore;
+pub mod ake;
+pub mod channel;
+pub mod noise;
+pub mod homomorphic;
+pub mod params;
+pub mod analysis;
+pub mod security;
+pub mod toy;
+
+pub mod io;
+#[cfg(any(test, feature = "fuzzing"))]
+pub mod fuzzing;
+pub mod ref_impl_compat;
@@ -1,119 +1,662 @@
 #![allow(non_snake_case)]
-#![feature(test)]
 #![feature(const_generics)]
-#![feature(try_trait)]
 
-extern crate test;
-extern crate sha3;
+use crystals_kyber::*;
+use crystals_kyber::kyber::*;
+use crystals_kyber::ring_params::{ RingParams, KyberParams, Toy16Params, Toy64Params };
 
-#[macro_use]
-mod util;
-#[cfg(target_feature = "avx2")]
-mod avx_util;
-
-mod encoding;
+use std::io::Write;
+use std::time::SystemTime;
 
-mod zq;
-mod ring;
+/// Decrypts a legacy base64 encoded ciphertext with a legacy base64 encoded secret key
+#[allow(unused)]
+fn cli_decrypt(key: &str, ciphertext: &str) -> Result<String, String>
+{
+    let ciphertext: Ciphertext = io::read_ciphertext(ciphertext).map_err(|error| format!("Invalid ciphertext: {}", error))?;
+    // this also accepts the secret keys of Kyber.KEM that are printed by gen
+    let sk: SecretKey = io::read_sk(key).map_err(|error| format!("Invalid secret key: {}", error))?;
+    let message: Plaintext = decrypt(sk, ciphertext);
+    return Ok(io::write_message(&message));
+}
 
-#[cfg(not(target_feature = "avx2"))]
-mod ref_r;
-mod rqvec;
+fn cli_key_gen() -> kem::KemSecretSeed
+{
+    return (util::random_seed(), util::random_seed());
+}
 
-#[cfg(target_feature = "avx2")]
-mod avx_zq;
-// avx implementation does not work in compatibility mode
-#[cfg(all(target_feature = "avx2", not(feature = "compatibility")))]
-mod avx_r;
+/// The positional arguments and the values of the --in, --out, --format, --keyring,
+/// --passphrase-file, --secret and --trace options
+struct CliOptions
+{
+    positional: Vec<String>,
+    input: Option<String>,
+    output: Option<String>,
+    format: Option<String>,
+    keyring: Option<String>,
+    passphrase_file: Option<String>,
+    secret: bool,
+    trace: bool
+}
 
-mod kyber;
+fn parse_options(args: &[String]) -> Result<CliOptions, String>
+{
+    let mut result = CliOptions {
+        positional: Vec::new(), input: None, output: None, format: None, keyring: None, passphrase_file: None, secret: false, trace: false
+    };
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--in" => result.input = Some(iter.next().ok_or("Option --in requires a file name")?.clone()),
+            "--out" => result.output = Some(iter.next().ok_or("Option --out requires a file name")?.clone()),
+            "--format" => result.format = Some(iter.next().ok_or("Option --format requires a key format")?.clone()),
+            "--keyring" => result.keyring = Some(iter.next().ok_or("Option --keyring requires a directory")?.clone()),
+            "--passphrase-file" => result.passphrase_file = Some(iter.next().ok_or("Option --passphrase-file requires a file name")?.clone()),
+            "--secret" => result.secret = true,
+            "--trace" => result.trace = true,
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+            _ => result.positional.push(arg.clone())
+        }
+    }
+    return Ok(result);
+}
 
-mod io;
-mod ref_impl_compat;
+/// Opens the given file, or stdin if no file or "-" is given
+fn open_input(path: Option<&str>) -> std::io::Result<Box<dyn std::io::Read>>
+{
+    match path {
+        None | Some("-") => Ok(Box::new(std::io::stdin())),
+        Some(path) => Ok(Box::new(std::io::BufReader::new(std::fs::File::open(path)?)))
+    }
+}
 
-use kyber::*;
+/// Opens the given file, or stdout if no file or "-" is given
+fn open_output(path: Option<&str>) -> std::io::Result<Box<dyn std::io::Write>>
+{
+    match path {
+        None | Some("-") => Ok(Box::new(std::io::stdout())),
+        Some(path) => Ok(Box::new(std::io::BufWriter::new(std::fs::File::create(path)?)))
+    }
+}
 
-use sha3::digest::{ ExtendableOutput, Input, XofReader };
+fn read_text(path: Option<&str>) -> Result<String, String>
+{
+    let mut result = String::new();
+    open_input(path).and_then(|mut input| std::io::Read::read_to_string(&mut input, &mut result))
+        .map_err(|error| format!("Could not read {}: {}", path.unwrap_or("stdin"), error))?;
+    return Ok(result);
+}
 
-use std::time::SystemTime;
+/// The directory given by --keyring, the environment variable KYBER_KEYRING or ~/.kyber/keyring
+fn keyring_dir(options: &CliOptions) -> std::path::PathBuf
+{
+    if let Some(dir) = &options.keyring {
+        return std::path::PathBuf::from(dir);
+    }
+    if let Some(dir) = std::env::var_os("KYBER_KEYRING") {
+        return std::path::PathBuf::from(dir);
+    }
+    let home = std::env::var_os("HOME").or(std::env::var_os("USERPROFILE")).unwrap_or_default();
+    return std::path::Path::new(&home).join(".kyber").join("keyring");
+}
 
-fn time_seed() -> Seed
+fn open_keystore(options: &CliOptions) -> keystore::Keystore
 {
-    let nanos: u32 = (SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos() % 1073741824) as u32;
-    let mut hasher = sha3::Shake256::default();
-    let data: [u8; 4] = util::create_array(|i| ((nanos >> (i * 8)) & 0xFF) as u8);
-    hasher.input(&data);
-    let mut result = [0; 32];
-    hasher.xof_result().read(&mut result);
-    return result;
+    keystore::Keystore::open(keyring_dir(options))
 }
 
-fn cli_encrypt(key: &str, message: &str) -> String
+/// Returns the entry with the given name or fingerprint from the keystore
+fn find_keystore_entry(options: &CliOptions, name: &str) -> Result<keystore::KeyEntry, String>
 {
-    let pk: PublicKey = io::read_pk(key);
-    let message: Plaintext = io::read_message(message);
-    let ciphertext = encrypt(&pk, message, time_seed());
-    return io::write_ciphertext(&ciphertext);
+    let keystore = open_keystore(options);
+    match keystore.find(name) {
+        Err(keystore::KeystoreError::NotFound(_)) => Err(format!("{} is neither a key file nor the name or fingerprint of a key in the keystore {}",
+            name, keystore.dir().display())),
+        result => result.map_err(|error| error.to_string())
+    }
 }
 
-fn cli_decrypt(key: &str, ciphertext: &str) -> String
+/// The passphrase is read from the first line of the --passphrase-file, the environment
+/// variable KYBER_PASSPHRASE or, if neither is given, from the terminal without echo after
+/// a prompt. A new passphrase has to be entered twice.
+fn read_passphrase(options: &CliOptions, prompt: &str, new: bool) -> Result<Vec<u8>, String>
 {
-    let ciphertext: Ciphertext = io::read_ciphertext(ciphertext);
-    let sk: SecretKey = io::read_sk(key);
-    let message: Plaintext = decrypt(sk, ciphertext);
-    return io::write_message(&message);
+    let passphrase = if let Some(path) = &options.passphrase_file {
+        read_text(Some(path))?.lines().next().unwrap_or("").to_owned()
+    } else if let Ok(passphrase) = std::env::var("KYBER_PASSPHRASE") {
+        passphrase
+    } else {
+        let read_line = |prompt: &str| {
+            match util::read_hidden_line(prompt) {
+                Ok(Some(line)) => Ok(line),
+                Ok(None) => Err("stdin is not a terminal, give the passphrase with --passphrase-file or KYBER_PASSPHRASE".to_owned()),
+                Err(error) => Err(format!("Could not read passphrase: {}", error))
+            }
+        };
+        let passphrase = read_line(prompt)?;
+        if new && read_line("Repeat the passphrase")? != passphrase {
+            return Err("The passphrases do not match".to_owned());
+        }
+        passphrase
+    };
+    if new && passphrase.is_empty() {
+        return Err("The passphrase must not be empty".to_owned());
+    }
+    return Ok(passphrase.into_bytes());
 }
 
-fn cli_key_gen() -> (String, String)
+/// Reads the public key from the given file, which may also be a secret key file, or
+/// from the keystore
+fn read_public_key_file(options: &CliOptions, name: &str) -> Result<PublicKey, String>
 {
-    let pk_seed = time_seed();
-    let mut sk_seed = time_seed();
-    sk_seed[0] ^= 0xF;
-    let (sk, pk) = key_gen(pk_seed, sk_seed);
-    return (io::write_sk(&sk), io::write_pk(&pk));
+    if !std::path::Path::new(name).is_file() && name != "-" {
+        return Ok(find_keystore_entry(options, name)?.public_key);
+    }
+    let text = read_text(Some(name))?;
+    // a secret key file can be used to encrypt to its own public key
+    pem::read_public_key(&text).or(pem::read_secret_key(&text).map(|sk| sk.1)).ok_or(format!("{} does not contain a Kyber public key", name))
 }
 
-fn main() 
+/// Reads the secret key and, if it is stored in seed form, its seed from the given file
+/// or from the keystore, which requires the passphrase
+fn read_secret_key_and_seed(options: &CliOptions, name: &str) -> Result<(kem::KemSecretKey, Option<kem::KemSecretSeed>), String>
 {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() < 2{
-        println!("Usage: crystals_kyber.exe command parameters...");
-        return;
+    if !std::path::Path::new(name).is_file() && name != "-" {
+        let entry = find_keystore_entry(options, name)?;
+        if !entry.has_secret_key() {
+            return Err(keystore::KeystoreError::NoSecretKey(entry.name).to_string());
+        }
+        let passphrase = read_passphrase(options, &format!("Passphrase for key {}", entry.name), false)?;
+        return entry.decrypt_secret_key(&passphrase).map_err(|error| error.to_string());
     }
-    match args[1].as_str() {
-        "enc" => {
-            if args.len() < 4 {
-                println!("Usage: crystals_kyber.exe enc public_key plaintext");
-                println!("  where message are 32 base64 encoded bytes, i.e. 43 characters of A-Z, a-z, 0-9, +, / followed by =");
-                println!("  longer messages are also allowed, then only the prefix will be used");
-                return;
-            }
-            let encryption = cli_encrypt(&args[2], &args[3]);
-            println!("");
-            println!("Ciphertext is {}", encryption);
-            println!("");
+    let text = read_text(Some(name))?;
+    let sk = pem::read_secret_key(&text).ok_or(format!("{} does not contain a Kyber secret key", name))?;
+    return Ok((sk, pem::read_secret_seed(&text)));
+}
+
+fn read_secret_key_file(options: &CliOptions, name: &str) -> Result<kem::KemSecretKey, String>
+{
+    read_secret_key_and_seed(options, name).map(|(sk, _)| sk)
+}
+
+fn print_fingerprint(pk: &PublicKey)
+{
+    let key_fingerprint = fingerprint::fingerprint(pk);
+    eprintln!("Fingerprint {}", fingerprint::to_hex(&key_fingerprint));
+    eprintln!("         or {}", fingerprint::to_words(&key_fingerprint));
+    eprintln!("     Key ID {}", fingerprint::short_id(&key_fingerprint));
+}
+
+/// Prints the fingerprint of a public or secret key
+fn cli_fingerprint(options: &CliOptions) -> Result<(), String>
+{
+    let pk = read_public_key_file(options, &options.positional[0])?;
+    let key_fingerprint = fingerprint::fingerprint(&pk);
+    write_output(options.output.as_ref().map(String::as_str), &format!("{}\n{}\n{}\n",
+        fingerprint::to_hex(&key_fingerprint), fingerprint::to_words(&key_fingerprint), fingerprint::short_id(&key_fingerprint)))
+}
+
+fn write_output(path: Option<&str>, text: &str) -> Result<(), String>
+{
+    open_output(path).and_then(|mut output| { output.write_all(text.as_bytes())?; output.flush() })
+        .map_err(|error| format!("Could not write {}: {}", path.unwrap_or("stdout"), error))
+}
+
+/// Writes BASE.pub and BASE.key, or both keys to stdout if the base name is "-"
+fn cli_gen(options: &CliOptions) -> Result<(), String>
+{
+    let seed = cli_key_gen();
+    let sk = kem::expand_secret_seed(&seed);
+    let pk_pem = public_key_pem(&sk.1, options.format.as_ref().map(String::as_str))?;
+    let sk_pem = secret_key_pem(&sk, Some(&seed), options.format.as_ref().map(String::as_str))?;
+    match options.output.as_ref().map(String::as_str) {
+        None | Some("-") => {
+            write_output(None, &format!("{}{}", pk_pem, sk_pem))?;
+            print_fingerprint(&sk.1);
+            Ok(())
         },
-        "dec" => {
-            if args.len() < 4 {
-                println!("Usage: crystals_kyber.exe dec secret_key ciphertext");
-                return;
+        Some(base) => {
+            let pk_path = format!("{}.pub", base);
+            let sk_path = format!("{}.key", base);
+            write_secret_output(&sk_path, &sk_pem)?;
+            write_output(Some(&pk_path), &pk_pem)?;
+            eprintln!("Wrote public key to {} and secret key to {}", pk_path, sk_path);
+            print_fingerprint(&sk.1);
+            Ok(())
+        }
+    }
+}
+
+/// Encodes the public key in the given format; for the seed format, this is the same as
+/// for the kyber format
+fn public_key_pem(pk: &PublicKey, format: Option<&str>) -> Result<String, String>
+{
+    match format {
+        None | Some("kyber") | Some("seed") => Ok(pem::write_public_key(pk)),
+        Some("pkcs8") => Ok(pem::encode(pem::SPKI_LABEL, &der::pk_to_spki(pk))),
+        Some(format) => Err(format!("Key format must be one of kyber, seed, pkcs8, got {}", format))
+    }
+}
+
+/// Encodes the secret key in the given format; the seed format is only possible
+/// if the seed is known
+fn secret_key_pem(sk: &kem::KemSecretKey, seed: Option<&kem::KemSecretSeed>, format: Option<&str>) -> Result<String, String>
+{
+    match format {
+        None | Some("kyber") => Ok(pem::write_secret_key(sk)),
+        Some("pkcs8") => Ok(pem::encode(pem::PKCS8_LABEL, &der::sk_to_pkcs8(sk))),
+        Some("seed") => seed.map(pem::write_secret_seed)
+            .ok_or("The secret key is stored in expanded form, which does not contain the seed it was generated from".to_owned()),
+        Some(format) => Err(format!("Key format must be one of kyber, seed, pkcs8, got {}", format))
+    }
+}
+
+fn write_secret_output(path: &str, text: &str) -> Result<(), String>
+{
+    if path == "-" {
+        return write_output(None, text);
+    }
+    util::create_secret_file(path).and_then(|mut file| file.write_all(text.as_bytes()))
+        .map_err(|error| format!("Could not write {}: {}", path, error))
+}
+
+/// Converts a secret key file into the format given by --format
+fn cli_convert(options: &CliOptions) -> Result<(), String>
+{
+    let (sk, seed) = read_secret_key_and_seed(options, &options.positional[0])?;
+    let sk_pem = secret_key_pem(&sk, seed.as_ref(), options.format.as_ref().map(String::as_str))?;
+    write_secret_output(options.output.as_ref().map(String::as_str).unwrap_or("-"), &sk_pem)
+}
+
+fn unix_time() -> u64
+{
+    SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0)
+}
+
+fn cli_keys_list(options: &CliOptions) -> Result<(), String>
+{
+    let mut text = String::new();
+    let (entries, errors) = open_keystore(options).list().map_err(|error| error.to_string())?;
+    for error in errors {
+        eprintln!("Skipped: {}", error);
+    }
+    for entry in entries {
+        text.push_str(&format!("{:<24} {} {:<6} {} parameter set {}\n", entry.name, fingerprint::short_id(&entry.fingerprint),
+            if entry.has_secret_key() { "secret" } else { "public" }, keystore::format_timestamp(entry.created), entry.parameter_set));
+    }
+    write_output(options.output.as_ref().map(String::as_str), &text)
+}
+
+/// Imports a public or secret key file; secret keys are encrypted under a new passphrase
+fn cli_keys_import(options: &CliOptions) -> Result<(), String>
+{
+    let (name, path) = match &options.positional[1..] {
+        [name, path] => (name, path),
+        _ => return Err("Usage: crystals_kyber.exe keys import name key_file".to_owned())
+    };
+    if !keystore::is_valid_name(name) {
+        return Err(keystore::KeystoreError::InvalidName(name.clone()).to_string());
+    }
+    let text = read_text(Some(path))?;
+    let keystore = open_keystore(options);
+    let entry = if let Some(sk) = pem::read_secret_key(&text) {
+        let seed = pem::read_secret_seed(&text);
+        let passphrase = read_passphrase(options, &format!("New passphrase for key {}", name), true)?;
+        keystore.add_secret_key(name, &sk, seed.as_ref(), &passphrase, keystore::DEFAULT_SCRYPT_PARAMS, util::random_seed(), unix_time())
+    } else if let Some(pk) = pem::read_public_key(&text) {
+        keystore.add_public_key(name, &pk, unix_time())
+    } else {
+        return Err(format!("{} does not contain a Kyber key", path));
+    }.map_err(|error| error.to_string())?;
+    eprintln!("Imported {} key {}", if entry.has_secret_key() { "secret" } else { "public" }, entry.name);
+    print_fingerprint(&entry.public_key);
+    Ok(())
+}
+
+/// Exports the public key or, with --secret, the decrypted secret key in the format given by --format
+fn cli_keys_export(options: &CliOptions) -> Result<(), String>
+{
+    let name = options.positional.get(1).ok_or("Usage: crystals_kyber.exe keys export name [--secret] [--format kyber|seed|pkcs8] [--out file]")?;
+    let format = options.format.as_ref().map(String::as_str);
+    if options.secret {
+        let (sk, seed) = read_secret_key_and_seed(options, name)?;
+        return write_secret_output(options.output.as_ref().map(String::as_str).unwrap_or("-"), &secret_key_pem(&sk, seed.as_ref(), format)?);
+    }
+    let entry = find_keystore_entry(options, name)?;
+    write_output(options.output.as_ref().map(String::as_str), &public_key_pem(&entry.public_key, format)?)
+}
+
+fn cli_keys_delete(options: &CliOptions) -> Result<(), String>
+{
+    let name = options.positional.get(1).ok_or("Usage: crystals_kyber.exe keys delete name")?;
+    open_keystore(options).delete(name).map_err(|error| error.to_string())?;
+    eprintln!("Deleted key {}", name);
+    Ok(())
+}
+
+fn cli_keys(options: &CliOptions) -> Result<(), String>
+{
+    match options.positional[0].as_str() {
+        "list" => cli_keys_list(options),
+        "import" => cli_keys_import(options),
+        "export" => cli_keys_export(options),
+        "delete" => cli_keys_delete(options),
+        command => Err(format!("Keys command must be one of list, import, export, delete, got {}", command))
+    }
+}
+
+/// Prints the failure probability of each parameter set and optionally compares the
+/// noise distribution with the one observed in the given count of encryptions
+fn cli_params_failure(options: &CliOptions) -> Result<(), String>
+{
+    println!("Decryption failure probability per ciphertext:");
+    for params in params::PARAMETER_SETS.iter() {
+        println!("  {:<12} 2^{:.1}", params.name, analysis::failure_probability(params).log2());
+    }
+    if let Some(count) = options.positional.get(1) {
+        let count: u64 = count.parse().map_err(|_| format!("Expected a count of ciphertexts, got {}", count))?;
+        let params = params::IMPLEMENTED;
+        let distribution = analysis::noise_distribution(&params);
+        let result = analysis::monte_carlo(count, util::random_seed());
+        println!("Monte-Carlo check of {} with {} ciphertexts: {} failures", params.name, result.ciphertexts, result.failures);
+        println!("  noise variance: computed {:.1}, observed {:.1}", distribution.variance(), result.variance());
+        for divisor in &[32, 16, 12, 8] {
+            let threshold = params.q as i64 / divisor;
+            println!("  P(|noise| > q/{:<2}): computed {:.3e}, observed {:.3e}", divisor,
+                distribution.tail_probability(threshold), result.tail_rate(threshold));
+        }
+    }
+    Ok(())
+}
+
+/// Prints the core-SVP hardness and the sizes of the given or all parameter sets
+fn cli_params_estimate(options: &CliOptions) -> Result<(), String>
+{
+    let parameter_sets = match options.positional.get(1) {
+        Some(name) => vec![params::find(name).ok_or(format!("Unknown parameter set {}", name))?],
+        None => params::PARAMETER_SETS.to_vec()
+    };
+    for params in parameter_sets {
+        println!("{}: n = {}, k = {}, q = {}, eta = {}, (dt, du, dv) = ({}, {}, {})", params.name,
+            params.n, params.k, params.q, params.eta, params.dt, params.du, params.dv);
+        println!("  public key {} bytes, ciphertext {} bytes, secret key {} bytes",
+            params.public_key_bytes(), params.ciphertext_bytes(), params.secret_key_bytes());
+        let estimates = security::estimate(&params);
+        for estimate in &estimates {
+            println!("  {:?} attack ({:?}): block size {}, {} samples, {:.1} bits", estimate.attack,
+                estimate.model, estimate.block_size, estimate.samples, estimate.bits);
+        }
+        println!("  core-SVP hardness: {:.1} bits classical, {:.1} bits quantum",
+            security::security_bits(&estimates, security::CostModel::Classical),
+            security::security_bits(&estimates, security::CostModel::Quantum));
+    }
+    Ok(())
+}
+
+fn cli_params(options: &CliOptions) -> Result<(), String>
+{
+    match options.positional[0].as_str() {
+        "failure" => cli_params_failure(options),
+        "estimate" => cli_params_estimate(options),
+        command => Err(format!("Params command must be one of failure, estimate, got {}", command))
+    }
+}
+
+/// Runs key generation, encryption and decryption of toy.rs with the given parameter set
+/// and a random or the given message, optionally printing all intermediate values
+fn cli_toy(options: &CliOptions) -> Result<(), String>
+{
+    let params = params::find(&options.positional[0]).ok_or(format!("Unknown parameter set {}", options.positional[0]))?;
+    let message = match options.positional.get(1) {
+        Some(hex) if hex.len() == 2 * toy::message_bytes(&params) && hex.chars().all(|c| c.is_ascii_hexdigit()) => util::from_hex(hex),
+        Some(_) => return Err(format!("The message must consist of {} hex digits", 2 * toy::message_bytes(&params))),
+        None => util::random_seed()[..toy::message_bytes(&params)].to_vec()
+    };
+    let decrypted = match (params.q, params.n) {
+        (KyberParams::Q, KyberParams::N) => run_toy::<KyberParams>(options, &params, &message),
+        (Toy16Params::Q, Toy16Params::N) => run_toy::<Toy16Params>(options, &params, &message),
+        (Toy64Params::Q, Toy64Params::N) => run_toy::<Toy64Params>(options, &params, &message),
+        (q, n) => return Err(format!("There are no ring parameters for q = {} and n = {}", q, n))
+    };
+    println!("{}: message {}, decrypted {}", params.name, util::to_hex(&message), util::to_hex(&decrypted));
+    if decrypted != message {
+        println!("Decryption failed, which happens with probability about 2^{:.1} for these parameters",
+            analysis::failure_probability(&params).log2());
+    }
+    Ok(())
+}
+
+/// Returns the decrypted message
+fn run_toy<P: RingParams>(options: &CliOptions, params: &params::ParameterSet, message: &[u8]) -> Vec<u8>
+{
+    let mut stdout = std::io::stdout();
+    let mut trace = if options.trace { toy::Trace::new(&mut stdout) } else { toy::Trace::disabled() };
+    let (sk, pk) = toy::key_gen::<P>(params, util::random_seed(), util::random_seed(), &mut trace);
+    let ciphertext = toy::encrypt::<P>(params, &pk, message, util::random_seed(), &mut trace);
+    return toy::decrypt(params, &sk, &ciphertext, &mut trace);
+}
+
+/// Encrypts the base64 encoded message given as argument or read from the input
+fn cli_enc(options: &CliOptions) -> Result<(), String>
+{
+    let pk = read_public_key_file(options, &options.positional[0])?;
+    let message = match options.positional.get(1) {
+        Some(message) => message.clone(),
+        None => read_text(options.input.as_ref().map(String::as_str))?
+    };
+    let message: Plaintext = io::read_message(message.trim()).map_err(|error| format!("Invalid message: {}, use encrypt-file for other data", error))?;
+    let ciphertext = encrypt(&pk, message, util::random_seed());
+    write_output(options.output.as_ref().map(String::as_str), &format!("{}\n", io::write_ciphertext(&ciphertext)))
+}
+
+/// Decrypts the base64 encoded ciphertext given as argument or read from the input
+fn cli_dec(options: &CliOptions) -> Result<(), String>
+{
+    let sk = read_secret_key_file(options, &options.positional[0])?;
+    let ciphertext = match options.positional.get(1) {
+        Some(ciphertext) => ciphertext.clone(),
+        None => read_text(options.input.as_ref().map(String::as_str))?
+    };
+    let ciphertext: Ciphertext = io::validate_ciphertext_base64(ciphertext.trim())
+        .map_err(|error| format!("Invalid ciphertext: {}", error))?;
+    let message: Plaintext = decrypt(sk.0, ciphertext);
+    write_output(options.output.as_ref().map(String::as_str), &format!("{}\n", io::write_message(&message)))
+}
+
+/// The input and output of the file commands can be given positionally or by --in/--out
+fn file_arguments(options: &CliOptions) -> (Option<&str>, Option<&str>)
+{
+    let input = options.positional.get(1).or(options.input.as_ref()).map(String::as_str);
+    let output = options.positional.get(2).or(options.output.as_ref()).map(String::as_str);
+    return (input, output);
+}
+
+fn cli_encrypt_file(options: &CliOptions) -> Result<(), String>
+{
+    let pk = read_public_key_file(options, &options.positional[0])?;
+    let (input_path, output_path) = file_arguments(options);
+    let mut input = open_input(input_path).map_err(|error| format!("Could not open input: {}", error))?;
+    let mut output = open_output(output_path).map_err(|error| format!("Could not open output: {}", error))?;
+    container::encrypt_stream(&pk, util::random_seed(), &mut input, &mut output)
+        .map_err(|error| format!("Encryption failed: {}", error))
+}
+
+fn cli_decrypt_file(options: &CliOptions) -> Result<(), String>
+{
+    let sk = read_secret_key_file(options, &options.positional[0])?;
+    let (input_path, output_path) = file_arguments(options);
+    let mut input = open_input(input_path).map_err(|error| format!("Could not open input: {}", error))?;
+    let mut output = open_output(output_path).map_err(|error| format!("Could not open output: {}", error))?;
+    let result = container::decrypt_stream(&sk, &mut input, &mut output);
+    if result.is_err() {
+        // do not leave unauthenticated data behind; this is not possible when writing to stdout
+        drop(output);
+        if let Some(path) = output_path.filter(|path| *path != "-") {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+    result.map_err(|error| format!("Decryption failed: {}", error))
+}
+
+/// Forwards stdin to the peer and the data of the peer to stdout, until the peer closes
+/// the channel. Then our side is closed as well, even if stdin is still open.
+fn run_channel(stream: std::net::TcpStream, keys: channel::ChannelKeys) -> Result<(), String>
+{
+    let reader = stream.try_clone().map_err(|error| format!("Could not use the connection: {}", error))?;
+    let (mut receiver, sender) = keys.split(reader, stream);
+    let sender = std::sync::Arc::new(std::sync::Mutex::new(sender));
+    let stdin_sender = sender.clone();
+    std::thread::spawn(move || {
+        let mut buffer = vec![0; channel::MAX_RECORD_SIZE];
+        let mut stdin = std::io::stdin();
+        while let Ok(count) = std::io::Read::read(&mut stdin, &mut buffer) {
+            if count == 0 || stdin_sender.lock().unwrap().send(&buffer[..count]).is_err() {
+                break;
             }
-            let decryption = cli_decrypt(&args[2], &args[3]);
-            println!("");
-            println!("Plaintext is {}", decryption);
-            println!("");
+        }
+        let _ = stdin_sender.lock().unwrap().close();
+    });
+    let mut stdout = std::io::stdout();
+    while let Some(data) = receiver.receive().map_err(|error| format!("Channel error: {}", error))? {
+        stdout.write_all(&data).and_then(|_| stdout.flush()).map_err(|error| format!("Could not write stdout: {}", error))?;
+    }
+    let _ = sender.lock().unwrap().close();
+    Ok(())
+}
+
+fn print_channel_established(peer_pk: &PublicKey)
+{
+    eprintln!("Secure channel established with the owner of the key {}", fingerprint::short_id(&fingerprint::fingerprint(peer_pk)));
+}
+
+/// Waits for one connection on the given address and authenticates the client by its public key
+fn cli_serve(options: &CliOptions) -> Result<(), String>
+{
+    if options.positional.len() != 3 {
+        return Err("Usage: crystals_kyber.exe serve address secret_key_file client_public_key_file".to_owned());
+    }
+    let sk = read_secret_key_file(options, &options.positional[1])?;
+    let client_pk = read_public_key_file(options, &options.positional[2])?;
+    let listener = std::net::TcpListener::bind(&options.positional[0])
+        .map_err(|error| format!("Could not listen on {}: {}", options.positional[0], error))?;
+    eprintln!("Listening on {}", listener.local_addr().map(|address| address.to_string()).unwrap_or_default());
+    let (mut stream, address) = listener.accept().map_err(|error| format!("Could not accept a connection: {}", error))?;
+    let keys = channel::server_handshake(&mut stream, &sk, &client_pk, util::random_seed())
+        .map_err(|error| format!("Handshake with {} failed: {}", address, error))?;
+    print_channel_established(&client_pk);
+    run_channel(stream, keys)
+}
+
+fn cli_connect(options: &CliOptions) -> Result<(), String>
+{
+    if options.positional.len() != 3 {
+        return Err("Usage: crystals_kyber.exe connect address secret_key_file server_public_key_file".to_owned());
+    }
+    let sk = read_secret_key_file(options, &options.positional[1])?;
+    let server_pk = read_public_key_file(options, &options.positional[2])?;
+    let mut stream = std::net::TcpStream::connect(&options.positional[0])
+        .map_err(|error| format!("Could not connect to {}: {}", options.positional[0], error))?;
+    let keys = channel::client_handshake(&mut stream, &sk, &server_pk, util::random_seed())
+        .map_err(|error| format!("Handshake failed: {}", error))?;
+    print_channel_established(&server_pk);
+    run_channel(stream, keys)
+}
+
+fn print_usage(command: &str)
+{
+    match command {
+        "enc" => {
+            eprintln!("Usage: crystals_kyber.exe enc public_key_file [plaintext] [--in file] [--out file]");
+            eprintln!("  where message are 32 base64 encoded bytes, i.e. 43 characters of A-Z, a-z, 0-9, +, / followed by =");
+            eprintln!("  to encrypt longer data, use encrypt-file instead");
+            eprintln!("  if no plaintext is given, it is read from the input file or stdin");
         },
+        "dec" => eprintln!("Usage: crystals_kyber.exe dec secret_key_file [ciphertext] [--in file] [--out file]"),
+        "encrypt-file" => eprintln!("Usage: crystals_kyber.exe encrypt-file public_key_file [input_file] [output_file]"),
+        "decrypt-file" => eprintln!("Usage: crystals_kyber.exe decrypt-file secret_key_file [input_file] [output_file]"),
         "gen" => {
-            let (sk, pk) = cli_key_gen();
-            println!("");
-            println!("Public key is {}", pk);
-
-            println!("");
-            println!("Secret key is {}", sk);
-            println!("");
+            eprintln!("Usage: crystals_kyber.exe gen [--out base_name] [--format kyber|seed|pkcs8]");
+            eprintln!("  writes base_name.pub and base_name.key, pkcs8 stores the keys as SubjectPublicKeyInfo and PKCS#8,");
+            eprintln!("  seed stores only the 64 byte seed of the secret key, from which it is derived when loading");
+        },
+        "fingerprint" => eprintln!("Usage: crystals_kyber.exe fingerprint key_file"),
+        "convert" => {
+            eprintln!("Usage: crystals_kyber.exe convert secret_key_file --format kyber|seed|pkcs8 [--out file]");
+            eprintln!("  converting to seed is only possible if the key is already stored in seed format");
         },
-        _ => println!("Command must be one of enc, dec, gen, got command {}", args[1])
+        "serve" => {
+            eprintln!("Usage: crystals_kyber.exe serve address secret_key_file client_public_key_file");
+            eprintln!("  waits for one connection on the address, e.g. 127.0.0.1:4433, and forwards stdin and stdout");
+            eprintln!("  over a channel that is mutually authenticated with both keys");
+        },
+        "connect" => eprintln!("Usage: crystals_kyber.exe connect address secret_key_file server_public_key_file"),
+        "keys" => {
+            eprintln!("Usage: crystals_kyber.exe keys list");
+            eprintln!("       crystals_kyber.exe keys import name key_file");
+            eprintln!("       crystals_kyber.exe keys export name [--secret] [--format kyber|seed|pkcs8] [--out file]");
+            eprintln!("       crystals_kyber.exe keys delete name");
+            eprintln!("  secret keys are stored encrypted, the passphrase is read from the first line of --passphrase-file,");
+            eprintln!("  the environment variable KYBER_PASSPHRASE or the terminal");
+        },
+        "params" => {
+            eprintln!("Usage: crystals_kyber.exe params failure [ciphertexts]");
+            eprintln!("       crystals_kyber.exe params estimate [parameter_set]");
+            eprintln!("  failure computes the decryption failure probability of each parameter set and, if a count is");
+            eprintln!("  given, compares the noise with that of this count of random encryptions");
+            eprintln!("  estimate prints the core-SVP hardness of the primal and dual attack and the sizes");
+        },
+        "toy" => {
+            eprintln!("Usage: crystals_kyber.exe toy parameter_set [message] [--trace]");
+            eprintln!("  runs key generation, encryption and decryption of a random or the given hex message with a");
+            eprintln!("  simple implementation for any parameter set, e.g. toy16, toy64 or kyber768; --trace prints all");
+            eprintln!("  intermediate values, like A, s, e, t, r, u, v and the rounding of each message bit");
+        },
+        _ => eprintln!("Usage: crystals_kyber.exe command parameters...")
     };
+    eprintln!("  Files can also be given by --in and --out, a missing file or - stands for stdin/stdout");
+    eprintln!("  Instead of a key file, the name, fingerprint or key ID of a key in the keystore can be given,");
+    eprintln!("  which is the directory given by --keyring, KYBER_KEYRING or ~/.kyber/keyring");
+}
+
+fn main() 
+{
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        print_usage("");
+        return;
+    }
+    let options = match parse_options(&args[2..]) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    let command: fn(&CliOptions) -> Result<(), String> = match args[1].as_str() {
+        "enc" => cli_enc,
+        "dec" => cli_dec,
+        "gen" => cli_gen,
+        "encrypt-file" => cli_encrypt_file,
+        "decrypt-file" => cli_decrypt_file,
+        "convert" => cli_convert,
+        "fingerprint" => cli_fingerprint,
+        "keys" => cli_keys,
+        "serve" => cli_serve,
+        "connect" => cli_connect,
+        "params" => cli_params,
+        "toy" => cli_toy,
+        _ => {
+            eprintln!("Command must be one of enc, dec, gen, encrypt-file, decrypt-file, convert, fingerprint, keys, serve, connect, params, toy, got command {}", args[1]);
+            std::process::exit(1);
+        }
+    };
+    if args[1] != "gen" && options.positional.len() < 1 {
+        print_usage(&args[1]);
+        std::process::exit(1);
+    }
+    if let Err(error) = command(&options) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
 }
 
 #[cfg(feature = "compatibility")]
@@ -146,6 +689,6 @@ fn test_decrypt_refimpl_encrypted() {
         hvKObHD0UCUmEKUKBFrERfDKh0rWXQZpjdrDBZalxeZ37YUau9HLtvt4E3LMM6KxUH6wT0XhNvskKGqZNW+lThgFBpu15jw7ioSQlEp2xjIFuuKs8T7Q7vjeHibc8VwXgYRcBFYsMA\
         nXK8d11+0hm29upL504GczN+p5gn04Ily0lOcaYv8PS6U5GUbtCho+0ToEZ1+GDEBlymfS040QQKoKxJi/NecIt8b40rPWCNp8/EiPrHrYJ/NtBDbONwjOCRzJRJeOUFvs2s4AOkGN\
         QBodmOqd";
-    let message = cli_decrypt(sk_str, ciphertext_str);
+    let message = cli_decrypt(sk_str, ciphertext_str).unwrap();
     assert_eq!("AAAABBBBAAAABBBBAAAABBBBAAAABBBBAAAABBBBCCA=", message);
 }
@@ -0,0 +1,98 @@
+use super::kyber::*;
+use super::rqvec::*;
+use super::ring::{ RqElementChineseRemainderRepr, RqElementCompression, CompressedRq };
+
+use sha3::digest::XofReader;
+
+/// Ciphertext of the multi-recipient scheme. All recipients share the matrix seed of
+/// their public keys, so the encryption randomness r, e1 can be reused and u = A^T r + e1
+/// is the same for everyone. Therefore it is only sent once, followed by one
+/// element v for each recipient (in the order of the given public keys).
+pub type MultiCiphertext = (CompressedRqVector<COMPRESSED_VECTOR_BIT_SIZE>, Vec<CompressedRq<COMPRESSED_RING_ELEMENT_BIT_SIZE>>);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MultiRecipientError
+{
+    NoRecipients,
+    DifferentMatrixSeeds
+}
+
+impl std::fmt::Display for MultiRecipientError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        match self {
+            MultiRecipientError::NoRecipients => write!(f, "Expected at least one recipient"),
+            MultiRecipientError::DifferentMatrixSeeds => write!(f, "All public keys must share the same matrix seed")
+        }
+    }
+}
+
+/// Encrypts the same plaintext to all given public keys. For a single recipient, the
+/// result is the same as the one of `kyber::encrypt` with the same seed.
+pub fn encrypt_multi(pks: &[PublicKey], plaintext: Plaintext, enc_seed: Seed) -> Result<MultiCiphertext, MultiRecipientError>
+{
+    let matrix_seed = &pks.first().ok_or(MultiRecipientError::NoRecipients)?.1;
+    if pks.iter().any(|pk| pk.1 != *matrix_seed) {
+        return Err(MultiRecipientError::DifferentMatrixSeeds);
+    }
+
+    let A = sample_uniform_matrix(matrix_seed);
+    let mut noise_random = expand_randomness_shake_256(enc_seed);
+    let r = sample_error_distribution_vector(&mut noise_random);
+    let e1 = sample_error_distribution_vector(&mut noise_random);
+    let u = A.transpose() * &r + &e1;
+    let message = RqElement::decompress(&CompressedRq::from_data(plaintext));
+    let v = pks.iter().map(|pk| {
+        let t = RqVector::decompress(&pk.0);
+        let e2 = sample_error_distribution_element(&mut noise_random);
+        ((&t * &r).to_coefficient_repr() + &e2 + &message).compress()
+    }).collect();
+    return Ok((u.compress(), v));
+}
+
+/// Generates a fresh key from the seed and encapsulates it to all given public keys
+pub fn encapsulate(pks: &[PublicKey], seed: Seed) -> Result<(Plaintext, MultiCiphertext), MultiRecipientError>
+{
+    let mut random = expand_randomness_shake_256(seed);
+    let mut key: Plaintext = [0; 32];
+    let mut enc_seed: Seed = [0; 32];
+    random.read(&mut key);
+    random.read(&mut enc_seed);
+    return Ok((key, encrypt_multi(pks, key, enc_seed)?));
+}
+
+/// Recovers the key from the shared component u and the component v of this recipient
+pub fn decapsulate(sk: SecretKey, u: &CompressedRqVector<COMPRESSED_VECTOR_BIT_SIZE>, v: &CompressedRq<COMPRESSED_RING_ELEMENT_BIT_SIZE>) -> Plaintext
+{
+    decrypt(sk, (u.clone(), v.clone()))
+}
+
+#[test]
+fn test_encapsulate_decapsulate() {
+    let matrix_seed = test_seed(0);
+    let keys: Vec<(SecretKey, PublicKey)> = (1..4).map(|i| key_gen(matrix_seed, test_seed(i))).collect();
+    let pks: Vec<PublicKey> = keys.iter().map(|(_sk, pk)| pk.clone()).collect();
+    let (key, (u, v)) = encapsulate(&pks, test_seed(42)).unwrap();
+    assert_eq!(keys.len(), v.len());
+    for (i, (sk, _pk)) in keys.into_iter().enumerate() {
+        assert_eq!(key, decapsulate(sk, &u, &v[i]));
+    }
+}
+
+#[test]
+fn test_single_recipient_equals_encrypt() {
+    let (_sk, pk) = key_gen(test_seed(0), test_seed(1));
+    let plaintext: Plaintext = test_seed(7);
+    let (u, mut v) = encrypt_multi(&[pk.clone()], plaintext, test_seed(3)).unwrap();
+    let expected = encrypt(&pk, plaintext, test_seed(3));
+    assert_eq!(super::io::write_ciphertext(&expected), super::io::write_ciphertext(&(u, v.pop().unwrap())));
+}
+
+#[test]
+fn test_invalid_recipients() {
+    assert_eq!(Some(MultiRecipientError::NoRecipients), encapsulate(&[], test_seed(42)).err());
+    let (_sk, pk) = key_gen(test_seed(0), test_seed(1));
+    let (_other_sk, other_pk) = key_gen(test_seed(2), test_seed(3));
+    assert_eq!(Some(MultiRecipientError::DifferentMatrixSeeds), encapsulate(&[pk, other_pk], test_seed(42)).err());
+}
@@ -0,0 +1,619 @@
+//! Handshakes in the style of the Noise protocol framework, where the Diffie-Hellman
+//! tokens are replaced by KEM tokens as in PQNoise:
+//!
+//! - `e`, `s`: send the ephemeral or static public key, as in Noise
+//! - `ekem`: encapsulate a key to the ephemeral public key of the peer, send the
+//!   ciphertext and mix the shared secret into the chaining key
+//! - `skem`: the same for the static public key of the peer, where the ciphertext is
+//!   encrypted if a key is already available
+//!
+//! The SymmetricState and CipherState follow the Noise specification, instantiated with
+//! SHA3-256 (and HMAC-SHA3-256 for HKDF) and ChaCha20-Poly1305. The protocol name is
+//! `Noise_pq<pattern>_Kyber768R1_ChaChaPoly_SHA3-256`.
+
+use super::kyber::{ PublicKey, Seed, PUBLIC_KEY_BYTES, CIPHERTEXT_BYTES, expand_randomness_shake_256 };
+use super::kem;
+use super::kem::KemSecretKey;
+use super::hkdf;
+use super::chacha20poly1305 as aead;
+use super::chacha20poly1305::{ AeadKey, AeadNonce, TAG_BYTES };
+use super::io;
+
+use sha3::digest::{ FixedOutput, Input, XofReader };
+
+pub const HASH_BYTES: usize = 32;
+/// The maximal size of a Noise message
+pub const MAX_MESSAGE_BYTES: usize = 65535;
+
+type Hash = [u8; HASH_BYTES];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token
+{
+    E,
+    S,
+    Ekem,
+    Skem
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern
+{
+    /// `-> e`, `<- ekem`
+    NN,
+    /// `<- s`, `...`, `-> skem, e`, `<- ekem`
+    NK,
+    /// `-> e`, `<- ekem, s`, `-> skem, s`, `<- skem`
+    XX,
+    /// `<- s`, `...`, `-> skem, e, s`, `<- ekem, skem`
+    IK
+}
+
+impl Pattern
+{
+    fn name(&self) -> &'static str
+    {
+        match self {
+            Pattern::NN => "NN",
+            Pattern::NK => "NK",
+            Pattern::XX => "XX",
+            Pattern::IK => "IK"
+        }
+    }
+
+    fn messages(&self) -> &'static [&'static [Token]]
+    {
+        use self::Token::*;
+        match self {
+            Pattern::NN => &[&[E], &[Ekem]],
+            Pattern::NK => &[&[Skem, E], &[Ekem]],
+            Pattern::XX => &[&[E], &[Ekem, S], &[Skem, S], &[Skem]],
+            Pattern::IK => &[&[Skem, E, S], &[Ekem, Skem]]
+        }
+    }
+
+    /// Whether the static key of the responder is known in advance (the pre-message `<- s`)
+    fn responder_static_known(&self) -> bool
+    {
+        *self == Pattern::NK || *self == Pattern::IK
+    }
+
+    fn initiator_has_static(&self) -> bool
+    {
+        *self == Pattern::XX || *self == Pattern::IK
+    }
+
+    fn responder_has_static(&self) -> bool
+    {
+        *self != Pattern::NN
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseError
+{
+    /// The message has the wrong length or contains an invalid public key or ciphertext
+    InvalidMessage,
+    DecryptionFailed,
+    /// It is the other party's turn, or the handshake is already finished
+    WrongTurn,
+    MessageTooLong,
+    NonceExhausted
+}
+
+impl std::fmt::Display for NoiseError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        match self {
+            NoiseError::InvalidMessage => write!(f, "Invalid handshake message"),
+            NoiseError::DecryptionFailed => write!(f, "Decryption failed, the message has been modified or the keys do not match"),
+            NoiseError::WrongTurn => write!(f, "The message does not fit the state of the handshake"),
+            NoiseError::MessageTooLong => write!(f, "Message too long"),
+            NoiseError::NonceExhausted => write!(f, "Too many messages for one key")
+        }
+    }
+}
+
+fn hash(data: &[&[u8]]) -> Hash
+{
+    let mut hasher = sha3::Sha3_256::default();
+    for part in data {
+        hasher.input(part);
+    }
+    let mut result = [0; HASH_BYTES];
+    result.copy_from_slice(&hasher.fixed_result());
+    return result;
+}
+
+/// HKDF as defined by Noise, returning two outputs
+fn noise_hkdf(chaining_key: &Hash, input_key_material: &[u8]) -> (Hash, Hash)
+{
+    let temp_key = hkdf::hmac(chaining_key, &[input_key_material]);
+    let output1 = hkdf::hmac(&temp_key, &[&[1]]);
+    let output2 = hkdf::hmac(&temp_key, &[&output1, &[2]]);
+    return (output1, output2);
+}
+
+pub struct CipherState
+{
+    key: Option<AeadKey>,
+    nonce: u64
+}
+
+impl CipherState
+{
+    fn new(key: Option<AeadKey>) -> CipherState
+    {
+        CipherState { key: key, nonce: 0 }
+    }
+
+    fn aead_nonce(&self) -> Result<AeadNonce, NoiseError>
+    {
+        // 2^64 - 1 is reserved by the specification
+        if self.nonce == u64::max_value() {
+            return Err(NoiseError::NonceExhausted);
+        }
+        let mut result: AeadNonce = [0; 12];
+        result[4..12].copy_from_slice(&self.nonce.to_le_bytes());
+        return Ok(result);
+    }
+
+    pub fn has_key(&self) -> bool
+    {
+        self.key.is_some()
+    }
+
+    /// Encrypts the plaintext, or returns it unchanged if there is no key yet
+    pub fn encrypt_with_ad(&mut self, ad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, NoiseError>
+    {
+        match &self.key {
+            None => Ok(plaintext.to_vec()),
+            Some(key) => {
+                let result = aead::seal(key, &self.aead_nonce()?, ad, plaintext);
+                self.nonce += 1;
+                Ok(result)
+            }
+        }
+    }
+
+    pub fn decrypt_with_ad(&mut self, ad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, NoiseError>
+    {
+        match &self.key {
+            None => Ok(ciphertext.to_vec()),
+            Some(key) => {
+                let result = aead::open(key, &self.aead_nonce()?, ad, ciphertext).ok_or(NoiseError::DecryptionFailed)?;
+                self.nonce += 1;
+                Ok(result)
+            }
+        }
+    }
+}
+
+pub struct SymmetricState
+{
+    cipher: CipherState,
+    chaining_key: Hash,
+    handshake_hash: Hash
+}
+
+impl SymmetricState
+{
+    pub fn new(protocol_name: &[u8]) -> SymmetricState
+    {
+        let mut handshake_hash = [0; HASH_BYTES];
+        if protocol_name.len() <= HASH_BYTES {
+            handshake_hash[0..protocol_name.len()].copy_from_slice(protocol_name);
+        } else {
+            handshake_hash = hash(&[protocol_name]);
+        }
+        return SymmetricState { cipher: CipherState::new(None), chaining_key: handshake_hash, handshake_hash: handshake_hash };
+    }
+
+    pub fn mix_key(&mut self, input_key_material: &[u8])
+    {
+        let (chaining_key, key) = noise_hkdf(&self.chaining_key, input_key_material);
+        self.chaining_key = chaining_key;
+        self.cipher = CipherState::new(Some(key));
+    }
+
+    pub fn mix_hash(&mut self, data: &[u8])
+    {
+        self.handshake_hash = hash(&[&self.handshake_hash, data]);
+    }
+
+    pub fn encrypt_and_hash(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, NoiseError>
+    {
+        let ciphertext = self.cipher.encrypt_with_ad(&self.handshake_hash, plaintext)?;
+        self.mix_hash(&ciphertext);
+        return Ok(ciphertext);
+    }
+
+    pub fn decrypt_and_hash(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, NoiseError>
+    {
+        let plaintext = self.cipher.decrypt_with_ad(&self.handshake_hash, ciphertext)?;
+        self.mix_hash(ciphertext);
+        return Ok(plaintext);
+    }
+
+    /// Returns the cipher states for initiator to responder and responder to initiator
+    pub fn split(&self) -> (CipherState, CipherState)
+    {
+        let (key1, key2) = noise_hkdf(&self.chaining_key, &[]);
+        return (CipherState::new(Some(key1)), CipherState::new(Some(key2)));
+    }
+
+    /// The length of a field of the given size after encrypt_and_hash()
+    fn encrypted_length(&self, length: usize) -> usize
+    {
+        if self.cipher.has_key() { length + TAG_BYTES } else { length }
+    }
+}
+
+pub struct HandshakeState
+{
+    symmetric: SymmetricState,
+    pattern: Pattern,
+    initiator: bool,
+    s: Option<KemSecretKey>,
+    e: Option<KemSecretKey>,
+    rs: Option<PublicKey>,
+    re: Option<PublicKey>,
+    message_index: usize,
+    random: sha3::Sha3XofReader
+}
+
+impl HandshakeState
+{
+    /// Starts a handshake. The static key s is required for the initiator of XX and IK
+    /// and for the responder of NK, XX and IK, the static public key rs of the responder
+    /// is required for the initiator of NK and IK. All ephemeral keys and encapsulations
+    /// are derived from the seed.
+    pub fn new(pattern: Pattern, initiator: bool, prologue: &[u8], s: Option<KemSecretKey>, rs: Option<PublicKey>, seed: Seed) -> HandshakeState
+    {
+        let needs_static = if initiator { pattern.initiator_has_static() } else { pattern.responder_has_static() };
+        assert!(s.is_some() == needs_static, "Pattern {} {} a static key", pattern.name(), if needs_static { "requires" } else { "does not use" });
+        let needs_remote_static = initiator && pattern.responder_static_known();
+        assert!(rs.is_some() == needs_remote_static, "Pattern {} {} the static public key of the responder",
+            pattern.name(), if needs_remote_static { "requires" } else { "does not know" });
+
+        let protocol_name = format!("Noise_pq{}_Kyber768R1_ChaChaPoly_SHA3-256", pattern.name());
+        let mut symmetric = SymmetricState::new(protocol_name.as_bytes());
+        symmetric.mix_hash(prologue);
+        if pattern.responder_static_known() {
+            let responder_pk = if initiator { rs.clone().unwrap() } else { s.as_ref().unwrap().1.clone() };
+            symmetric.mix_hash(&io::pk_to_bytes(&responder_pk));
+        }
+        return HandshakeState {
+            symmetric: symmetric,
+            pattern: pattern,
+            initiator: initiator,
+            s: s,
+            e: None,
+            rs: rs,
+            re: None,
+            message_index: 0,
+            random: expand_randomness_shake_256(seed)
+        };
+    }
+
+    fn next_seed(&mut self) -> Seed
+    {
+        let mut result: Seed = [0; 32];
+        self.random.read(&mut result);
+        return result;
+    }
+
+    fn is_my_turn(&self) -> bool
+    {
+        (self.message_index % 2 == 0) == self.initiator
+    }
+
+    pub fn is_finished(&self) -> bool
+    {
+        self.message_index == self.pattern.messages().len()
+    }
+
+    /// The hash of the whole handshake, which can be used for channel binding
+    pub fn handshake_hash(&self) -> &[u8; HASH_BYTES]
+    {
+        &self.symmetric.handshake_hash
+    }
+
+    /// The static public key of the peer, if it is known
+    pub fn remote_static(&self) -> Option<&PublicKey>
+    {
+        self.rs.as_ref()
+    }
+
+    /// The length of the next message with a payload of the given length; the fields are
+    /// encrypted once a key has been mixed in by ekem or skem
+    fn message_length(&self, payload_length: usize) -> usize
+    {
+        let tag_length = |has_key: bool| if has_key { TAG_BYTES } else { 0 };
+        let mut has_key = self.symmetric.cipher.has_key();
+        let mut result = 0;
+        for token in self.pattern.messages()[self.message_index] {
+            match token {
+                Token::E => result += PUBLIC_KEY_BYTES,
+                Token::S => result += PUBLIC_KEY_BYTES + tag_length(has_key),
+                Token::Ekem => {
+                    result += CIPHERTEXT_BYTES;
+                    has_key = true;
+                },
+                Token::Skem => {
+                    result += CIPHERTEXT_BYTES + tag_length(has_key);
+                    has_key = true;
+                }
+            }
+        }
+        return result + payload_length + tag_length(has_key);
+    }
+
+    /// Writes the next message with the given payload. The errors WrongTurn and
+    /// MessageTooLong leave the state unchanged, so the call can be repeated with a shorter
+    /// payload.
+    pub fn write_message(&mut self, payload: &[u8]) -> Result<Vec<u8>, NoiseError>
+    {
+        if self.is_finished() || !self.is_my_turn() {
+            return Err(NoiseError::WrongTurn);
+        }
+        let length = self.message_length(payload.len());
+        if length > MAX_MESSAGE_BYTES {
+            return Err(NoiseError::MessageTooLong);
+        }
+        let mut result = Vec::with_capacity(length);
+        for token in self.pattern.messages()[self.message_index] {
+            match token {
+                Token::E => {
+                    let (matrix_seed, secret_seed) = (self.next_seed(), self.next_seed());
+                    let (e, e_pk) = kem::key_gen(matrix_seed, secret_seed);
+                    let e_bytes = io::pk_to_bytes(&e_pk);
+                    self.symmetric.mix_hash(&e_bytes);
+                    result.extend_from_slice(&e_bytes);
+                    self.e = Some(e);
+                },
+                Token::S => {
+                    let s_bytes = io::pk_to_bytes(&self.s.as_ref().unwrap().1);
+                    result.extend_from_slice(&self.symmetric.encrypt_and_hash(&s_bytes)?);
+                },
+                Token::Ekem => {
+                    let seed = self.next_seed();
+                    let (shared_secret, ciphertext) = kem::encapsulate(self.re.as_ref().unwrap(), seed);
+                    let ciphertext_bytes = io::ciphertext_to_bytes(&ciphertext);
+                    self.symmetric.mix_hash(&ciphertext_bytes);
+                    result.extend_from_slice(&ciphertext_bytes);
+                    self.symmetric.mix_key(&shared_secret);
+                },
+                Token::Skem => {
+                    let seed = self.next_seed();
+                    let (shared_secret, ciphertext) = kem::encapsulate(self.rs.as_ref().unwrap(), seed);
+                    result.extend_from_slice(&self.symmetric.encrypt_and_hash(&io::ciphertext_to_bytes(&ciphertext))?);
+                    self.symmetric.mix_key(&shared_secret);
+                }
+            }
+        }
+        result.extend_from_slice(&self.symmetric.encrypt_and_hash(payload)?);
+        debug_assert_eq!(length, result.len());
+        self.message_index += 1;
+        return Ok(result);
+    }
+
+    /// Processes the message of the peer and returns its payload. After an error, the
+    /// handshake must be aborted.
+    pub fn read_message(&mut self, message: &[u8]) -> Result<Vec<u8>, NoiseError>
+    {
+        if self.is_finished() || self.is_my_turn() {
+            return Err(NoiseError::WrongTurn);
+        }
+        if message.len() > MAX_MESSAGE_BYTES {
+            return Err(NoiseError::MessageTooLong);
+        }
+        let mut rest = message;
+        let mut take = |length: usize| {
+            if rest.len() < length {
+                return Err(NoiseError::InvalidMessage);
+            }
+            let (field, remaining) = rest.split_at(length);
+            rest = remaining;
+            return Ok(field);
+        };
+        for token in self.pattern.messages()[self.message_index] {
+            match token {
+                Token::E => {
+                    let e_bytes = take(PUBLIC_KEY_BYTES)?;
+                    self.re = Some(io::validate_public_key(e_bytes).map_err(|_| NoiseError::InvalidMessage)?);
+                    self.symmetric.mix_hash(e_bytes);
+                },
+                Token::S => {
+                    let field = take(self.symmetric.encrypted_length(PUBLIC_KEY_BYTES))?;
+                    let s_bytes = self.symmetric.decrypt_and_hash(field)?;
+                    self.rs = Some(io::validate_public_key(&s_bytes).map_err(|_| NoiseError::InvalidMessage)?);
+                },
+                Token::Ekem => {
+                    let ciphertext_bytes = take(CIPHERTEXT_BYTES)?;
+                    let ciphertext = io::validate_ciphertext(ciphertext_bytes).map_err(|_| NoiseError::InvalidMessage)?;
+                    self.symmetric.mix_hash(ciphertext_bytes);
+                    self.symmetric.mix_key(&kem::decapsulate(self.e.as_ref().unwrap(), &ciphertext));
+                },
+                Token::Skem => {
+                    let field = take(self.symmetric.encrypted_length(CIPHERTEXT_BYTES))?;
+                    let ciphertext_bytes = self.symmetric.decrypt_and_hash(field)?;
+                    let ciphertext = io::validate_ciphertext(&ciphertext_bytes).map_err(|_| NoiseError::InvalidMessage)?;
+                    self.symmetric.mix_key(&kem::decapsulate(self.s.as_ref().unwrap(), &ciphertext));
+                }
+            }
+        }
+        let payload = self.symmetric.decrypt_and_hash(rest)?;
+        self.message_index += 1;
+        return Ok(payload);
+    }
+
+    /// Returns the cipher states for sending and for receiving, once the handshake is finished
+    pub fn split(&self) -> (CipherState, CipherState)
+    {
+        assert!(self.is_finished(), "The handshake is not finished yet");
+        let (initiator_to_responder, responder_to_initiator) = self.symmetric.split();
+        if self.initiator {
+            return (initiator_to_responder, responder_to_initiator);
+        } else {
+            return (responder_to_initiator, initiator_to_responder);
+        }
+    }
+}
+
+#[cfg(test)]
+use super::kyber::test_seed;
+
+/// Runs the whole handshake with payloads "message i" and returns both states together with all messages
+#[cfg(test)]
+fn run_handshake(pattern: Pattern, initiator_s: Option<KemSecretKey>, responder_s: Option<KemSecretKey>, rs: Option<PublicKey>)
+    -> Result<(HandshakeState, HandshakeState, Vec<Vec<u8>>), NoiseError>
+{
+    let mut initiator = HandshakeState::new(pattern, true, b"prologue", initiator_s, rs, test_seed(1));
+    let mut responder = HandshakeState::new(pattern, false, b"prologue", responder_s, None, test_seed(2));
+    let mut messages = Vec::new();
+    let mut i = 0;
+    while !initiator.is_finished() {
+        let (sender, receiver) = if i % 2 == 0 { (&mut initiator, &mut responder) } else { (&mut responder, &mut initiator) };
+        let payload = format!("message {}", i).into_bytes();
+        let message = sender.write_message(&payload)?;
+        assert_eq!(payload, receiver.read_message(&message)?);
+        messages.push(message);
+        i += 1;
+    }
+    assert!(responder.is_finished());
+    return Ok((initiator, responder, messages));
+}
+
+#[cfg(test)]
+fn static_keys_for(pattern: Pattern) -> (Option<KemSecretKey>, Option<KemSecretKey>, Option<PublicKey>)
+{
+    let (initiator_sk, _) = kem::key_gen(test_seed(10), test_seed(11));
+    let (responder_sk, responder_pk) = kem::key_gen(test_seed(12), test_seed(13));
+    return (
+        if pattern.initiator_has_static() { Some(initiator_sk) } else { None },
+        if pattern.responder_has_static() { Some(responder_sk) } else { None },
+        if pattern.responder_static_known() { Some(responder_pk) } else { None }
+    );
+}
+
+#[test]
+fn test_patterns() {
+    for pattern in &[Pattern::NN, Pattern::NK, Pattern::XX, Pattern::IK] {
+        let (initiator_s, responder_s, rs) = static_keys_for(*pattern);
+        let initiator_pk = initiator_s.as_ref().map(|sk| sk.1.clone());
+        let responder_pk = responder_s.as_ref().map(|sk| sk.1.clone());
+        let (initiator, responder, _) = run_handshake(*pattern, initiator_s, responder_s, rs).unwrap();
+        assert_eq!(initiator.handshake_hash(), responder.handshake_hash());
+        // the static keys have been transmitted (or were known in advance)
+        if let Some(pk) = responder_pk {
+            assert_eq!(io::pk_to_bytes(&pk), io::pk_to_bytes(initiator.remote_static().unwrap()));
+        }
+        if let Some(pk) = initiator_pk {
+            assert_eq!(io::pk_to_bytes(&pk), io::pk_to_bytes(responder.remote_static().unwrap()));
+        }
+
+        let (mut initiator_send, mut initiator_receive) = initiator.split();
+        let (mut responder_send, mut responder_receive) = responder.split();
+        for i in 0..3 {
+            let ciphertext = initiator_send.encrypt_with_ad(b"", &[i; 100]).unwrap();
+            assert_eq!(vec![i; 100], responder_receive.decrypt_with_ad(b"", &ciphertext).unwrap());
+            let ciphertext = responder_send.encrypt_with_ad(b"ad", &[i; 10]).unwrap();
+            assert_eq!(vec![i; 10], initiator_receive.decrypt_with_ad(b"ad", &ciphertext).unwrap());
+        }
+        assert_eq!(Err(NoiseError::DecryptionFailed), initiator_receive.decrypt_with_ad(b"", &initiator_send.encrypt_with_ad(b"", b"x").unwrap()));
+    }
+}
+
+#[test]
+fn test_message_sizes() {
+    let (initiator_s, responder_s, rs) = static_keys_for(Pattern::IK);
+    let (_, _, messages) = run_handshake(Pattern::IK, initiator_s, responder_s, rs).unwrap();
+    // skem in clear, e in clear, encrypted s, encrypted payload
+    assert_eq!(CIPHERTEXT_BYTES + PUBLIC_KEY_BYTES + PUBLIC_KEY_BYTES + TAG_BYTES + 9 + TAG_BYTES, messages[0].len());
+    // ekem in clear, encrypted skem, encrypted payload
+    assert_eq!(CIPHERTEXT_BYTES + CIPHERTEXT_BYTES + TAG_BYTES + 9 + TAG_BYTES, messages[1].len());
+}
+
+#[test]
+fn test_too_long_payload() {
+    let (initiator_s, responder_s, rs) = static_keys_for(Pattern::IK);
+    let mut initiator = HandshakeState::new(Pattern::IK, true, b"", initiator_s, rs, test_seed(1));
+    let mut responder = HandshakeState::new(Pattern::IK, false, b"", responder_s, None, test_seed(2));
+    let overhead = CIPHERTEXT_BYTES + PUBLIC_KEY_BYTES + PUBLIC_KEY_BYTES + TAG_BYTES + TAG_BYTES;
+    assert_eq!(Err(NoiseError::MessageTooLong), initiator.write_message(&vec![0; MAX_MESSAGE_BYTES - overhead + 1]));
+    // the failed call did not change the state, so the handshake can continue
+    let message = initiator.write_message(&vec![1; MAX_MESSAGE_BYTES - overhead]).unwrap();
+    assert_eq!(MAX_MESSAGE_BYTES, message.len());
+    assert_eq!(vec![1; MAX_MESSAGE_BYTES - overhead], responder.read_message(&message).unwrap());
+    let response = responder.write_message(b"").unwrap();
+    initiator.read_message(&response).unwrap();
+    assert_eq!(initiator.handshake_hash(), responder.handshake_hash());
+}
+
+#[test]
+fn test_tampered_message() {
+    for pattern in &[Pattern::NN, Pattern::NK, Pattern::XX, Pattern::IK] {
+        let (initiator_s, responder_s, rs) = static_keys_for(*pattern);
+        let mut initiator = HandshakeState::new(*pattern, true, b"", initiator_s, rs, test_seed(1));
+        let mut responder = HandshakeState::new(*pattern, false, b"", responder_s, None, test_seed(2));
+        let mut message = initiator.write_message(b"").unwrap();
+        message[100] ^= 1;
+        // NN and XX send no encrypted data in the first message, so the modification
+        // is only detected when the responder's message is read
+        match responder.read_message(&message) {
+            Ok(_) => {
+                let response = responder.write_message(b"").unwrap();
+                assert_eq!(Err(NoiseError::DecryptionFailed), initiator.read_message(&response));
+            },
+            Err(error) => assert_eq!(NoiseError::DecryptionFailed, error)
+        }
+    }
+}
+
+#[test]
+fn test_wrong_static_key() {
+    let (_, responder_s, _) = static_keys_for(Pattern::NK);
+    let (_, other_pk) = kem::key_gen(test_seed(12), test_seed(14));
+    match run_handshake(Pattern::NK, None, responder_s, Some(other_pk)) {
+        Err(error) => assert_eq!(NoiseError::DecryptionFailed, error),
+        Ok(_) => panic!("Handshake with the wrong static key succeeded")
+    };
+}
+
+#[test]
+fn test_wrong_turn_and_length() {
+    let mut initiator = HandshakeState::new(Pattern::NN, true, b"", None, None, test_seed(1));
+    let mut responder = HandshakeState::new(Pattern::NN, false, b"", None, None, test_seed(2));
+    assert_eq!(Err(NoiseError::WrongTurn), responder.write_message(b""));
+    assert_eq!(Err(NoiseError::WrongTurn), initiator.read_message(b""));
+    let message = initiator.write_message(b"").unwrap();
+    assert_eq!(Err(NoiseError::InvalidMessage), responder.read_message(&message[..PUBLIC_KEY_BYTES - 1]));
+}
+
+/// Regression vectors: the hash of all handshake messages and the handshake hash, for
+/// fixed seeds and payloads. They were produced by this implementation and protect
+/// against unintended changes of the wire format or the key schedule. The compatibility
+/// mode encodes ring elements differently, so the vectors only hold without it.
+#[cfg(not(feature = "compatibility"))]
+#[test]
+fn test_regression_vectors() {
+    let vectors = [
+        (Pattern::NN, "dc0a95a627faee6e53b0a84faae940ff384b84bc3e1958a9b98753e48268c873",
+            "cf33c893b8230962e650b24ccac24d762289a5aaf8a982237d3ebfe434e0862e"),
+        (Pattern::NK, "4e31a072b9be0ca71abeedcbace1307355236e10497d8e8762786a1a7da52198",
+            "9c47c84de589c760202602eb0aff2678c1249d406f59602cb267989d682c3fb3"),
+        (Pattern::XX, "916d3d9ef7cb7044aba623f7e037059701575dae19400ff00cb0c1067c2f0563",
+            "9da43c60211b7835ab560e08e9feaf9dfe4cb22faf7dba5f266f982050050d93"),
+        (Pattern::IK, "cf01693a7b9708b9e6bfd66707e33460b18fcbd04026c74b6ef7d8408e6995cb",
+            "f5c6e4d5a124e842c2044080c46f24b9c36d1de662aa450d17e3e9fe365afbbe")
+    ];
+    for (pattern, messages_hash, handshake_hash) in &vectors {
+        let (initiator_s, responder_s, rs) = static_keys_for(*pattern);
+        let (initiator, _, messages) = run_handshake(*pattern, initiator_s, responder_s, rs).unwrap();
+        let all_messages: Vec<u8> = messages.concat();
+        assert_eq!(*messages_hash, super::util::to_hex(&hash(&[&all_messages])), "{:?}", pattern);
+        assert_eq!(*handshake_hash, super::util::to_hex(initiator.handshake_hash()), "{:?}", pattern);
+    }
+}
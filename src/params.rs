@@ -0,0 +1,113 @@
+//! Descriptions of the parameter sets of the first round submission of Kyber and of some
+//! toy parameter sets, which are used by the analysis tools and toy.rs. Only KYBER768 is
+//! implemented by kyber.rs.
+
+use super::kyber::{ COMPRESSED_VECTOR_BIT_SIZE, COMPRESSED_RING_ELEMENT_BIT_SIZE, UNCOMPRESSED_BIT_SIZE, ETA };
+use super::ring::N;
+use super::rqvec::DIM;
+use super::zq::Q;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParameterSet
+{
+    pub name: &'static str,
+    /// Degree of the ring extension
+    pub n: usize,
+    /// Rank of the module, i.e. the length of the secret vector
+    pub k: usize,
+    pub q: u32,
+    /// The secret and error coefficients are sampled from the centered binomial
+    /// distribution with parameter eta, i.e. with variance eta / 2
+    pub eta: u32,
+    /// The count of bits used for each coefficient of t, u and v
+    pub dt: u16,
+    pub du: u16,
+    pub dv: u16
+}
+
+pub const KYBER512: ParameterSet = ParameterSet {
+    name: "Kyber512", n: 256, k: 2, q: 7681, eta: 5, dt: 11, du: 11, dv: 3
+};
+
+pub const KYBER768: ParameterSet = ParameterSet {
+    name: "Kyber768", n: N, k: DIM, q: Q, eta: ETA as u32,
+    dt: COMPRESSED_VECTOR_BIT_SIZE, du: COMPRESSED_VECTOR_BIT_SIZE, dv: COMPRESSED_RING_ELEMENT_BIT_SIZE
+};
+
+/// Kyber768 with t sent uncompressed, as in later versions of Kyber
+pub const KYBER768_UNCOMPRESSED_T: ParameterSet = ParameterSet {
+    name: "Kyber768-t13", dt: UNCOMPRESSED_BIT_SIZE, ..KYBER768
+};
+
+pub const KYBER1024: ParameterSet = ParameterSet {
+    name: "Kyber1024", n: 256, k: 4, q: 7681, eta: 3, dt: 11, du: 11, dv: 3
+};
+
+/// Tiny and completely insecure parameter sets, with which the computations of toy.rs can
+/// be followed by hand
+pub const TOY16: ParameterSet = ParameterSet {
+    name: "Toy16", n: 16, k: 2, q: 257, eta: 2, dt: 9, du: 6, dv: 3
+};
+
+pub const TOY64: ParameterSet = ParameterSet {
+    name: "Toy64", n: 64, k: 2, q: 769, eta: 2, dt: 10, du: 8, dv: 4
+};
+
+pub const PARAMETER_SETS: [ParameterSet; 6] = [KYBER512, KYBER768, KYBER1024, KYBER768_UNCOMPRESSED_T, TOY16, TOY64];
+
+/// The parameter set implemented by kyber.rs
+pub const IMPLEMENTED: ParameterSet = KYBER768;
+
+impl ParameterSet
+{
+    /// The count of bits of an uncompressed coefficient, i.e. the bit length of q - 1
+    pub fn coefficient_bits(&self) -> usize
+    {
+        (32 - (self.q - 1).leading_zeros()) as usize
+    }
+
+    /// The byte sizes of the encodings of io.rs
+    pub fn secret_key_bytes(&self) -> usize
+    {
+        self.k * self.n * self.coefficient_bits() / 8
+    }
+
+    pub fn public_key_bytes(&self) -> usize
+    {
+        self.k * self.n * self.dt as usize / 8 + 32
+    }
+
+    pub fn ciphertext_bytes(&self) -> usize
+    {
+        (self.k * self.n * self.du as usize + self.n * self.dv as usize) / 8
+    }
+}
+
+/// Finds a parameter set by its name, ignoring case
+pub fn find(name: &str) -> Option<ParameterSet>
+{
+    PARAMETER_SETS.iter().find(|params| params.name.eq_ignore_ascii_case(name)).cloned()
+}
+
+#[test]
+fn test_find()
+{
+    assert_eq!(Some(KYBER512), find("kyber512"));
+    assert_eq!(Some(IMPLEMENTED), find("Kyber768"));
+    assert_eq!(None, find("Kyber"));
+}
+
+#[test]
+fn test_sizes()
+{
+    use super::kyber::{ SECRET_KEY_BYTES, PUBLIC_KEY_BYTES, CIPHERTEXT_BYTES };
+    use super::zq::ENCODE_BITS;
+    assert_eq!(ENCODE_BITS, IMPLEMENTED.coefficient_bits());
+    assert_eq!(SECRET_KEY_BYTES, IMPLEMENTED.secret_key_bytes());
+    assert_eq!(PUBLIC_KEY_BYTES, IMPLEMENTED.public_key_bytes());
+    assert_eq!(CIPHERTEXT_BYTES, IMPLEMENTED.ciphertext_bytes());
+    assert_eq!(736, KYBER512.public_key_bytes());
+    assert_eq!(1504, KYBER1024.ciphertext_bytes());
+    assert_eq!(super::kyber::public_key_bytes(UNCOMPRESSED_BIT_SIZE), KYBER768_UNCOMPRESSED_T.public_key_bytes());
+    assert_eq!(1280, KYBER768_UNCOMPRESSED_T.public_key_bytes());
+}
@@ -0,0 +1,164 @@
+//! PEM-style armoring of keys, i.e. the base64 encoding of the binary key, wrapped
+//! into lines of 64 characters between a BEGIN and an END line.
+
+use super::encoding::*;
+use super::kyber::PublicKey;
+use super::kem::{ KemSecretKey, KemSecretSeed, KEM_SECRET_KEY_BYTES, KEM_SECRET_SEED_BYTES, expand_secret_seed };
+use super::io;
+use super::der;
+
+pub const PUBLIC_KEY_LABEL: &str = "KYBER PUBLIC KEY";
+pub const SECRET_KEY_LABEL: &str = "KYBER SECRET KEY";
+/// The compact secret key, containing only the seeds from which the key is derived
+pub const SECRET_SEED_LABEL: &str = "KYBER SECRET KEY SEED";
+/// The labels of the DER encoded SubjectPublicKeyInfo and PKCS#8 structures, see der.rs
+pub const SPKI_LABEL: &str = "PUBLIC KEY";
+pub const PKCS8_LABEL: &str = "PRIVATE KEY";
+
+const LINE_LENGTH: usize = 64;
+
+pub fn encode(label: &str, data: &[u8]) -> String
+{
+    let mut result = format!("-----BEGIN {}-----\n", label);
+    {
+        let mut current_line_length = 0;
+        let result_ref = &mut result;
+        let mut encoder = Base64Encoder::new(|c| {
+            result_ref.push(c);
+            current_line_length += 1;
+            if current_line_length == LINE_LENGTH {
+                result_ref.push('\n');
+                current_line_length = 0;
+            }
+        });
+        encoder.encode_bytes(data);
+    }
+    if !result.ends_with('\n') {
+        result.push('\n');
+    }
+    result.push_str(&format!("-----END {}-----\n", label));
+    return result;
+}
+
+/// Returns the label and the decoded content of the first PEM block in the text,
+/// or None if there is none or it is malformed
+pub fn decode(text: &str) -> Option<(String, Vec<u8>)>
+{
+    let mut lines = text.lines().map(str::trim).skip_while(|line| !line.starts_with("-----BEGIN "));
+    let label = lines.next()?.strip_prefix("-----BEGIN ")?.strip_suffix("-----")?.to_owned();
+    let end_line = format!("-----END {}-----", label);
+
+    let mut content = String::new();
+    loop {
+        let line = lines.next()?;
+        if line == end_line {
+            break;
+        }
+        content.push_str(line);
+    }
+    let padding = content.len() - content.trim_end_matches('=').len();
+    if padding > 2 || content.len() % 4 != 0 {
+        return None;
+    }
+    content.truncate(content.len() - padding);
+    if !content.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/') {
+        return None;
+    }
+
+    let byte_count = content.len() * 6 / 8;
+    let mut chars = content.chars();
+    let mut decoder = Base64Decoder::new(move || chars.next());
+    let data = (0..byte_count).map(|_| decoder.read()).collect::<Option<Vec<u8>>>()?;
+    return Some((label, data));
+}
+
+pub fn write_public_key(pk: &PublicKey) -> String
+{
+    encode(PUBLIC_KEY_LABEL, &io::pk_to_bytes(pk))
+}
+
+pub fn write_secret_key(sk: &KemSecretKey) -> String
+{
+    encode(SECRET_KEY_LABEL, &io::kem_sk_to_bytes(sk))
+}
+
+pub fn write_secret_seed(seed: &KemSecretSeed) -> String
+{
+    encode(SECRET_SEED_LABEL, &io::secret_seed_to_bytes(seed))
+}
+
+pub fn read_secret_seed(text: &str) -> Option<KemSecretSeed>
+{
+    match decode(text)? {
+        (ref label, ref data) if label == SECRET_SEED_LABEL && data.len() == KEM_SECRET_SEED_BYTES => io::secret_seed_from_bytes(data).ok(),
+        _ => None
+    }
+}
+
+pub fn read_public_key(text: &str) -> Option<PublicKey>
+{
+    match decode(text)? {
+        (ref label, ref data) if label == PUBLIC_KEY_LABEL => io::validate_public_key(data).ok(),
+        (ref label, ref data) if label == SPKI_LABEL => der::pk_from_spki(data).ok(),
+        _ => None
+    }
+}
+
+pub fn read_secret_key(text: &str) -> Option<KemSecretKey>
+{
+    match decode(text)? {
+        (ref label, ref data) if label == SECRET_KEY_LABEL && data.len() == KEM_SECRET_KEY_BYTES => io::kem_sk_from_bytes(data).ok(),
+        (ref label, ref data) if label == PKCS8_LABEL => der::sk_from_pkcs8(data).ok(),
+        (ref label, ref data) if label == SECRET_SEED_LABEL && data.len() == KEM_SECRET_SEED_BYTES => {
+            io::secret_seed_from_bytes(data).ok().map(|seed| expand_secret_seed(&seed))
+        },
+        _ => None
+    }
+}
+
+#[test]
+fn test_encode_decode() {
+    for len in 0..100 {
+        let data: Vec<u8> = (0..len).map(|i| (i * 37 + 5) as u8).collect();
+        let pem = encode("TEST", &data);
+        assert!(pem.lines().all(|line| line.len() <= LINE_LENGTH || line.starts_with("-----")));
+        assert_eq!(Some(("TEST".to_owned(), data)), decode(&pem));
+    }
+}
+
+#[test]
+fn test_decode_rejects_malformed() {
+    assert_eq!(None, decode("-----BEGIN TEST-----\nQUJD\n"));
+    assert_eq!(None, decode("-----BEGIN TEST-----\nQU*D\n-----END TEST-----\n"));
+    assert_eq!(None, decode("-----BEGIN TEST-----\nQUJ\n-----END TEST-----\n"));
+    assert_eq!(None, decode("-----BEGIN TEST-----\nQUJD\n-----END OTHER-----\n"));
+    assert_eq!(Some(("TEST".to_owned(), b"ABC".to_vec())), decode("comment\n-----BEGIN TEST-----\r\nQUJD\r\n-----END TEST-----\r\n"));
+}
+
+#[test]
+fn test_read_write_keys() {
+    let (sk, pk) = super::kem::key_gen([4; 32], [5; 32]);
+    let pk_pem = write_public_key(&pk);
+    let sk_pem = write_secret_key(&sk);
+    assert!(pk_pem.starts_with("-----BEGIN KYBER PUBLIC KEY-----\n"));
+    assert_eq!(pk_pem, write_public_key(&read_public_key(&pk_pem).unwrap()));
+    assert_eq!(sk_pem, write_secret_key(&read_secret_key(&sk_pem).unwrap()));
+    assert!(read_public_key(&sk_pem).is_none());
+    assert!(read_secret_key(&pk_pem).is_none());
+
+    let spki_pem = encode(SPKI_LABEL, &der::pk_to_spki(&pk));
+    let pkcs8_pem = encode(PKCS8_LABEL, &der::sk_to_pkcs8(&sk));
+    assert_eq!(pk_pem, write_public_key(&read_public_key(&spki_pem).unwrap()));
+    assert_eq!(sk_pem, write_secret_key(&read_secret_key(&pkcs8_pem).unwrap()));
+}
+
+#[test]
+fn test_read_write_secret_seed() {
+    let seed = ([4; 32], [5; 32]);
+    let seed_pem = write_secret_seed(&seed);
+    assert!(seed_pem.starts_with("-----BEGIN KYBER SECRET KEY SEED-----\n"));
+    assert_eq!(seed, read_secret_seed(&seed_pem).unwrap());
+    let (sk, _) = super::kem::key_gen([4; 32], [5; 32]);
+    assert_eq!(write_secret_key(&sk), write_secret_key(&read_secret_key(&seed_pem).unwrap()));
+    assert!(read_secret_seed(&write_secret_key(&sk)).is_none());
+}
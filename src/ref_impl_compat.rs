@@ -31,6 +31,14 @@ const CONVERT_INV_PERMUTATION_INDICES: [usize; 256] = [41, 189, 103, 240, 28, 13
     247, 24, 130, 86, 201, 61, 167, 112, 236, 6, 145, 77, 219, 32, 180, 106, 254, 21, 139, 95, 196, 50, 174, 121, 227, 15, 152, 
     66, 214];
 
+/// The index in our chinese remainder representation of the value at the given index of
+/// theirs
+#[allow(unused)]
+pub fn our_zeta_index(their_index: usize) -> usize
+{
+    CONVERT_PERMUTATION_INDICES[their_index]
+}
+
 #[allow(unused)]
 pub fn convert_their_crt_our_crt(values: [ZqElement; 256]) -> [ZqElement; 256]
 {
@@ -130,6 +130,16 @@ impl<'a> From<&'a [i16]> for RqElementCoefficientReprImpl
     }
 }
 
+impl<'a> From<&'a [ZqElement]> for RqElementCoefficientReprImpl
+{
+    fn from(value: &'a [ZqElement]) -> RqElementCoefficientReprImpl {
+        assert_eq!(N, value.len());
+        return RqElementCoefficientReprImpl {
+            data: util::create_array(|i| value[i])
+        };
+    }
+}
+
 impl From<[ZqElement; N]> for RqElementCoefficientReprImpl
 {
     #[inline(always)]
@@ -182,8 +192,10 @@ impl RqElementCoefficientRepr for RqElementCoefficientReprImpl
             values: result_values
         }
     }
+}
 
-    
+impl RqElementCompression for RqElementCoefficientReprImpl
+{
     fn compress<const D: u16>(&self) -> CompressedRq<D>
     {
         let mut data = [CompressedZq::zero(); N];
@@ -437,14 +449,11 @@ impl encoding::Encodable for RqElementChineseRemainderReprImpl
         }
     }
 
-    fn decode<T: encoding::Decoder>(data: &mut T) -> Self
+    fn try_decode<T: encoding::Decoder>(data: &mut T) -> Option<Self>
     {
-        RqElementChineseRemainderReprImpl {
-            values: util::create_array(|_i| {
-                let data_bits = data.read_bits(ENCODE_BITS).expect("Input too short");
-                ZqElement::from_perfect(data_bits as i16)
-            })
-        }
+        Some(RqElementChineseRemainderReprImpl {
+            values: util::try_create_array(|_i| ZqElement::try_decode(data).ok_or(())).ok()?
+        })
     }
 }
 
@@ -459,6 +468,17 @@ impl<'a> From<&'a [i16]> for RqElementChineseRemainderReprImpl
     }
 }
 
+impl<'a> From<&'a [ZqElement]> for RqElementChineseRemainderReprImpl
+{
+    fn from(value: &'a [ZqElement]) -> Self
+    {
+        assert_eq!(N, value.len());
+        RqElementChineseRemainderReprImpl {
+            values: util::create_array(|i| value[i])
+        }
+    }
+}
+
 impl From<[ZqElement; N]> for RqElementChineseRemainderReprImpl
 {
     fn from(value: [ZqElement; N]) -> Self
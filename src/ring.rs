@@ -6,30 +6,39 @@ use std::convert::From;
 
 use super::encoding;
 use super::util;
+use super::ring_params::{ RingParams, KyberParams };
 
 ///Degree of the ring extension
-pub const N: usize = 256;
+pub const N: usize = KyberParams::N;
 
-/// Elements of the ring Rq := Zq[X] / (X^N + 1)
-pub trait RqElementCoefficientRepr: Eq + Clone + 
-    for<'a> From<&'a [i16]> + From<[ZqElement; 256]> +
+/// Elements of the ring Rq := Zq[X] / (X^N + 1), where q = P::Q and N = P::N, e.g. the
+/// ones of generic_ring.rs. As array lengths cannot depend on P, the coefficients are given
+/// as slices of length P::N.
+pub trait RqElementCoefficientRepr<P: RingParams = KyberParams>: Eq + Clone + 
+    for<'a> From<&'a [i16]> + for<'a> From<&'a [Zq<P>]> +
     for<'a> Add<&'a Self, Output = Self> + 
     for<'a> Sub<&'a Self, Output = Self> + 
-    Mul<ZqElement, Output = Self> +
+    Mul<Zq<P>, Output = Self> +
     for<'a> AddAssign<&'a Self> + 
     for<'a> SubAssign<&'a Self> + 
-    MulAssign<ZqElement>
+    MulAssign<Zq<P>>
 {
-    type ChineseRemainderRepr: RqElementChineseRemainderRepr<CoefficientRepr = Self>;
+    type ChineseRemainderRepr: RqElementChineseRemainderRepr<P, CoefficientRepr = Self>;
 
     fn get_zero() -> Self;
     fn to_chinese_remainder_repr(self) -> Self::ChineseRemainderRepr;
+}
+
+/// Compression of the elements of the ring of degree N = 256 used by kyber.rs, which is not
+/// part of RqElementCoefficientRepr, as CompressedRq has this fixed length
+pub trait RqElementCompression: RqElementCoefficientRepr<KyberParams>
+{
     fn compress<const D: u16>(&self) -> CompressedRq<D>;
     fn decompress<const D: u16>(x: &CompressedRq<D>) -> Self;
 }
 
-pub trait RqElementChineseRemainderRepr: Eq + Clone + encoding::Encodable +
-    for<'a> From<&'a [i16]> + From<[ZqElement; 256]> +
+pub trait RqElementChineseRemainderRepr<P: RingParams = KyberParams>: Eq + Clone + encoding::Encodable +
+    for<'a> From<&'a [i16]> + for<'a> From<&'a [Zq<P>]> +
     for<'a> Add<&'a Self, Output = Self> + 
     for<'a> Sub<&'a Self, Output = Self> + 
     for<'a> Mul<&'a Self, Output = Self> + 
@@ -37,12 +46,12 @@ pub trait RqElementChineseRemainderRepr: Eq + Clone + encoding::Encodable +
     for<'a> SubAssign<&'a Self> +
     for<'a> MulAssign<&'a Self>
 {
-    type CoefficientRepr: RqElementCoefficientRepr<ChineseRemainderRepr = Self>;
+    type CoefficientRepr: RqElementCoefficientRepr<P, ChineseRemainderRepr = Self>;
 
     fn get_zero() -> Self;
     fn to_coefficient_repr(self) -> Self::CoefficientRepr;
-    fn value_at_zeta(&self, zeta_index: usize) -> ZqElement;
-    fn mul_scalar(&mut self, x: ZqElement);
+    fn value_at_zeta(&self, zeta_index: usize) -> Zq<P>;
+    fn mul_scalar(&mut self, x: Zq<P>);
     /// More efficient but semantically equivalent to `self += a * b`
     fn add_product(&mut self, a: &Self, b: &Self);
 }
@@ -64,11 +73,11 @@ impl<const D: u16> encoding::Encodable for CompressedRq<D>
         }
     }
 
-    fn decode<T: encoding::Decoder>(data: &mut T) -> Self
+    fn try_decode<T: encoding::Decoder>(data: &mut T) -> Option<Self>
     {
-        CompressedRq {
-            data: util::create_array(|_i| CompressedZq::decode(data))
-        }
+        Some(CompressedRq {
+            data: util::try_create_array(|_i| CompressedZq::try_decode(data).ok_or(())).ok()?
+        })
     }
 }
 
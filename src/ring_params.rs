@@ -0,0 +1,149 @@
+//! The parameters of a ring Rq := Zq[X] / (X^N + 1), which are used to instantiate `Zq`
+//! and the Rq traits. The fast implementations in ref_r.rs and avx_r.rs use the hard-coded
+//! tables of zq.rs and are therefore restricted to KyberParams, whereas generic_ring.rs
+//! works for all parameters and computes its tables when it is created.
+
+use super::zq::Zq;
+use super::generic_ring::Ring;
+
+use std::fmt::Debug;
+use std::sync::atomic::AtomicPtr;
+use std::ptr;
+
+pub trait RingParams: Clone + Copy + PartialEq + Eq + Debug + Send + Sync + 'static
+{
+    /// The prime modulus q, which must be smaller than 2^14 so that products of two
+    /// elements fit into u32 and representatives into i16
+    const Q: u32;
+    /// The degree N of X^N + 1, which must be a power of two
+    const N: usize;
+    /// A primitive 2N-th root of unity in Zq, so that X^N + 1 splits into linear factors.
+    /// Its powers are the twiddle factors of the NTT.
+    const ROOT_OF_UNITY: u32;
+
+    /// The Ring of generic_ring.rs for these parameters, which is created by the first call
+    /// and then shared by all threads; implemented using a static AtomicPtr and
+    /// Ring::get_or_create()
+    fn ring() -> &'static Ring<Self>;
+
+    /// The count of bits we write when encoding an element of Zq
+    fn encode_bits() -> usize
+    {
+        (32 - (Self::Q - 1).leading_zeros()) as usize
+    }
+}
+
+/// q = 7681 and N = 256, as in the first round submission of Kyber
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KyberParams;
+
+impl RingParams for KyberParams
+{
+    const Q: u32 = 7681;
+    const N: usize = 256;
+    const ROOT_OF_UNITY: u32 = 1704;
+
+    fn ring() -> &'static Ring<KyberParams>
+    {
+        static RING: AtomicPtr<Ring<KyberParams>> = AtomicPtr::new(ptr::null_mut());
+        Ring::get_or_create(&RING)
+    }
+}
+
+/// q = 12289 and N = 1024, as in NewHope
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct NewHopeParams;
+
+impl RingParams for NewHopeParams
+{
+    const Q: u32 = 12289;
+    const N: usize = 1024;
+    const ROOT_OF_UNITY: u32 = 7;
+
+    fn ring() -> &'static Ring<NewHopeParams>
+    {
+        static RING: AtomicPtr<Ring<NewHopeParams>> = AtomicPtr::new(ptr::null_mut());
+        Ring::get_or_create(&RING)
+    }
+}
+
+/// The ring of the toy parameter set params::TOY16
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Toy16Params;
+
+impl RingParams for Toy16Params
+{
+    const Q: u32 = 257;
+    const N: usize = 16;
+    const ROOT_OF_UNITY: u32 = 136;
+
+    fn ring() -> &'static Ring<Toy16Params>
+    {
+        static RING: AtomicPtr<Ring<Toy16Params>> = AtomicPtr::new(ptr::null_mut());
+        Ring::get_or_create(&RING)
+    }
+}
+
+/// The ring of the toy parameter set params::TOY64
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Toy64Params;
+
+impl RingParams for Toy64Params
+{
+    const Q: u32 = 769;
+    const N: usize = 64;
+    const ROOT_OF_UNITY: u32 = 761;
+
+    fn ring() -> &'static Ring<Toy64Params>
+    {
+        static RING: AtomicPtr<Ring<Toy64Params>> = AtomicPtr::new(ptr::null_mut());
+        Ring::get_or_create(&RING)
+    }
+}
+
+/// Returns the powers w^0, ..., w^(2N - 1) of the root of unity w = P::ROOT_OF_UNITY
+pub fn unity_roots<P: RingParams>() -> Vec<Zq<P>>
+{
+    let root = Zq::<P>::from_perfect(P::ROOT_OF_UNITY as i16);
+    let mut result = Vec::with_capacity(2 * P::N);
+    let mut power = Zq::one();
+    for _ in 0..2 * P::N {
+        result.push(power);
+        power *= root;
+    }
+    return result;
+}
+
+/// Checks the requirements on the parameters documented in RingParams
+pub fn check_params<P: RingParams>() -> bool
+{
+    let root = Zq::<P>::from_perfect(P::ROOT_OF_UNITY as i16);
+    P::Q < (1 << 14) && P::N.is_power_of_two() && (P::Q - 1) % (2 * P::N as u32) == 0 &&
+        (2..P::Q).all(|d| d * d > P::Q || P::Q % d != 0) &&
+        root.pow(P::N) == -Zq::one()
+}
+
+#[cfg(test)]
+use super::zq::{ UNITY_ROOTS_512, REV_UNITY_ROOTS_512, ZqElement, ENCODE_BITS, ONE };
+
+#[test]
+fn test_check_params()
+{
+    assert!(check_params::<KyberParams>());
+    assert!(check_params::<NewHopeParams>());
+    assert!(check_params::<Toy16Params>());
+    assert!(check_params::<Toy64Params>());
+    assert_eq!(ENCODE_BITS, KyberParams::encode_bits());
+    assert_eq!(14, NewHopeParams::encode_bits());
+}
+
+#[test]
+fn test_unity_roots_match_tables()
+{
+    let roots = unity_roots::<KyberParams>();
+    assert_eq!(&UNITY_ROOTS_512[..], &roots[..]);
+    for i in 0..256 {
+        assert_eq!(ONE, REV_UNITY_ROOTS_512[i] * roots[i]);
+    }
+    assert_eq!(ZqElement::from(1704), roots[1]);
+}
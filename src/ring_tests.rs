@@ -0,0 +1,274 @@
+//! Property tests of the algebraic laws that all implementations of Zq and Rq have to
+//! satisfy, checked on pseudo-random inputs. The inputs are derived from fixed seeds using
+//! SHAKE-256, so each run checks the same inputs and failures can be reproduced.
+
+use super::zq::*;
+use super::ring::*;
+use super::ring_params::{ RingParams, KyberParams, NewHopeParams, Toy16Params, Toy64Params, unity_roots };
+use super::generic_ring;
+use super::coeff_r;
+use super::kyber::{ expand_randomness_shake_256, Seed };
+use super::ref_impl_compat;
+use super::util;
+use super::io;
+use super::encoding::Encodable;
+
+use sha3::digest::XofReader;
+
+/// The count of random inputs for each law
+const ITERATIONS: usize = 8;
+
+/// Produces pseudo-random elements from a fixed seed
+struct TestRng
+{
+    reader: sha3::Sha3XofReader
+}
+
+impl TestRng
+{
+    fn new(seed: u8) -> TestRng
+    {
+        let mut seed_bytes: Seed = [0x3C; 32];
+        seed_bytes[0] = seed;
+        TestRng {
+            reader: expand_randomness_shake_256(seed_bytes)
+        }
+    }
+
+    fn next_u16(&mut self) -> u16
+    {
+        let mut buffer = [0; 2];
+        self.reader.read(&mut buffer);
+        return u16::from_le_bytes(buffer);
+    }
+
+    /// Uniform in Zq, by rejection sampling
+    fn zq<P: RingParams>(&mut self) -> Zq<P>
+    {
+        let mask = (1 << P::encode_bits()) - 1;
+        loop {
+            let value = self.next_u16() & mask;
+            if (value as u32) < P::Q {
+                return Zq::from_perfect(value as i16);
+            }
+        }
+    }
+
+    fn coefficients<P: RingParams>(&mut self) -> Vec<Zq<P>>
+    {
+        (0..P::N).map(|_| self.zq()).collect()
+    }
+}
+
+fn zq_laws<P: RingParams>(seed: u8)
+{
+    let mut rng = TestRng::new(seed);
+    let zero = Zq::<P>::zero();
+    let one = Zq::<P>::one();
+    assert_eq!(zero, -zero);
+    for _ in 0..ITERATIONS * 32 {
+        let (a, b, c): (Zq<P>, Zq<P>, Zq<P>) = (rng.zq(), rng.zq(), rng.zq());
+        assert_eq!(a + b, b + a);
+        assert_eq!(a * b, b * a);
+        assert_eq!((a + b) + c, a + (b + c));
+        assert_eq!((a * b) * c, a * (b * c));
+        assert_eq!(a * (b + c), a * b + a * c);
+        assert_eq!(a, a + zero);
+        assert_eq!(a, a * one);
+        assert_eq!(zero, a + (-a));
+        assert_eq!(a, -(-a));
+        assert_eq!(a - b, a + (-b));
+        if b != zero {
+            assert_eq!(a, a / b * b);
+        }
+        assert_eq!(a * a * a, a.pow(3));
+        assert_eq!(a, Zq::from(a.representative_pos()));
+        assert_eq!(a, Zq::from(a.representative_posneg()));
+        assert!(a.representative_posneg().abs() as u32 <= P::Q / 2);
+    }
+}
+
+/// The distance of a and b in Zq, i.e. the absolute value of the centered representative
+/// of a - b
+fn distance<P: RingParams>(a: Zq<P>, b: Zq<P>) -> u32
+{
+    (a - b).representative_posneg().abs() as u32
+}
+
+/// The error of compressing to d bits is at most q/2^(d + 1), plus 1/2 for rounding the
+/// decompressed value to an integer, i.e. at most round(q/2^(d + 1)) as stated in the
+/// Kyber specification
+fn compression_bound<P: RingParams>(d: u16) -> u32
+{
+    (P::Q as f64 / (1u32 << (d + 1)) as f64).round() as u32
+}
+
+fn check_compression_error<P: RingParams, const D: u16>()
+{
+    for x in 0..P::Q {
+        let value = Zq::<P>::from_perfect(x as i16);
+        let compressed = value.compress::<D>();
+        assert!((compressed.data as u32) < (1 << D));
+        let error = distance(value, Zq::decompress(compressed));
+        assert!(error <= compression_bound::<P>(D), "Error {} too big for x = {}, d = {}", error, x, D);
+    }
+}
+
+fn compression_laws<P: RingParams>()
+{
+    check_compression_error::<P, 1>();
+    check_compression_error::<P, 3>();
+    check_compression_error::<P, 4>();
+    check_compression_error::<P, 6>();
+    check_compression_error::<P, 10>();
+    check_compression_error::<P, 11>();
+    for x in 0..(1 << 11) {
+        // decompressing and compressing again is the identity if 2^d < q
+        let compressed = CompressedZq::<11> { data: x };
+        assert_eq!(x, Zq::<KyberParams>::decompress(compressed).compress::<11>().data);
+    }
+}
+
+/// Evaluates the polynomial with the given coefficients at x
+fn evaluate<P: RingParams>(coefficients: &[Zq<P>], x: Zq<P>) -> Zq<P>
+{
+    coefficients.iter().rev().fold(Zq::zero(), |current, coefficient| current * x + *coefficient)
+}
+
+/// The index of the root of unity at which value_at_zeta(zeta_index) of ref_r.rs, avx_r.rs
+/// and coeff_r.rs evaluates, i.e. value_at_zeta(zeta_index) = a(UNITY_ROOTS_512[root_index(zeta_index)])
+fn root_index(zeta_index: usize) -> usize
+{
+    let index = if cfg!(feature = "compatibility") { ref_impl_compat::our_zeta_index(zeta_index) } else { zeta_index };
+    return 2 * index + 1;
+}
+
+/// The laws that hold for all implementations of the Rq traits, for any degree P::N, where
+/// value_at_zeta(zeta_index) evaluates at w^root_index(zeta_index) for w = P::ROOT_OF_UNITY
+fn ring_laws<P: RingParams, R: RqElementCoefficientRepr<P>>(seed: u8, root_index: fn(usize) -> usize)
+{
+    let mut rng = TestRng::new(seed);
+    let zero = R::get_zero();
+    let crt_zero = R::ChineseRemainderRepr::get_zero();
+    let mut one_coefficients = vec![Zq::zero(); P::N];
+    one_coefficients[0] = Zq::one();
+    let one = R::from(&one_coefficients[..]).to_chinese_remainder_repr();
+    let unity_roots = unity_roots::<P>();
+    for _ in 0..ITERATIONS {
+        let (a_coefficients, b_coefficients, c_coefficients) = (rng.coefficients::<P>(), rng.coefficients::<P>(), rng.coefficients::<P>());
+        let (a, b, c) = (R::from(&a_coefficients[..]), R::from(&b_coefficients[..]), R::from(&c_coefficients[..]));
+        let scalar: Zq<P> = rng.zq();
+
+        // additive group and scalar multiplication on the coefficients
+        assert!(a.clone() + &b == b.clone() + &a);
+        assert!((a.clone() + &b) + &c == a.clone() + &(b.clone() + &c));
+        assert!(a.clone() + &zero == a);
+        assert!((a.clone() + &b) - &b == a);
+        assert!(a.clone() - &a == zero);
+        assert!(a.clone() * scalar == R::from(&a_coefficients.iter().map(|x| *x * scalar).collect::<Vec<_>>()[..]));
+        assert!((a.clone() + &b) * scalar == (a.clone() * scalar) + &(b.clone() * scalar));
+
+        // NTT round trip and the ring laws in chinese remainder representation
+        assert!(a.clone().to_chinese_remainder_repr().to_coefficient_repr() == a);
+        let (x, y, z) = (a.clone().to_chinese_remainder_repr(), b.clone().to_chinese_remainder_repr(), c.clone().to_chinese_remainder_repr());
+        assert!(x.clone() + &y == (a.clone() + &b).to_chinese_remainder_repr());
+        assert!(x.clone() - &y == (a.clone() - &b).to_chinese_remainder_repr());
+        assert!(x.clone() * &y == y.clone() * &x);
+        assert!((x.clone() * &y) * &z == x.clone() * &(y.clone() * &z));
+        assert!(x.clone() * &(y.clone() + &z) == (x.clone() * &y) + &(x.clone() * &z));
+        assert!(x.clone() * &one == x);
+        assert!(x.clone() * &crt_zero == crt_zero);
+
+        // add_product and mul_scalar
+        let mut sum = z.clone();
+        sum.add_product(&x, &y);
+        assert!(sum == z.clone() + &(x.clone() * &y));
+        let mut scaled = x.clone();
+        scaled.mul_scalar(scalar);
+        assert!(scaled.to_coefficient_repr() == a.clone() * scalar);
+
+        // value_at_zeta is the evaluation at a primitive 2N-th root of unity, and the
+        // product is the pointwise product of these values
+        for zeta_index in [0, 1, 2, P::N / 2 - 1, P::N / 2, P::N - 1].iter() {
+            let root = unity_roots[root_index(*zeta_index)];
+            assert_eq!(evaluate(&a_coefficients, root), x.value_at_zeta(*zeta_index));
+            assert_eq!(x.value_at_zeta(*zeta_index) * y.value_at_zeta(*zeta_index), (x.clone() * &y).value_at_zeta(*zeta_index));
+        }
+
+        // the encoding of the chinese remainder representation can be decoded
+        let mut encoded = Vec::new();
+        x.encode(&mut io::bytes_encode(&mut encoded));
+        assert_eq!(P::N * P::encode_bits(), encoded.len() * 8);
+        assert!(R::ChineseRemainderRepr::try_decode(&mut io::bytes_decode(&encoded)) == Some(x));
+    }
+}
+
+fn rq_laws<R: RqElementCompression>(seed: u8)
+{
+    ring_laws::<KyberParams, R>(seed, root_index);
+    let mut rng = TestRng::new(seed);
+    for _ in 0..ITERATIONS {
+        // compression works coefficient-wise
+        let a_coefficients = rng.coefficients::<KyberParams>();
+        let a = R::from(&a_coefficients[..]);
+        let compressed = a.compress::<11>();
+        assert!((0..N).all(|i| compressed.data[i].data == a_coefficients[i].compress::<11>().data));
+        let decompressed: [ZqElement; N] = util::create_array(|i| ZqElement::decompress(compressed.data[i]));
+        assert!(R::decompress(&compressed) == R::from(&decompressed[..]));
+        assert!((0..N).all(|i| distance(a_coefficients[i], decompressed[i]) <= compression_bound::<KyberParams>(11)));
+    }
+}
+
+fn generic_ring_laws<P: RingParams>(seed: u8)
+{
+    let mut rng = TestRng::new(seed);
+    let ring = generic_ring::Ring::<P>::new();
+    let random = |rng: &mut TestRng| generic_ring::Element::from((0..P::N).map(|_| rng.zq::<P>()).collect::<Vec<_>>());
+    for _ in 0..ITERATIONS {
+        let (a, b, c) = (random(&mut rng), random(&mut rng), random(&mut rng));
+        assert_eq!(a, ring.from_ntt(ring.to_ntt(a.clone())));
+        assert_eq!(ring.mul(&a, &b), ring.mul(&b, &a));
+        assert_eq!(ring.mul(&ring.mul(&a, &b), &c), ring.mul(&a, &ring.mul(&b, &c)));
+        assert_eq!(ring.mul(&a, &(b.clone() + &c)), ring.mul(&a, &b) + &ring.mul(&a, &c));
+    }
+}
+
+#[test]
+fn test_zq_laws()
+{
+    zq_laws::<KyberParams>(0);
+    zq_laws::<NewHopeParams>(1);
+    zq_laws::<Toy16Params>(2);
+    zq_laws::<Toy64Params>(3);
+}
+
+#[test]
+fn test_compression_error()
+{
+    compression_laws::<KyberParams>();
+    compression_laws::<NewHopeParams>();
+}
+
+#[test]
+fn test_rq_laws()
+{
+    // one of the NTT-based implementations ref_r.rs and avx_r.rs, or coeff_r.rs if the
+    // feature coefficient_domain is enabled
+    rq_laws::<super::kyber::RqElement>(10);
+    rq_laws::<coeff_r::RqElementCoefficientReprImpl<coeff_r::Schoolbook>>(11);
+    rq_laws::<coeff_r::RqElementCoefficientReprImpl<coeff_r::Karatsuba>>(12);
+    rq_laws::<coeff_r::RqElementCoefficientReprImpl<coeff_r::ToomCook4>>(13);
+}
+
+#[test]
+fn test_generic_ring_laws()
+{
+    generic_ring_laws::<Toy16Params>(20);
+    generic_ring_laws::<Toy64Params>(21);
+    generic_ring_laws::<KyberParams>(22);
+    generic_ring_laws::<NewHopeParams>(23);
+    // the Rq traits, with the values at w^(2i + 1) in the natural order
+    ring_laws::<Toy16Params, generic_ring::Element<Toy16Params>>(24, |i| 2 * i + 1);
+    ring_laws::<Toy64Params, generic_ring::Element<Toy64Params>>(25, |i| 2 * i + 1);
+    ring_laws::<NewHopeParams, generic_ring::Element<NewHopeParams>>(26, |i| 2 * i + 1);
+}
@@ -27,11 +27,11 @@ impl<R: RqElementCoefficientRepr> encoding::Encodable for RqVector3<R>
         }
     }
 
-    fn decode<T: encoding::Decoder>(data: &mut T) -> Self
+    fn try_decode<T: encoding::Decoder>(data: &mut T) -> Option<Self>
     {
-        RqVector3 {
-            data: util::create_array(|_i| R::ChineseRemainderRepr::decode(data))
-        }
+        Some(RqVector3 {
+            data: util::try_create_array(|_i| R::ChineseRemainderRepr::try_decode(data).ok_or(())).ok()?
+        })
     }
 }
 
@@ -277,15 +277,15 @@ impl<const D: u16> encoding::Encodable for CompressedRqVector<D>
         }
     }
 
-    fn decode<T: encoding::Decoder>(data: &mut T) -> Self
+    fn try_decode<T: encoding::Decoder>(data: &mut T) -> Option<Self>
     {
-        CompressedRqVector {
-            data: util::create_array(|_i| CompressedRq::decode(data))
-        }
+        Some(CompressedRqVector {
+            data: util::try_create_array(|_i| CompressedRq::try_decode(data).ok_or(())).ok()?
+        })
     }
 }
 
-impl<T: RqElementCoefficientRepr> RqVector3<T>
+impl<T: RqElementCompression> RqVector3<T>
 {
     pub fn compress<const D: u16>(self) -> CompressedRqVector<D>
     {
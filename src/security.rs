@@ -0,0 +1,180 @@
+//! Estimates the hardness of the MLWE instance of a parameter set in the core-SVP model,
+//! following the methodology of the Kyber submission (and of NewHope): An attack that
+//! needs BKZ with block size b costs as much as one call to an SVP oracle in dimension b,
+//! which takes 2^(0.292 b) operations classically and 2^(0.265 b) with a quantum
+//! computer (using the best known sieving algorithms). All lower order terms are
+//! ignored, so this is a conservative estimate.
+//!
+//! The MLWE instance is treated as a LWE instance of dimension k * n with up to k * n
+//! samples and secret and error of standard deviation sqrt(eta / 2).
+
+use super::params::ParameterSet;
+
+use std::f64::consts::{ PI, E };
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Attack
+{
+    /// Finds the short vector (s, e, 1) in a lattice given by the samples (uSVP)
+    Primal,
+    /// Finds a short vector in the dual lattice, to distinguish the samples from uniform
+    Dual
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CostModel
+{
+    Classical,
+    Quantum
+}
+
+impl CostModel
+{
+    /// log2 of the cost of the SVP oracle in dimension b
+    pub fn svp_cost(&self, block_size: usize) -> f64
+    {
+        match self {
+            CostModel::Classical => 0.292 * block_size as f64,
+            CostModel::Quantum => 0.265 * block_size as f64
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AttackEstimate
+{
+    pub attack: Attack,
+    pub model: CostModel,
+    pub block_size: usize,
+    /// The count of LWE samples used by the attack
+    pub samples: usize,
+    /// log2 of the cost of the attack
+    pub bits: f64
+}
+
+/// The smallest block size considered
+const MIN_BLOCK_SIZE: usize = 50;
+
+/// log of the root Hermite factor achieved by BKZ with the given block size
+fn log_root_hermite_factor(block_size: usize) -> f64
+{
+    let b = block_size as f64;
+    return ((PI * b).ln() / b + (b / (2. * PI * E)).ln()) / (2. * (b - 1.));
+}
+
+fn lwe_dimension(params: &ParameterSet) -> usize
+{
+    params.k * params.n
+}
+
+fn standard_deviation(params: &ParameterSet) -> f64
+{
+    (params.eta as f64 / 2.).sqrt()
+}
+
+/// Whether BKZ with the given block size finds the unique shortest vector of the embedding
+/// lattice of dimension d = n + m + 1, i.e. whether the projection of the secret onto the
+/// last b Gram-Schmidt vectors is shorter than the b-th last Gram-Schmidt norm
+fn primal_succeeds(params: &ParameterSet, samples: usize, block_size: usize) -> bool
+{
+    let n = lwe_dimension(params) as f64;
+    let m = samples as f64;
+    let b = block_size as f64;
+    let d = n + m + 1.;
+    let q = params.q as f64;
+    return standard_deviation(params).ln() + b.ln() / 2. <
+        (2. * b - d - 1.) * log_root_hermite_factor(block_size) + m / d * q.ln();
+}
+
+/// log2 of the cost of the dual attack, which needs about 1/eps^2 short dual vectors to
+/// distinguish with advantage eps, where each run of the sieve yields 2^(0.2075 b) of them
+fn dual_cost(params: &ParameterSet, samples: usize, block_size: usize, model: CostModel) -> f64
+{
+    let n = lwe_dimension(params) as f64;
+    let m = samples as f64;
+    let d = n + m;
+    let q = params.q as f64;
+    let log_length = (d - 1.) * log_root_hermite_factor(block_size) + n / d * q.ln();
+    let tau = log_length.exp() * standard_deviation(params) / q;
+    let log2_advantage = -2. * PI * PI * tau * tau / 2f64.ln();
+    let log2_vectors_per_sieve = block_size as f64 * (4f64 / 3.).sqrt().log2();
+    let log2_repetitions = (-2. * log2_advantage - log2_vectors_per_sieve).max(0.);
+    return model.svp_cost(block_size) + log2_repetitions;
+}
+
+/// Finds the cheapest parameters of the given attack
+pub fn estimate_attack(params: &ParameterSet, attack: Attack, model: CostModel) -> AttackEstimate
+{
+    let n = lwe_dimension(params);
+    let max_samples = n;
+    let mut best = AttackEstimate {
+        attack: attack,
+        model: model,
+        block_size: n + max_samples + 1,
+        samples: max_samples,
+        bits: std::f64::INFINITY
+    };
+    for block_size in MIN_BLOCK_SIZE..=(n + max_samples) {
+        if model.svp_cost(block_size) >= best.bits {
+            break;
+        }
+        for samples in block_size.saturating_sub(n).max(1)..=max_samples {
+            let bits = match attack {
+                Attack::Primal if primal_succeeds(params, samples, block_size) => model.svp_cost(block_size),
+                Attack::Primal => continue,
+                Attack::Dual => dual_cost(params, samples, block_size, model)
+            };
+            if bits < best.bits {
+                best.block_size = block_size;
+                best.samples = samples;
+                best.bits = bits;
+            }
+        }
+    }
+    return best;
+}
+
+/// The estimates of the primal and dual attack, classically and quantumly
+pub fn estimate(params: &ParameterSet) -> Vec<AttackEstimate>
+{
+    let mut result = Vec::new();
+    for attack in [Attack::Primal, Attack::Dual].iter() {
+        for model in [CostModel::Classical, CostModel::Quantum].iter() {
+            result.push(estimate_attack(params, *attack, *model));
+        }
+    }
+    return result;
+}
+
+/// The cost of the cheapest attack in the given model
+pub fn security_bits(estimates: &[AttackEstimate], model: CostModel) -> f64
+{
+    estimates.iter().filter(|estimate| estimate.model == model).map(|estimate| estimate.bits).fold(std::f64::INFINITY, f64::min)
+}
+
+#[cfg(test)]
+use super::params;
+
+#[test]
+fn test_root_hermite_factor()
+{
+    let delta = log_root_hermite_factor(250).exp();
+    assert!((delta - 1.00546).abs() < 1e-5);
+    assert!(log_root_hermite_factor(500) < log_root_hermite_factor(250));
+}
+
+#[test]
+fn test_estimates_of_submission()
+{
+    // the submission states a quantum core-SVP hardness of 102, 161 and 218 bits
+    let expected = [102., 161., 218.];
+    for (params, quantum) in params::PARAMETER_SETS.iter().zip(expected.iter()) {
+        let estimates = estimate(params);
+        assert_eq!(4, estimates.len());
+        assert!((security_bits(&estimates, CostModel::Quantum) - quantum).abs() < 1.5, "{}: {:?}", params.name, estimates);
+        assert!(security_bits(&estimates, CostModel::Classical) > security_bits(&estimates, CostModel::Quantum));
+        let primal = estimate_attack(params, Attack::Primal, CostModel::Classical);
+        assert!(primal_succeeds(params, primal.samples, primal.block_size));
+        assert!(!primal_succeeds(params, primal.samples, primal.block_size - 1));
+    }
+}
@@ -0,0 +1,474 @@
+//! A straightforward implementation of Kyber.CPA for all parameter sets of params.rs, with
+//! which the algebra can be followed by hand for tiny (insecure) parameter sets like
+//! params::TOY16. The dimension k and the compression are given at runtime, the ring
+//! arithmetic is done by generic_ring.rs for the RingParams P of the parameter set, e.g.
+//! ring_params::Toy16Params, and every intermediate value can be written to a trace.
+//!
+//! Sampling, rounding and the byte encodings work as in kyber.rs, in particular the matrix A
+//! is sampled in the NTT domain and the secret key is stored in the NTT domain. Hence, for
+//! the parameter set Kyber768, the keys and ciphertexts are the same as the ones of
+//! kyber.rs (unless the feature compatibility changes the order of the NTT values there).
+
+use super::params::ParameterSet;
+use super::zq::Zq;
+use super::ring_params::RingParams;
+use super::generic_ring::{ Ring, Element, NttElement };
+use super::encoding::Encoder;
+use super::io;
+
+use sha3::digest::{ ExtendableOutput, Input, XofReader };
+
+use std::io::Write;
+
+pub type Seed = [u8; 32];
+
+/// A polynomial of degree less than n, with coefficients in 0..q
+pub type Poly = Vec<u32>;
+
+/// The vector t of the public key compressed to dt bits, and the seed of A
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublicKey
+{
+    pub t: Vec<Vec<u16>>,
+    pub seed: Seed
+}
+
+/// The vector s in the NTT domain, as the secret keys of kyber.rs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecretKey<P: RingParams>
+{
+    pub s: Vec<NttElement<P>>
+}
+
+/// The vector u compressed to du bits and the element v compressed to dv bits
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ciphertext
+{
+    pub u: Vec<Vec<u16>>,
+    pub v: Vec<u16>
+}
+
+/// Receives the intermediate values of the computations, if enabled
+pub struct Trace<'a>
+{
+    output: Option<&'a mut dyn Write>
+}
+
+impl<'a> Trace<'a>
+{
+    pub fn disabled() -> Trace<'a>
+    {
+        Trace {
+            output: None
+        }
+    }
+
+    pub fn new(output: &'a mut dyn Write) -> Trace<'a>
+    {
+        Trace {
+            output: Some(output)
+        }
+    }
+
+    /// Writing the trace is best effort, so errors are ignored
+    fn line(&mut self, line: std::fmt::Arguments)
+    {
+        if let Some(output) = &mut self.output {
+            let _ = output.write_fmt(line);
+            let _ = output.write_all(b"\n");
+        }
+    }
+
+    fn heading(&mut self, title: &str)
+    {
+        self.line(format_args!("== {} ==", title));
+    }
+
+    fn poly<T: std::fmt::Display>(&mut self, name: &str, poly: &[T])
+    {
+        let values: Vec<String> = poly.iter().map(|x| x.to_string()).collect();
+        self.line(format_args!("{} = [{}]", name, values.join(", ")));
+    }
+
+    fn vector<T: std::fmt::Display>(&mut self, name: &str, vector: &[Vec<T>])
+    {
+        for (i, poly) in vector.iter().enumerate() {
+            self.poly(&format!("{}[{}]", name, i), poly);
+        }
+    }
+}
+
+fn reduce(x: i64, q: u32) -> u32
+{
+    x.rem_euclid(q as i64) as u32
+}
+
+/// The ring Zq[X] / (X^n + 1) of the parameter set, which must be the one of P
+fn create_ring<P: RingParams>(params: &ParameterSet) -> &'static Ring<P>
+{
+    assert!(P::Q == params.q && P::N == params.n, "The ring of {} is not the one of q = {} and N = {}", params.name, P::Q, P::N);
+    return P::ring();
+}
+
+fn to_element<P: RingParams>(poly: &[u32]) -> Element<P>
+{
+    Element::from(poly.iter().map(|x| Zq::from_perfect(*x as i16)).collect::<Vec<_>>())
+}
+
+fn to_poly<P: RingParams>(a: &Element<P>) -> Poly
+{
+    a.coefficients().iter().map(|x| x.representative_pos() as u32).collect()
+}
+
+fn inner_product<P: RingParams>(a: &[NttElement<P>], b: &[NttElement<P>]) -> NttElement<P>
+{
+    let mut result = NttElement::from(vec![Zq::zero(); P::N]);
+    for (x, y) in a.iter().zip(b.iter()) {
+        result += &(x.clone() * y);
+    }
+    return result;
+}
+
+/// Returns the y in 0..2^d such that q/2^d * y is nearest to x, as ZqElement::compress()
+pub fn compress(x: u32, d: u16, q: u32) -> u16
+{
+    let (x, q) = (x as u64, q as u64);
+    ((((x << (d + 1)) + q) / (2 * q)) % (1 << d)) as u16
+}
+
+/// Returns the element of Zq nearest to q/2^d * y, as ZqElement::decompress()
+pub fn decompress(y: u16, d: u16, q: u32) -> u32
+{
+    let (y, q) = (y as u64, q as u64);
+    ((((y * q) << 1) + (1 << d)) >> (d + 1)) as u32 % q as u32
+}
+
+/// The representatives in -q/2..=q/2, which are easier to read for small values
+fn centered(vector: &[Poly], q: u32) -> Vec<Vec<i64>>
+{
+    vector.iter().map(|poly| poly.iter().map(|x| if *x > q / 2 { *x as i64 - q as i64 } else { *x as i64 }).collect()).collect()
+}
+
+fn compress_poly(poly: &[u32], d: u16, q: u32) -> Vec<u16>
+{
+    poly.iter().map(|x| compress(*x, d, q)).collect()
+}
+
+fn decompress_poly(poly: &[u16], d: u16, q: u32) -> Poly
+{
+    poly.iter().map(|y| decompress(*y, d, q)).collect()
+}
+
+/// Samples uniform coefficients by rejection sampling of little endian 16 bit values,
+/// masked to the bit length of q, as kyber::sample_uniform_zq()
+fn sample_uniform<T: XofReader>(reader: &mut T, params: &ParameterSet) -> Poly
+{
+    let mask = (1u32 << params.coefficient_bits()) - 1;
+    let mut result = Vec::with_capacity(params.n);
+    let mut buffer = [0; 2];
+    while result.len() < params.n {
+        reader.read(&mut buffer);
+        let value = u16::from_le_bytes(buffer) as u32 & mask;
+        if value < params.q {
+            result.push(value);
+        }
+    }
+    return result;
+}
+
+/// Returns A in the NTT domain, where A[row][col] is derived from SHAKE-128(seed || col || row),
+/// as kyber::sample_uniform_matrix()
+fn sample_matrix<P: RingParams>(seed: &Seed, params: &ParameterSet) -> Vec<Vec<NttElement<P>>>
+{
+    (0..params.k).map(|row| (0..params.k).map(|col| {
+        let mut hasher = sha3::Shake128::default();
+        hasher.input(seed);
+        hasher.input(&[col as u8, row as u8]);
+        let values = sample_uniform(&mut hasher.xof_result(), params);
+        NttElement::from(values.iter().map(|x| Zq::from_perfect(*x as i16)).collect::<Vec<_>>())
+    }).collect()).collect()
+}
+
+/// Samples from the centered binomial distribution, using 2 * eta bits of the reader per
+/// coefficient as kyber::sample_centered_binomial_element()
+fn sample_centered_binomial<T: XofReader>(reader: &mut T, params: &ParameterSet) -> Poly
+{
+    let eta = params.eta as usize;
+    let mut bytes = vec![0; (params.n * 2 * eta + 7) / 8];
+    reader.read(&mut bytes);
+    let bit = |i: usize| ((bytes[i / 8] >> (i % 8)) & 1) as i64;
+    return (0..params.n).map(|i| {
+        let a: i64 = (0..eta).map(|j| bit(2 * eta * i + j)).sum();
+        let b: i64 = (0..eta).map(|j| bit(2 * eta * i + eta + j)).sum();
+        reduce(a - b, params.q)
+    }).collect();
+}
+
+fn sample_centered_binomial_vector<T: XofReader>(reader: &mut T, params: &ParameterSet) -> Vec<Poly>
+{
+    (0..params.k).map(|_| sample_centered_binomial(reader, params)).collect()
+}
+
+fn expand_randomness(seed: &Seed) -> sha3::Sha3XofReader
+{
+    let mut hasher = sha3::Shake256::default();
+    hasher.input(seed);
+    return hasher.xof_result();
+}
+
+/// The count of message bytes, i.e. one bit per coefficient
+pub fn message_bytes(params: &ParameterSet) -> usize
+{
+    params.n / 8
+}
+
+pub fn key_gen<P: RingParams>(params: &ParameterSet, matrix_seed: Seed, secret_seed: Seed, trace: &mut Trace) -> (SecretKey<P>, PublicKey)
+{
+    let q = params.q;
+    let ring = create_ring::<P>(params);
+    trace.heading("key generation");
+    let A = sample_matrix::<P>(&matrix_seed, params);
+    trace.line(format_args!("A is sampled in the NTT domain, its coefficients are"));
+    for (i, row) in A.iter().enumerate() {
+        let row: Vec<Poly> = row.iter().map(|a| to_poly(&ring.from_ntt(a.clone()))).collect();
+        trace.vector(&format!("A[{}]", i), &row);
+    }
+    let mut noise = expand_randomness(&secret_seed);
+    let s = sample_centered_binomial_vector(&mut noise, params);
+    let e = sample_centered_binomial_vector(&mut noise, params);
+    trace.vector("s", &centered(&s, q));
+    trace.vector("e", &centered(&e, q));
+    let s: Vec<NttElement<P>> = s.iter().map(|poly| ring.to_ntt(to_element(poly))).collect();
+    let t: Vec<Poly> = A.iter().zip(e.iter()).map(|(row, e)| to_poly(&(ring.from_ntt(inner_product(row, &s)) + &to_element(e)))).collect();
+    trace.vector("(A s + e)", &t);
+    let t: Vec<Vec<u16>> = t.iter().map(|poly| compress_poly(poly, params.dt, q)).collect();
+    trace.line(format_args!("t = compress_{}(A s + e)", params.dt));
+    trace.vector("t", &t);
+    return (SecretKey { s: s }, PublicKey { t: t, seed: matrix_seed });
+}
+
+/// Encrypts the first n bits of the message, where bit i is bit i % 8 of byte i / 8; the
+/// message must have at least message_bytes(params) bytes
+pub fn encrypt<P: RingParams>(params: &ParameterSet, pk: &PublicKey, message: &[u8], enc_seed: Seed, trace: &mut Trace) -> Ciphertext
+{
+    assert!(message.len() >= message_bytes(params), "The message has {} bytes, but {} requires {}", message.len(), params.name, message_bytes(params));
+    let q = params.q;
+    let ring = create_ring::<P>(params);
+    trace.heading("encryption");
+    let t: Vec<Poly> = pk.t.iter().map(|poly| decompress_poly(poly, params.dt, q)).collect();
+    trace.vector(&format!("decompress_{}(t)", params.dt), &t);
+    let A = sample_matrix::<P>(&pk.seed, params);
+    let mut noise = expand_randomness(&enc_seed);
+    let r = sample_centered_binomial_vector(&mut noise, params);
+    let e1 = sample_centered_binomial_vector(&mut noise, params);
+    let e2 = sample_centered_binomial(&mut noise, params);
+    trace.vector("r", &centered(&r, q));
+    trace.vector("e1", &centered(&e1, q));
+    trace.poly("e2", &centered(&[e2.clone()], q)[0]);
+
+    let bits: Vec<u16> = (0..params.n).map(|i| ((message[i / 8] >> (i % 8)) & 1) as u16).collect();
+    let m = decompress_poly(&bits, 1, q);
+    trace.poly("m", &bits);
+    trace.poly("decompress_1(m)", &m);
+
+    let t: Vec<NttElement<P>> = t.iter().map(|poly| ring.to_ntt(to_element(poly))).collect();
+    let r: Vec<NttElement<P>> = r.iter().map(|poly| ring.to_ntt(to_element(poly))).collect();
+    let u: Vec<Poly> = (0..params.k).map(|col| {
+        let column: Vec<NttElement<P>> = A.iter().map(|row| row[col].clone()).collect();
+        to_poly(&(ring.from_ntt(inner_product(&column, &r)) + &to_element(&e1[col])))
+    }).collect();
+    trace.vector("(A^T r + e1)", &u);
+    let v = to_poly(&(ring.from_ntt(inner_product(&t, &r)) + &to_element(&e2) + &to_element(&m)));
+    trace.poly("t^T r + e2 + decompress_1(m)", &v);
+
+    let u: Vec<Vec<u16>> = u.iter().map(|poly| compress_poly(poly, params.du, q)).collect();
+    let v = compress_poly(&v, params.dv, q);
+    trace.line(format_args!("u = compress_{}(A^T r + e1), v = compress_{}(t^T r + e2 + decompress_1(m))", params.du, params.dv));
+    trace.vector("u", &u);
+    trace.poly("v", &v);
+    return Ciphertext { u: u, v: v };
+}
+
+pub fn decrypt<P: RingParams>(params: &ParameterSet, sk: &SecretKey<P>, c: &Ciphertext, trace: &mut Trace) -> Vec<u8>
+{
+    let q = params.q;
+    let ring = create_ring::<P>(params);
+    trace.heading("decryption");
+    let u: Vec<Poly> = c.u.iter().map(|poly| decompress_poly(poly, params.du, q)).collect();
+    let v = decompress_poly(&c.v, params.dv, q);
+    trace.vector(&format!("decompress_{}(u)", params.du), &u);
+    trace.poly(&format!("decompress_{}(v)", params.dv), &v);
+    let u: Vec<NttElement<P>> = u.iter().map(|poly| ring.to_ntt(to_element(poly))).collect();
+    let noisy = to_poly(&(to_element::<P>(&v) - &ring.from_ntt(inner_product(&sk.s, &u))));
+    trace.poly("v - s^T u", &noisy);
+
+    let mut result = vec![0; message_bytes(params)];
+    for (i, x) in noisy.iter().enumerate() {
+        let bit = compress(*x, 1, q);
+        // the distance to the representatives 0 and q/2 of the bits 0 and 1
+        let distance_to_zero = std::cmp::min(*x, q - x);
+        let distance_to_half = (*x as i64 - decompress(1, 1, q) as i64).abs();
+        trace.line(format_args!("coefficient {}: {} is at distance {} from 0 and {} from {}, so the bit is {}",
+            i, x, distance_to_zero, distance_to_half, decompress(1, 1, q), bit));
+        result[i / 8] |= (bit as u8) << (i % 8);
+    }
+    trace.line(format_args!("message = {}", super::util::to_hex(&result)));
+    return result;
+}
+
+/// Encodes the public key as io::pk_to_bytes(), i.e. the coefficients of t with dt bits each
+/// in little endian bit order, followed by the seed
+pub fn pk_to_bytes(params: &ParameterSet, pk: &PublicKey) -> Vec<u8>
+{
+    let mut result = Vec::new();
+    {
+        let mut encoder = io::bytes_encode(&mut result);
+        for y in pk.t.iter().flatten() {
+            encoder.encode_bits(*y, params.dt as usize);
+        }
+        encoder.encode_bytes(&pk.seed);
+    }
+    return result;
+}
+
+/// Encodes the values of s in the NTT domain with P::encode_bits() bits each, as the secret
+/// keys of kyber.rs
+pub fn sk_to_bytes<P: RingParams>(sk: &SecretKey<P>) -> Vec<u8>
+{
+    let mut result = Vec::new();
+    {
+        let mut encoder = io::bytes_encode(&mut result);
+        for x in sk.s.iter().flat_map(|poly| poly.values()) {
+            encoder.encode_bits(x.representative_pos() as u16, P::encode_bits());
+        }
+    }
+    return result;
+}
+
+/// Encodes the ciphertext as io::ciphertext_to_bytes()
+pub fn ciphertext_to_bytes(params: &ParameterSet, c: &Ciphertext) -> Vec<u8>
+{
+    let mut result = Vec::new();
+    {
+        let mut encoder = io::bytes_encode(&mut result);
+        for y in c.u.iter().flatten() {
+            encoder.encode_bits(*y, params.du as usize);
+        }
+        for y in &c.v {
+            encoder.encode_bits(*y, params.dv as usize);
+        }
+    }
+    return result;
+}
+
+#[cfg(test)]
+use super::params;
+#[cfg(test)]
+use super::kyber::test_seed;
+#[cfg(test)]
+use super::ring_params::{ KyberParams, Toy16Params, Toy64Params };
+
+#[test]
+fn test_negacyclic_mul()
+{
+    let ring = create_ring::<Toy16Params>(&params::TOY16);
+    let q = params::TOY16.q;
+    let mut x = vec![0; 16];
+    x[1] = 1;
+    let mut x_15 = vec![0; 16];
+    x_15[15] = 1;
+    let mut minus_one = vec![0; 16];
+    minus_one[0] = q - 1;
+    assert_eq!(minus_one, to_poly(&ring.mul(&to_element(&x), &to_element(&x_15))));
+
+    let a: Poly = (0..16).map(|i| i * 17 % q).collect();
+    let b: Poly = (0..16).map(|i| (i * i + 3) % q).collect();
+    let product = |x: &Poly, y: &Poly| ring.mul(&to_element(x), &to_element(y));
+    assert_eq!(product(&a, &b), product(&b, &a));
+    let b_plus_x = to_poly(&(to_element::<Toy16Params>(&b) + &to_element(&x)));
+    assert_eq!(product(&a, &b) + &product(&a, &x), product(&a, &b_plus_x));
+}
+
+#[test]
+fn test_compress_matches_zq()
+{
+    use super::zq::{ ZqElement, CompressedZq, Q };
+    for x in 0..Q {
+        let value = ZqElement::from_perfect(x as i16);
+        assert_eq!(value.compress::<11>().data, compress(x, 11, Q));
+        assert_eq!(value.compress::<3>().data, compress(x, 3, Q));
+    }
+    for y in 0..8 {
+        assert_eq!(ZqElement::decompress(CompressedZq::<3> { data: y }).representative_pos() as u32, decompress(y, 3, Q));
+    }
+}
+
+#[cfg(test)]
+fn check_roundtrip<P: RingParams>(params: &ParameterSet)
+{
+    for i in 0..4 {
+        let message: Vec<u8> = (0..message_bytes(params)).map(|j| (j as u8).wrapping_mul(37) ^ i).collect();
+        let (sk, pk) = key_gen::<P>(params, test_seed(i), test_seed(i + 1), &mut Trace::disabled());
+        let ciphertext = encrypt::<P>(params, &pk, &message, test_seed(i + 2), &mut Trace::disabled());
+        assert_eq!(message, decrypt(params, &sk, &ciphertext, &mut Trace::disabled()), "{}", params.name);
+        assert_eq!(params.secret_key_bytes(), sk_to_bytes(&sk).len());
+        assert_eq!(params.public_key_bytes(), pk_to_bytes(params, &pk).len());
+        assert_eq!(params.ciphertext_bytes(), ciphertext_to_bytes(params, &ciphertext).len());
+    }
+}
+
+#[test]
+fn test_roundtrip()
+{
+    check_roundtrip::<Toy16Params>(&params::TOY16);
+    check_roundtrip::<Toy64Params>(&params::TOY64);
+    check_roundtrip::<KyberParams>(&params::KYBER768);
+    check_roundtrip::<KyberParams>(&params::KYBER512);
+}
+
+#[test]
+#[cfg(not(feature = "compatibility"))]
+fn test_matches_kyber()
+{
+    use super::kyber;
+
+    let params = params::KYBER768;
+    for i in 0..2 {
+        let mut message = [0; 32];
+        expand_randomness(&test_seed(i + 10)).read(&mut message);
+        let (sk, pk) = key_gen::<KyberParams>(&params, test_seed(i), test_seed(i + 1), &mut Trace::disabled());
+        let ciphertext = encrypt::<KyberParams>(&params, &pk, &message, test_seed(i + 2), &mut Trace::disabled());
+        let (expected_sk, expected_pk) = kyber::key_gen(test_seed(i), test_seed(i + 1));
+        let expected_ciphertext = kyber::encrypt(&expected_pk, message, test_seed(i + 2));
+
+        assert_eq!(io::pk_to_bytes(&expected_pk), pk_to_bytes(&params, &pk));
+        let mut expected_sk_bytes = Vec::new();
+        super::encoding::Encodable::encode(&expected_sk, &mut io::bytes_encode(&mut expected_sk_bytes));
+        assert_eq!(expected_sk_bytes, sk_to_bytes(&sk));
+        assert_eq!(io::ciphertext_to_bytes(&expected_ciphertext), ciphertext_to_bytes(&params, &ciphertext));
+    }
+}
+
+#[test]
+fn test_trace()
+{
+    let params = params::TOY16;
+    let mut output = Vec::new();
+    {
+        let mut trace = Trace::new(&mut output);
+        let (sk, pk) = key_gen::<Toy16Params>(&params, test_seed(0), test_seed(1), &mut trace);
+        let ciphertext = encrypt::<Toy16Params>(&params, &pk, &[0xA5, 0x0F], test_seed(2), &mut trace);
+        assert_eq!(vec![0xA5, 0x0F], decrypt(&params, &sk, &ciphertext, &mut trace));
+    }
+    let output = String::from_utf8(output).unwrap();
+    for name in &["A[1][0] = [", "s[1] = [", "e[0] = [", "t = compress_9(A s + e)", "t[1] = [", "r[0] = [", "e2 = [",
+        "u[0] = [", "v = [", "v - s^T u = [", "coefficient 15: ", "message = a50f"]
+    {
+        assert!(output.contains(name), "missing {}", name);
+    }
+    // each coefficient of A is in 0..q
+    let line = output.lines().find(|line| line.starts_with("A[0][0] = [")).unwrap();
+    let values: Vec<u32> = line["A[0][0] = [".len()..line.len() - 1].split(", ").map(|x| x.parse().unwrap()).collect();
+    assert_eq!(16, values.len());
+    assert!(values.iter().all(|x| *x < params.q));
+}
@@ -79,3 +79,85 @@ pub fn cartesian<I, J>(mut fst: I, snd: J) -> CartesianIterator<I, J>
         current_iter: snd
     };
 }
+
+#[allow(unused)]
+pub fn to_hex(data: &[u8]) -> String
+{
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn from_hex(data: &str) -> Vec<u8>
+{
+    assert!(data.len() % 2 == 0, "Hex string must have an even length");
+    (0..data.len() / 2).map(|i| u8::from_str_radix(&data[2 * i..2 * i + 2], 16).expect("Invalid hex string")).collect()
+}
+
+/// Returns 32 bytes from the random number generator of the operating system. All seeds
+/// that protect secrets, i.e. of keys, encapsulations, handshakes and salts, must be taken
+/// from here.
+pub fn random_seed() -> [u8; 32]
+{
+    let mut result = [0; 32];
+    getrandom::getrandom(&mut result).expect("The random number generator of the operating system failed");
+    return result;
+}
+
+/// Prompts for a line on the terminal and reads it with echo turned off, e.g. a passphrase,
+/// and returns it without the line break. Returns None if stdin is not a terminal, as the
+/// input cannot be hidden then.
+#[cfg(unix)]
+pub fn read_hidden_line(prompt: &str) -> std::io::Result<Option<String>>
+{
+    let fd = libc::STDIN_FILENO;
+    if unsafe { libc::isatty(fd) } != 1 {
+        return Ok(None);
+    }
+    eprint!("{}: ", prompt);
+    let mut original: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let mut hidden = original;
+    // still echo the line break, so that the next output starts on a new line
+    hidden.c_lflag &= !libc::ECHO;
+    hidden.c_lflag |= libc::ECHONL;
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &hidden) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let mut line = String::new();
+    let result = std::io::stdin().read_line(&mut line);
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &original) };
+    result?;
+    return Ok(Some(line.trim_end_matches(&['\r', '\n'][..]).to_owned()));
+}
+
+#[cfg(not(unix))]
+pub fn read_hidden_line(_prompt: &str) -> std::io::Result<Option<String>>
+{
+    Ok(None)
+}
+
+/// Creates a file that is only readable and writable by the current user
+pub fn create_secret_file<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<std::fs::File>
+{
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{ OpenOptionsExt, PermissionsExt };
+        options.mode(0o600);
+        let file = options.open(path)?;
+        // mode() only applies to newly created files
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        return Ok(file);
+    }
+    #[cfg(not(unix))]
+    {
+        return options.open(path);
+    }
+}
+
+#[test]
+fn test_random_seed() {
+    assert!(random_seed() != random_seed());
+}
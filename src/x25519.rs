@@ -0,0 +1,211 @@
+//! The Diffie-Hellman function X25519 from RFC 7748. The field arithmetic follows
+//! TweetNaCl: an element of GF(2^255 - 19) is stored as 16 signed limbs of 16 bits
+//! each, and all operations run in time independent of secret data.
+
+type FieldElement = [i64; 16];
+
+pub type X25519Key = [u8; 32];
+
+const BASE_POINT: X25519Key = [
+    9, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
+];
+
+/// (A - 2) / 4 where A = 486662 is the curve parameter
+const A24: FieldElement = [0xDB41, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+fn carry(x: &mut FieldElement)
+{
+    for i in 0..16 {
+        x[i] += 1 << 16;
+        let c = x[i] >> 16;
+        if i < 15 {
+            x[i + 1] += c - 1;
+        } else {
+            // 2^256 = 38 mod p
+            x[0] += 38 * (c - 1);
+        }
+        x[i] -= c << 16;
+    }
+}
+
+/// Swaps x and y if condition is 1, does nothing if it is 0
+fn conditional_swap(x: &mut FieldElement, y: &mut FieldElement, condition: i64)
+{
+    let mask = !(condition - 1);
+    for i in 0..16 {
+        let t = mask & (x[i] ^ y[i]);
+        x[i] ^= t;
+        y[i] ^= t;
+    }
+}
+
+fn add(x: &FieldElement, y: &FieldElement) -> FieldElement
+{
+    let mut result = [0; 16];
+    for i in 0..16 {
+        result[i] = x[i] + y[i];
+    }
+    return result;
+}
+
+fn sub(x: &FieldElement, y: &FieldElement) -> FieldElement
+{
+    let mut result = [0; 16];
+    for i in 0..16 {
+        result[i] = x[i] - y[i];
+    }
+    return result;
+}
+
+fn mul(x: &FieldElement, y: &FieldElement) -> FieldElement
+{
+    let mut product = [0_i64; 31];
+    for i in 0..16 {
+        for j in 0..16 {
+            product[i + j] += x[i] * y[j];
+        }
+    }
+    for i in 0..15 {
+        product[i] += 38 * product[i + 16];
+    }
+    let mut result = [0; 16];
+    result.copy_from_slice(&product[0..16]);
+    carry(&mut result);
+    carry(&mut result);
+    return result;
+}
+
+fn square(x: &FieldElement) -> FieldElement
+{
+    mul(x, x)
+}
+
+/// Calculates x^(p - 2) = x^-1
+fn invert(x: &FieldElement) -> FieldElement
+{
+    let mut result = *x;
+    for i in (0..254).rev() {
+        result = square(&result);
+        if i != 2 && i != 4 {
+            result = mul(&result, x);
+        }
+    }
+    return result;
+}
+
+fn unpack(data: &X25519Key) -> FieldElement
+{
+    let mut result = [0; 16];
+    for i in 0..16 {
+        result[i] = data[2 * i] as i64 + ((data[2 * i + 1] as i64) << 8);
+    }
+    // the most significant bit is ignored
+    result[15] &= 0x7FFF;
+    return result;
+}
+
+fn pack(x: &FieldElement) -> X25519Key
+{
+    let mut t = *x;
+    carry(&mut t);
+    carry(&mut t);
+    carry(&mut t);
+    // now t < 2p, so subtracting p at most twice yields the canonical representative
+    for _ in 0..2 {
+        let mut m = [0_i64; 16];
+        m[0] = t[0] - 0xFFED;
+        for i in 1..15 {
+            m[i] = t[i] - 0xFFFF - ((m[i - 1] >> 16) & 1);
+            m[i - 1] &= 0xFFFF;
+        }
+        m[15] = t[15] - 0x7FFF - ((m[14] >> 16) & 1);
+        let borrow = (m[15] >> 16) & 1;
+        m[14] &= 0xFFFF;
+        conditional_swap(&mut t, &mut m, 1 - borrow);
+    }
+    let mut result = [0; 32];
+    for i in 0..16 {
+        result[2 * i] = (t[i] & 0xFF) as u8;
+        result[2 * i + 1] = (t[i] >> 8) as u8;
+    }
+    return result;
+}
+
+/// Computes the u-coordinate of scalar * P, where point is the u-coordinate of P,
+/// using the Montgomery ladder
+pub fn x25519(scalar: &X25519Key, point: &X25519Key) -> X25519Key
+{
+    let mut clamped = *scalar;
+    clamped[0] &= 248;
+    clamped[31] = (clamped[31] & 127) | 64;
+
+    let x = unpack(point);
+    let mut a: FieldElement = [0; 16];
+    let mut b: FieldElement = x;
+    let mut c: FieldElement = [0; 16];
+    let mut d: FieldElement = [0; 16];
+    a[0] = 1;
+    d[0] = 1;
+    for i in (0..255).rev() {
+        let bit = ((clamped[i >> 3] >> (i & 7)) & 1) as i64;
+        conditional_swap(&mut a, &mut b, bit);
+        conditional_swap(&mut c, &mut d, bit);
+        let mut e = add(&a, &c);
+        a = sub(&a, &c);
+        c = add(&b, &d);
+        b = sub(&b, &d);
+        d = square(&e);
+        let f = square(&a);
+        a = mul(&c, &a);
+        c = mul(&b, &e);
+        e = add(&a, &c);
+        a = sub(&a, &c);
+        b = square(&a);
+        c = sub(&d, &f);
+        a = mul(&c, &A24);
+        a = add(&a, &d);
+        c = mul(&c, &a);
+        a = mul(&d, &f);
+        d = mul(&b, &x);
+        b = square(&e);
+        conditional_swap(&mut a, &mut b, bit);
+        conditional_swap(&mut c, &mut d, bit);
+    }
+    return pack(&mul(&a, &invert(&c)));
+}
+
+/// Computes the public key belonging to the given secret scalar
+pub fn x25519_base(scalar: &X25519Key) -> X25519Key
+{
+    x25519(scalar, &BASE_POINT)
+}
+
+#[cfg(test)]
+fn key_from_hex(data: &str) -> X25519Key
+{
+    let mut result = [0; 32];
+    result.copy_from_slice(&super::util::from_hex(data));
+    return result;
+}
+
+#[test]
+fn test_rfc7748_vector() {
+    let scalar = key_from_hex("a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4");
+    let point = key_from_hex("e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c");
+    let expected = key_from_hex("c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552");
+    assert_eq!(expected, x25519(&scalar, &point));
+}
+
+#[test]
+fn test_rfc7748_diffie_hellman() {
+    let alice_sk = key_from_hex("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+    let bob_sk = key_from_hex("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+    let alice_pk = x25519_base(&alice_sk);
+    let bob_pk = x25519_base(&bob_sk);
+    assert_eq!(key_from_hex("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a"), alice_pk);
+    assert_eq!(key_from_hex("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f"), bob_pk);
+    let expected = key_from_hex("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742");
+    assert_eq!(expected, x25519(&alice_sk, &bob_pk));
+    assert_eq!(expected, x25519(&bob_sk, &alice_pk));
+}
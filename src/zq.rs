@@ -3,21 +3,23 @@ use std::cmp::{ PartialEq, Eq };
 use std::fmt::{ Debug, Display, Formatter };
 use std::convert::From;
 use std::mem::swap;
+use std::marker::PhantomData;
 
 use super::encoding;
+use super::ring_params::{ RingParams, KyberParams };
 
 macro_rules! zq_arr {
     ($($num:literal),*) => {
-        [$(ZqElement { value: $num }),*]
+        [$(ZqElement { value: $num, params: PhantomData }),*]
     };
 }
 
-pub const Q: u32 = 7681;
+pub const Q: u32 = KyberParams::Q;
 
-pub const ZERO: ZqElement = ZqElement { value: 0 };
-pub const ONE: ZqElement = ZqElement { value: 1 };
+pub const ZERO: ZqElement = ZqElement { value: 0, params: PhantomData };
+pub const ONE: ZqElement = ZqElement { value: 1, params: PhantomData };
 
-/// All 512-th root of unity
+/// All 512-th root of unity, i.e. the powers of KyberParams::ROOT_OF_UNITY
 pub const UNITY_ROOTS_512: [ZqElement; 512] = zq_arr![
     1, 1704, 198, 7109, 799, 1959, 4582, 3832, 878, 5998, 
     4862, 4730, 2551, 7139, 5833, 218, 2784, 4759, 5881, 5200, 4607, 346, 5828, 7060, 1794, 7619, 1886, 
@@ -72,8 +74,9 @@ pub const REV_UNITY_ROOTS_512: [ZqElement; 256] = zq_arr![
     1800, 2922, 4897, 7463, 1848, 542, 5130, 2951, 2819, 1683, 6803, 3849, 3099, 5722, 6882, 572, 7483, 5977
 ];
 
-fn extended_euclidean_algorithm_mod_q(fst: u32, snd: u32) -> (u32, u32) 
+fn extended_euclidean_algorithm_mod_q<P: RingParams>(fst: u32, snd: u32) -> (u32, u32) 
 {
+    let q = P::Q;
     let (mut a, mut b): (u32, u32) = (fst, snd);
     let (mut sa, mut ta): (u32, u32) = (1, 0);
     let (mut sb, mut tb): (u32, u32) = (0, 1);
@@ -82,13 +85,13 @@ fn extended_euclidean_algorithm_mod_q(fst: u32, snd: u32) -> (u32, u32)
     // a = sa * fst + ta * snd mod q,
     // b = sb * fst + tb * snd mod q
     while b != 0 {
-        ta = ta + Q - ((a / b * tb) % Q);
-        sa = sa + Q - ((a / b * sb) % Q);
-        if ta >= Q {
-            ta -= Q;
+        ta = ta + q - ((a / b * tb) % q);
+        sa = sa + q - ((a / b * sb) % q);
+        if ta >= q {
+            ta -= q;
         }
-        if sa >= Q {
-            sa -= Q;
+        if sa >= q {
+            sa -= q;
         }
         a = a % b;
         swap(&mut a, &mut b);
@@ -101,20 +104,34 @@ fn extended_euclidean_algorithm_mod_q(fst: u32, snd: u32) -> (u32, u32)
 // The count of bits we write when encoding an element of Zq 
 pub const ENCODE_BITS: usize = 13;
 
-/// The type of elements of the ring Zq := Z / qZ
+/// The type of elements of the ring Zq := Z / qZ with q = P::Q
 #[derive(Clone, Copy, PartialEq, Eq)]
-pub struct ZqElement 
+pub struct Zq<P: RingParams = KyberParams>
 {
-    value: u32
+    value: u32,
+    params: PhantomData<P>
 }
 
-impl ZqElement 
+/// The elements of Zq for the modulus q = Q of Kyber
+pub type ZqElement = Zq<KyberParams>;
+
+impl<P: RingParams> Zq<P>
 {
+    pub fn zero() -> Zq<P>
+    {
+        Zq::from_perfect(0)
+    }
+
+    pub fn one() -> Zq<P>
+    {
+        Zq::from_perfect(1)
+    }
+
     // Raises this element to the power of a natural number
-    pub fn pow(self, mut rhs: usize) -> ZqElement
+    pub fn pow(self, mut rhs: usize) -> Zq<P>
     {
-        let mut power: ZqElement = self;
-        let mut result: ZqElement = ONE;
+        let mut power: Zq<P> = self;
+        let mut result: Zq<P> = Zq::one();
         while rhs != 0 {
             if rhs & 1 == 1 {
                 result *= power;
@@ -134,23 +151,35 @@ impl ZqElement
     // contract: Zq::From(x.representative_posneg()) == x
     pub fn representative_posneg(self) -> i16
     {
-        if self.value > Q/2 {
-            -(self.value as i16)
+        if self.value > P::Q/2 {
+            self.value as i16 - P::Q as i16
         } else {
             self.value as i16
         }
     }
 
-    pub fn from_perfect(value: i16) -> ZqElement
+    pub fn from_perfect(value: i16) -> Zq<P>
     {
-        debug_assert!(value >= 0 && (value as u32) < Q, "Got value {} which is not in range 0..{}", value, Q);
-        ZqElement {
-            value: value as u32
+        debug_assert!(value >= 0 && (value as u32) < P::Q, "Got value {} which is not in range 0..{}", value, P::Q);
+        Zq {
+            value: value as u32,
+            params: PhantomData
         }
     }
+
+    /// Reads a value written with P::encode_bits() bits, and returns None if the input is
+    /// too short or the value is not in 0..q
+    pub fn try_decode<T: encoding::Decoder>(data: &mut T) -> Option<Zq<P>>
+    {
+        let value = data.read_bits(P::encode_bits())?;
+        if value as u32 >= P::Q {
+            return None;
+        }
+        return Some(Zq::from_perfect(value as i16));
+    }
 }
 
-impl Debug for ZqElement 
+impl<P: RingParams> Debug for Zq<P>
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
     {
@@ -158,7 +187,7 @@ impl Debug for ZqElement
     }
 }
 
-impl Display for ZqElement 
+impl<P: RingParams> Display for Zq<P>
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result
     {
@@ -166,119 +195,119 @@ impl Display for ZqElement
     }
 }
 
-impl Add<ZqElement> for ZqElement
+impl<P: RingParams> Add<Zq<P>> for Zq<P>
 {
-    type Output = ZqElement;
+    type Output = Zq<P>;
 
     #[inline(always)]
-    fn add(mut self, rhs: ZqElement) -> Self::Output
+    fn add(mut self, rhs: Zq<P>) -> Self::Output
     {
         self += rhs;
         return self;
     }
 }
 
-impl Mul<ZqElement> for ZqElement
+impl<P: RingParams> Mul<Zq<P>> for Zq<P>
 {
-    type Output = ZqElement;
+    type Output = Zq<P>;
 
     #[inline(always)]
-    fn mul(mut self, rhs: ZqElement) -> Self::Output
+    fn mul(mut self, rhs: Zq<P>) -> Self::Output
     {
         self *= rhs;
         return self;
     }
 }
 
-impl Sub<ZqElement> for ZqElement
+impl<P: RingParams> Sub<Zq<P>> for Zq<P>
 {
-    type Output = ZqElement;
+    type Output = Zq<P>;
 
     #[inline(always)]
-    fn sub(mut self, rhs: ZqElement) -> Self::Output
+    fn sub(mut self, rhs: Zq<P>) -> Self::Output
     {
         self -= rhs;
         return self;
     }
 }
 
-impl Div<ZqElement> for ZqElement
+impl<P: RingParams> Div<Zq<P>> for Zq<P>
 {
-    type Output = ZqElement;
+    type Output = Zq<P>;
 
     #[inline(always)]
-    fn div(mut self, rhs: ZqElement) -> Self::Output
+    fn div(mut self, rhs: Zq<P>) -> Self::Output
     {
         self /= rhs;
         return self;
     }
 }
 
-impl Neg for ZqElement
+impl<P: RingParams> Neg for Zq<P>
 {
-    type Output = ZqElement;
+    type Output = Zq<P>;
 
     #[inline(always)]
     fn neg(self) -> Self::Output
     {
-        ZqElement {
-            value: Q - self.value
+        Zq {
+            value: if self.value == 0 { 0 } else { P::Q - self.value },
+            params: PhantomData
         }
     }
 }
 
-impl AddAssign<ZqElement> for ZqElement
+impl<P: RingParams> AddAssign<Zq<P>> for Zq<P>
 {
     #[inline(always)]
-    fn add_assign(&mut self, rhs: ZqElement)
+    fn add_assign(&mut self, rhs: Zq<P>)
     {
         self.value = self.value + rhs.value;
-        if self.value >= Q {
-            self.value -= Q;
+        if self.value >= P::Q {
+            self.value -= P::Q;
         }
     }
 }
 
-impl MulAssign<ZqElement> for ZqElement
+impl<P: RingParams> MulAssign<Zq<P>> for Zq<P>
 {
     #[inline(always)]
-    fn mul_assign(&mut self, rhs: ZqElement)
+    fn mul_assign(&mut self, rhs: Zq<P>)
     {
-        self.value = self.value * rhs.value % Q
+        self.value = self.value * rhs.value % P::Q
     }
 }
 
-impl SubAssign<ZqElement> for ZqElement
+impl<P: RingParams> SubAssign<Zq<P>> for Zq<P>
 {
     #[inline(always)]
-    fn sub_assign(&mut self, rhs: ZqElement)
+    fn sub_assign(&mut self, rhs: Zq<P>)
     {
-        self.value = self.value + Q - rhs.value;
-        if self.value >= Q {
-            self.value -= Q;
+        self.value = self.value + P::Q - rhs.value;
+        if self.value >= P::Q {
+            self.value -= P::Q;
         }
     }
 }
 
-impl DivAssign<ZqElement> for ZqElement
+impl<P: RingParams> DivAssign<Zq<P>> for Zq<P>
 {
     #[inline(always)]
-    fn div_assign(&mut self, rhs: ZqElement)
+    fn div_assign(&mut self, rhs: Zq<P>)
     {
-        self.value = (self.value * extended_euclidean_algorithm_mod_q(Q, rhs.value).1) % Q;
+        self.value = (self.value * extended_euclidean_algorithm_mod_q::<P>(P::Q, rhs.value).1) % P::Q;
     }
 }
 
-impl From<i16> for ZqElement
+impl<P: RingParams> From<i16> for Zq<P>
 {
     // Returns the equivalence class of the argument in Zq
     #[inline(always)]
-    fn from(value: i16) -> ZqElement
+    fn from(value: i16) -> Zq<P>
     {
-        // A i16 is positive for sure after adding 5 * Q > 32768
-        // and this addition will not overflow as i32
-        ZqElement {
-            value: ((value as i32 + 5 * Q as i32) % Q as i32) as u32 
+        Zq {
+            value: (value as i32).rem_euclid(P::Q as i32) as u32,
+            params: PhantomData
         }
     }
 }
@@ -296,11 +325,11 @@ impl<const D: u16> encoding::Encodable for CompressedZq<D>
         encoder.encode_bits(self.data, D as usize);
     }
 
-    fn decode<T: encoding::Decoder>(data: &mut T) -> Self
+    fn try_decode<T: encoding::Decoder>(data: &mut T) -> Option<Self>
     {
-        CompressedZq {
-            data: data.read_bits(D as usize).expect("Input too short")
-        }
+        Some(CompressedZq {
+            data: data.read_bits(D as usize)?
+        })
     }
 }
 
@@ -312,28 +341,26 @@ impl<const D: u16> Debug for CompressedZq<D>
     }
 }
 
-impl ZqElement 
+impl<P: RingParams> Zq<P>
 {
     // Returns the element y in 0, ..., 2^d - 1 such
     // that q/2^n * y is nearest to x.representative_pos()
     pub fn compress<const D: u16>(self) -> CompressedZq<D>
     {
-        // this floating point approach always leads to the right result:
-        // for each x, n, |0.5 - (x * n / 7681) mod 1| >= |0.5 - (x * 1 / 7681) mod 1|
-        // >= |0.5 - (3840 / 7681) mod 1| >= 6.509569066531773E-5 
-        // > (error in floating point representation of 1/7681) * 7681
-        let n = (1 << D) as f32;
+        // round(x * 2^d / q) = floor((x * 2^(d + 1) + q) / 2q), and this is never
+        // a tie as q is odd; everything fits into u32 as x < 2^15 and d < 16
         CompressedZq {
-            data: (self.representative_pos() as f32 * n / Q as f32).round() as u16 % (1 << D)
+            data: (((self.value << (D + 1)) + P::Q) / (2 * P::Q)) as u16 % (1 << D)
         }
     }
     
     // Returns the element y of Zq for which
     // y.representative_pos() is nearest to 2^d/q * x 
-    pub fn decompress<const D: u16>(x: CompressedZq<D>) -> ZqElement
+    pub fn decompress<const D: u16>(x: CompressedZq<D>) -> Zq<P>
     {
-        let n = (1 << D) as f32;
-        ZqElement::from((x.data as f32 * Q as f32 / n).round() as i16)
+        // round(y * q / 2^d) with ties rounded up, i.e. floor((2yq + 2^d) / 2^(d + 1))
+        let value = ((x.data as u32 * P::Q * 2) + (1 << D)) >> (D + 1);
+        Zq::from_perfect((value % P::Q) as i16)
     }
 }
 